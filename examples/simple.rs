@@ -6,6 +6,16 @@ use btree::BTree;
 fn main() {
     let dir = PathBuf::from(env::var("BTREE_FILENAME").expect("BTREE_FILENAME not set"));
 
-    let mut tree: BTree<String, String> = BTree::new(dir, 16).expect("Could not create BTree.");
-    tree.insert(String::from("a"), String::from("hello"));
+    let mut tree: BTree<String, String> =
+        BTree::new(dir.clone(), 17).expect("Could not create BTree.");
+    tree.insert(String::from("a"), String::from("hello"))
+        .expect("Could not insert into BTree.");
+    tree.flush().expect("Could not flush BTree.");
+    drop(tree);
+
+    let mut tree: BTree<String, String> = BTree::open(dir).expect("Could not open BTree.");
+    let value = tree
+        .get(&String::from("a"))
+        .expect("Could not read from BTree.");
+    println!("a = {:?}", value);
 }
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::storage::{FileData, Storage};
+
+/// A change to a file, waiting for the background thread to make it.
+enum Op {
+    Write(PathBuf, Vec<u8>),
+    Rename(PathBuf, PathBuf),
+    Remove(PathBuf),
+    Sync(PathBuf),
+}
+
+impl Op {
+    /// The files the change touches, which can't be read until it has been made.
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Op::Write(path, _) | Op::Remove(path) | Op::Sync(path) => vec![path],
+            Op::Rename(from, to) => vec![from, to],
+        }
+    }
+
+    fn apply(&self, storage: &dyn Storage) -> io::Result<()> {
+        match self {
+            Op::Write(path, data) => storage.write(path, data),
+            Op::Rename(from, to) => storage.rename(from, to),
+            Op::Remove(path) => storage.remove(path),
+            Op::Sync(path) => storage.sync(path),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    /// How many queued changes touch each file.
+    paths: HashMap<PathBuf, usize>,
+    queued: usize,
+    /// The first change the background thread failed to make, for every call after it to
+    /// report.
+    error: Option<io::Error>,
+}
+
+impl Pending {
+    /// Report the first change the background thread failed to make, if any.
+    fn error(&self) -> io::Result<()> {
+        match &self.error {
+            Some(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What the tree and the background thread share.
+#[derive(Default)]
+struct Shared {
+    pending: Mutex<Pending>,
+    /// Notified each time the background thread finishes a change.
+    done: Condvar,
+}
+
+/// Storage that hands every change to its files to a thread of its own, which makes them in
+/// order on `inner`, so that the tree doesn't wait for writes and syncs as it goes. A read of
+/// a file with changes still queued waits for them, and a barrier waits for everything queued,
+/// so nothing the tree reads or flushes ever misses a change.
+///
+/// A change the thread fails to make is reported by every call to the storage from then on, and
+/// none of the changes queued after it are made. They were queued on the understanding that it
+/// had been, so making them could leave the files pointing at ones that were never written.
+/// The tree has to be opened again to carry on from what was last flushed.
+pub(crate) struct BackgroundStorage {
+    inner: Arc<dyn Storage>,
+    shared: Arc<Shared>,
+    /// `None` once the storage is dropped, which tells the thread to stop.
+    sender: Option<Sender<Op>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundStorage {
+    /// `inner`, written to from a background thread if `background` is set.
+    pub(crate) fn wrap(inner: Arc<dyn Storage>, background: bool) -> Arc<dyn Storage> {
+        if !background {
+            return inner;
+        }
+
+        let shared = Arc::new(Shared::default());
+        let (sender, receiver) = mpsc::channel::<Op>();
+        let thread = {
+            let inner = inner.clone();
+            let shared = shared.clone();
+            thread::spawn(move || {
+                for op in receiver {
+                    let failed = shared.pending.lock().unwrap().error.is_some();
+                    let result = if failed { Ok(()) } else { op.apply(&*inner) };
+                    let mut pending = shared.pending.lock().unwrap();
+                    for path in op.paths() {
+                        let count = pending.paths.get_mut(path).unwrap();
+                        *count -= 1;
+                        if *count == 0 {
+                            pending.paths.remove(path);
+                        }
+                    }
+                    pending.queued -= 1;
+                    if let Err(e) = result {
+                        pending.error.get_or_insert(e);
+                    }
+                    shared.done.notify_all();
+                }
+            })
+        };
+
+        Arc::new(Self {
+            inner,
+            shared,
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.shared.pending.lock().unwrap().error()
+    }
+
+    fn queue(&self, op: Op) -> io::Result<()> {
+        let mut pending = self.shared.pending.lock().unwrap();
+        pending.error()?;
+        for path in op.paths() {
+            *pending.paths.entry(path.to_path_buf()).or_default() += 1;
+        }
+        pending.queued += 1;
+        // Queued with the lock still held, so the thread can't finish the change before it is
+        // counted.
+        self.sender.as_ref().unwrap().send(op).unwrap();
+
+        Ok(())
+    }
+
+    /// Wait until `done` holds of what is still queued, then report any change that failed.
+    fn wait(&self, done: impl Fn(&Pending) -> bool) -> io::Result<()> {
+        let pending = self.shared.pending.lock().unwrap();
+        let pending = self.shared.done.wait_while(pending, |p| !done(p)).unwrap();
+
+        pending.error()
+    }
+
+    fn wait_for(&self, path: &Path) -> io::Result<()> {
+        self.wait(|pending| !pending.paths.contains_key(path))
+    }
+
+    fn wait_for_all(&self) -> io::Result<()> {
+        self.wait(|pending| pending.queued == 0)
+    }
+}
+
+impl Storage for BackgroundStorage {
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.check()?;
+        self.inner.create_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.wait_for(path)?;
+        self.inner.read(path)
+    }
+
+    fn read_data(&self, path: &Path) -> io::Result<FileData> {
+        self.wait_for(path)?;
+        self.inner.read_data(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.queue(Op::Write(path.to_path_buf(), data.to_vec()))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.queue(Op::Rename(from.to_path_buf(), to.to_path_buf()))
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.queue(Op::Remove(path.to_path_buf()))
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.wait_for_all()?;
+        self.inner.list(path)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.queue(Op::Sync(path.to_path_buf()))
+    }
+
+    fn link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.wait_for_all()?;
+        self.inner.link(from, to)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        self.wait_for(path)?;
+        self.inner.size(path)
+    }
+
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>> {
+        self.check()?;
+        self.inner.lock(path, shared)
+    }
+
+    fn barrier(&self) -> io::Result<()> {
+        self.wait_for_all()?;
+        self.inner.barrier()
+    }
+}
+
+impl Drop for BackgroundStorage {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread finish what is queued and stop.
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{read_node_file, write_node_file, BTree, Compare, Error, BLOOM_FILE};
+
+/// How many bits the filter sets aside for each key it is sized for, which with `HASHES` bits
+/// set per key makes about one in a hundred missing keys look present.
+const BITS_PER_KEY: usize = 10;
+const HASHES: u64 = 7;
+/// The fewest keys a filter is sized for, so that a small tree doesn't rebuild its filter
+/// every few inserts while it grows.
+const MIN_KEYS: usize = 1024;
+/// The most keys a filter is sized for, which keeps it to 80MiB. A filter this size is never
+/// rebuilt to a larger one, and lets more missing keys through as the tree grows past it.
+const MAX_KEYS: usize = 1 << 26;
+
+/// A Bloom filter over every key in a tree, which can tell for sure that a key isn't there
+/// without looking through any node.
+///
+/// Keys can only be added, so a removed key may still look present until the filter is rebuilt,
+/// which costs lookups of it a search but never gets one wrong. Keys are told apart by their
+/// MessagePack encoding, so keys the tree's order treats as equal have to encode the same.
+#[derive(Deserialize, Serialize)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    /// How many keys have been added, counting any added more than once.
+    added: usize,
+    /// How many keys the filter was sized for, past which it is rebuilt to a larger size.
+    sized_for: usize,
+    /// Matched against `Meta::bloom_filter` when the filter is read back, to tell whether the
+    /// tree changed after it was last written.
+    generation: u64,
+    /// Whether keys have been added since the filter was last written.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl BloomFilter {
+    /// A filter with room for `keys` keys, twice over, so that it takes a while to fill, as far
+    /// as `MAX_KEYS` allows.
+    fn new(keys: usize, generation: u64) -> Self {
+        let sized_for = keys.saturating_mul(2).clamp(MIN_KEYS, MAX_KEYS);
+        Self {
+            bits: vec![0; (sized_for * BITS_PER_KEY).div_ceil(64)],
+            added: 0,
+            sized_for,
+            generation,
+            dirty: true,
+        }
+    }
+
+    pub(crate) fn insert<Q>(&mut self, key: &Q)
+    where
+        Q: Serialize + ?Sized,
+    {
+        for bit in self.bits_of(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.added += 1;
+        self.dirty = true;
+    }
+
+    /// Whether `key` may be in the tree. If not, it isn't.
+    pub(crate) fn might_contain<Q>(&self, key: &Q) -> bool
+    where
+        Q: Serialize + ?Sized,
+    {
+        self.bits_of(key)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Whether more keys have been added than the filter was sized for, and it could be sized
+    /// for more.
+    pub(crate) fn is_full(&self) -> bool {
+        self.added > self.sized_for && self.sized_for < MAX_KEYS
+    }
+
+    /// Whether the filter will fill up before it has taken in `keys` keys in all, and could be
+    /// sized for more of them.
+    fn fills_before(&self, keys: usize) -> bool {
+        self.sized_for < keys.min(MAX_KEYS)
+    }
+
+    /// The generation the meta file has to name for the filter to be read back, which is the
+    /// next one if the filter has changed since it was written.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation + u64::from(self.dirty)
+    }
+
+    /// The bits a key sets, picked by double hashing with the two halves of the key's hash.
+    fn bits_of<Q>(&self, key: &Q) -> impl Iterator<Item = usize>
+    where
+        Q: Serialize + ?Sized,
+    {
+        let hash = fnv1a(&rmp_serde::to_vec(key).expect("keys can be encoded"));
+        let (first, second) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        let len = self.bits.len() as u64 * 64;
+
+        (0..HASHES).map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % len) as usize)
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`. Filters outlive the process that writes them, so the hash
+/// can't change between builds the way `std`'s default hasher may.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl<K, V, C> BTree<K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    /// Give a new, empty tree a filter.
+    pub(crate) fn start_bloom_filter(&mut self) -> Result<(), Error> {
+        self.bloom = Some(BloomFilter::new(0, 0));
+        self.flush()
+    }
+
+    /// Add `key` to the filter, if there is one, ahead of inserting it.
+    pub(crate) fn note_key(&mut self, key: &K) {
+        if let Some(filter) = self.bloom.as_mut() {
+            filter.insert(key);
+        }
+    }
+
+    /// Whether `key` may be in the tree, as far as the filter can tell. Without a filter, any
+    /// key may be.
+    pub(crate) fn might_contain<Q>(&self, key: &Q) -> bool
+    where
+        Q: Serialize + ?Sized,
+    {
+        self.bloom
+            .as_ref()
+            .is_none_or(|filter| filter.might_contain(key))
+    }
+
+    /// Empty the filter, if there is one, and size it for `keys` keys, for when every key is
+    /// about to be added again.
+    pub(crate) fn reset_bloom_filter(&mut self, keys: usize) {
+        if let Some(filter) = self.bloom.as_mut() {
+            *filter = BloomFilter::new(keys, filter.generation);
+        }
+    }
+
+    /// Replace the filter with one built from every key in the tree, sized for how many there
+    /// are, which also forgets the keys that have been removed since.
+    pub(crate) fn rebuild_bloom_filter(&mut self) -> Result<(), Error> {
+        self.rebuild_bloom_filter_for(self.len)
+    }
+
+    /// Rebuild the filter now, sized for the `keys` keys the tree is about to grow to, if it
+    /// would fill up on the way there, so that it is rebuilt once rather than each time it
+    /// fills.
+    pub(crate) fn reserve_bloom_filter(&mut self, keys: usize) -> Result<(), Error> {
+        if self
+            .bloom
+            .as_ref()
+            .is_some_and(|filter| filter.fills_before(keys))
+        {
+            self.rebuild_bloom_filter_for(keys)?;
+        }
+
+        Ok(())
+    }
+
+    fn rebuild_bloom_filter_for(&mut self, keys: usize) -> Result<(), Error> {
+        self.reset_bloom_filter(keys);
+        // The filter is taken out of the tree while every node is looked up, and put back even
+        // if one can't be read.
+        let mut filter = match self.bloom.take() {
+            Some(filter) => filter,
+            None => return Ok(()),
+        };
+        let added = self.add_every_key(&mut filter);
+        self.bloom = Some(filter);
+
+        added
+    }
+
+    fn add_every_key(&mut self, filter: &mut BloomFilter) -> Result<(), Error> {
+        // `None` stands for the root.
+        let mut stack = vec![None];
+        while let Some(path) = stack.pop() {
+            let node = self.node(path.as_ref())?;
+            for key in &node.data.keys {
+                filter.insert(key);
+            }
+            stack.extend(node.data.children.iter().flatten().cloned().map(Some));
+        }
+
+        Ok(())
+    }
+
+    /// Rebuild the filter if it has taken in more keys than it was sized for, before it starts
+    /// to let too many missing keys through.
+    pub(crate) fn grow_bloom_filter(&mut self) -> Result<(), Error> {
+        if self.bloom.as_ref().is_some_and(BloomFilter::is_full) {
+            self.rebuild_bloom_filter()?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the filter of a tree whose meta file names `generation`, or build it again if the
+    /// file is missing, damaged or from another generation, as after a crash.
+    pub(crate) fn load_bloom_filter(&mut self, generation: u64) -> Result<(), Error> {
+        let path = self.tree_file(BLOOM_FILE);
+        match read_node_file::<BloomFilter>(&*self.storage, self.encoding, &path) {
+            Ok(filter) if filter.generation == generation => {
+                self.bloom = Some(filter);
+                Ok(())
+            }
+            _ => {
+                self.bloom = Some(BloomFilter::new(0, generation));
+                self.rebuild_bloom_filter()
+            }
+        }
+    }
+
+    /// Write the filter out if it has changed, which has to happen before the meta file naming
+    /// its new generation is.
+    pub(crate) fn save_bloom_filter(&mut self) -> Result<(), Error> {
+        let path = self.tree_file(BLOOM_FILE);
+        if let Some(filter) = self.bloom.as_mut().filter(|filter| filter.dirty) {
+            filter.generation += 1;
+            filter.dirty = false;
+            write_node_file(&*self.storage, self.encoding, &path, &*filter)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::background::BackgroundStorage;
+use crate::cache::DEFAULT_CACHE_CAPACITY;
+use crate::crypto::Key;
+use crate::durability::DurableStorage;
+use crate::{
+    repair, tree_file, BTree, CodecKind, Durability, Encoding, Error, FileStorage, Naming, Natural,
+    RepairReport, Storage, DEFAULT_MERGE_THRESHOLD, META_FILE,
+};
+
+/// The node capacity of trees created by a builder that isn't given one.
+pub(crate) const DEFAULT_CAPACITY: usize = 63;
+
+/// Opens or creates a `BTree` with settings beyond those `BTree::new` and `BTree::open` take.
+pub struct BTreeBuilder<K, V> {
+    backing_dir: PathBuf,
+    capacity: usize,
+    merge_threshold: f32,
+    cache_capacity: usize,
+    cache_budget: Option<usize>,
+    create_if_missing: bool,
+    write_ahead_log: bool,
+    copy_on_write: bool,
+    version_history: usize,
+    bloom_filter: bool,
+    compression: bool,
+    encryption_key: Option<Key>,
+    external_values: Option<usize>,
+    codec: Option<CodecKind>,
+    durability: Durability,
+    background_flush: bool,
+    mmap: bool,
+    storage: Arc<dyn Storage>,
+    file_prefix: String,
+    file_extension: Option<String>,
+    namespace: Option<String>,
+    deterministic_names: bool,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> BTreeBuilder<K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    pub fn new(backing_dir: PathBuf) -> Self {
+        Self {
+            backing_dir,
+            capacity: DEFAULT_CAPACITY,
+            merge_threshold: DEFAULT_MERGE_THRESHOLD,
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            cache_budget: None,
+            create_if_missing: true,
+            write_ahead_log: false,
+            copy_on_write: false,
+            version_history: 0,
+            bloom_filter: false,
+            compression: false,
+            encryption_key: None,
+            external_values: None,
+            codec: None,
+            durability: Durability::None,
+            background_flush: false,
+            mmap: false,
+            storage: Arc::new(FileStorage),
+            file_prefix: String::new(),
+            file_extension: None,
+            namespace: None,
+            deterministic_names: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// The capacity of each node, if the tree has to be created. An existing tree keeps the
+    /// capacity it was created with.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// How empty a node may get as entries are removed from it, as a fraction of its capacity,
+    /// if the tree has to be created. A node that would drop below it borrows a key from a
+    /// sibling, or failing that merges with one. Lower thresholds merge less often and leave
+    /// more of each node unused, and the threshold must be above 0 and at most 0.5, which it is
+    /// unless given. However low it is, every node but the root keeps at least one key. An
+    /// existing tree keeps the threshold it was created with.
+    pub fn merge_threshold(mut self, merge_threshold: f32) -> Self {
+        self.merge_threshold = merge_threshold;
+        self
+    }
+
+    /// See `BTree::set_cache_capacity`.
+    pub fn cache_capacity(mut self, cache_capacity: usize) -> Self {
+        self.cache_capacity = cache_capacity;
+        self
+    }
+
+    /// See `BTree::set_cache_budget`.
+    pub fn cache_budget(mut self, bytes: usize) -> Self {
+        self.cache_budget = Some(bytes);
+        self
+    }
+
+    /// Whether to create a new tree if `backing_dir` doesn't exist, rather than fail. On by
+    /// default.
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.create_if_missing = create_if_missing;
+        self
+    }
+
+    /// See `BTree::set_write_ahead_log`.
+    pub fn write_ahead_log(mut self, write_ahead_log: bool) -> Self {
+        self.write_ahead_log = write_ahead_log;
+        self
+    }
+
+    /// See `BTree::set_copy_on_write`.
+    pub fn copy_on_write(mut self, copy_on_write: bool) -> Self {
+        self.copy_on_write = copy_on_write;
+        self
+    }
+
+    /// See `BTree::set_version_history`.
+    pub fn version_history(mut self, versions: usize) -> Self {
+        self.version_history = versions;
+        self
+    }
+
+    /// Whether to keep a Bloom filter over every key, if the tree has to be created, which `get`
+    /// and `contains_key` check before searching, so that most lookups of a missing key read no
+    /// nodes at all. Every insert adds its key to the filter, which is written out by `flush`
+    /// and `close`, though not as each change is committed with the write-ahead log or
+    /// copy-on-write, and the filter is rebuilt from every node whenever it fills up, by
+    /// `compact`, and after a crash. Keys that compare equal have to serialize the same. An
+    /// existing tree keeps the setting it was created with.
+    #[cfg(feature = "bloom")]
+    pub fn bloom_filter(mut self, bloom_filter: bool) -> Self {
+        self.bloom_filter = bloom_filter;
+        self
+    }
+
+    /// Whether the files of each node are compressed with zstd, if the tree has to be created.
+    /// This suits values that compress well and don't change often, since every save of a node
+    /// compresses it again. An existing tree keeps the setting it was created with.
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Encrypt the files of each node with ChaCha20-Poly1305 under `key`, if the tree has to be
+    /// created, and decrypt them with it either way. An encrypted tree records that it is, but
+    /// not its key, so it can only be opened again with the same key, and fails with
+    /// `Error::KeyRequired` without one. A wrong key fails with `NodeError::Authentication`,
+    /// as does a file that has been tampered with or swapped for another of the tree's files,
+    /// rather than reading back garbage. Only the nodes, their values, the Bloom filter and the
+    /// write-ahead log are encrypted: the meta file, which says which node is the root and how
+    /// many entries there are, is only signed, so it can be read but not changed. Nothing
+    /// stops a file being put back as it was in an older version of the tree, though. An
+    /// existing tree that isn't encrypted fails to open with `Error::NotEncrypted`.
+    #[cfg(feature = "encryption")]
+    pub fn encryption_key(mut self, key: [u8; 32]) -> Self {
+        self.encryption_key = Some(Key(key));
+        self
+    }
+
+    /// The most bytes a value can encode to and still be kept with the other values of its
+    /// node, if the tree has to be created. A larger value is written to a file of its own,
+    /// which its node's values file names, so that loading a node for its other values doesn't
+    /// read it too and a node's files stay small however large its values get. It is read back
+    /// by `get` like any other. Saving a node again only writes out the large values that have
+    /// changed since its values were read or written. An existing tree keeps the setting it was
+    /// created with, and without one keeps every value with its node.
+    pub fn external_values(mut self, threshold: usize) -> Self {
+        self.external_values = Some(threshold);
+        self
+    }
+
+    /// The format to write each node's files in, if the tree has to be created, which is
+    /// MessagePack unless given. An existing tree keeps the format it was created with, and
+    /// fails to open if it isn't this one.
+    pub fn codec(mut self, codec: CodecKind) -> Self {
+        self.codec = Some(codec);
+        self
+    }
+
+    /// When the tree syncs the files it writes. `Durability::None` unless given.
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Whether the tree hands what it writes to a thread of its own to write out and sync, so
+    /// that inserts and removes don't wait on the disk. `flush` and `close` still wait for
+    /// everything written so far, and so do reads of a file that hasn't been written yet. A
+    /// write that fails in the background is reported by every call that goes to storage from
+    /// then on, and nothing written after it is written out, so the tree has to be opened again
+    /// to carry on from what was last flushed. Off by default.
+    pub fn background_flush(mut self, background_flush: bool) -> Self {
+        self.background_flush = background_flush;
+        self
+    }
+
+    /// Whether to read node files by mapping them into memory rather than copying them out, by
+    /// keeping the tree's files in `MmapStorage`, which takes the place of any storage given to
+    /// `storage`. Only safe while nothing but the tree writes to its files, as `MmapStorage`
+    /// explains. Off by default.
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Where the tree keeps its files, which is `FileStorage` unless given. Whether the tree
+    /// exists already is still decided by whether `backing_dir` exists on disk.
+    pub fn storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// A prefix for the name of each node's files, if the tree has to be created, to tell them
+    /// apart from other files in a shared directory. An existing tree keeps the names it was
+    /// created with.
+    pub fn file_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.file_prefix = prefix.into();
+        self
+    }
+
+    /// An extension for each node's files, such as `node` for `root.node`, if the tree has to
+    /// be created. Node files have none unless given. An existing tree keeps the names it was
+    /// created with.
+    pub fn file_extension(mut self, extension: impl Into<String>) -> Self {
+        self.file_extension = Some(extension.into());
+        self
+    }
+
+    /// Whether new nodes are named in sequence, `node-0000001` and on, rather than by random
+    /// UUIDs, if the tree has to be created, so that the same changes to the same tree always
+    /// leave the same files behind, as reproducing a failing test or fuzzing run wants. The next
+    /// number is recorded in the meta file, so a tree reopened goes on from there. An existing
+    /// tree keeps the names it was created with. Files of values moved out by `external_values`
+    /// are numbered within their node either way, so they are named the same each time too.
+    pub fn deterministic_names(mut self, deterministic_names: bool) -> Self {
+        self.deterministic_names = deterministic_names;
+        self
+    }
+
+    /// Keep the tree in a namespace of `backing_dir`, so that other trees can share the
+    /// directory in namespaces of their own. The tree's files are all named after it, and its
+    /// `gc` leaves every other tree's files alone. Unlike the other naming settings, this picks
+    /// out which tree to open as well as naming a new one.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Repair the tree in `backing_dir`, or in its namespace if one was given, as `BTree::repair`
+    /// does, leaving every other tree in the directory alone. None of the other settings are
+    /// used.
+    pub fn repair(self) -> Result<RepairReport, Error> {
+        repair::repair::<K, V>(self.backing_dir, self.namespace.as_deref())
+    }
+
+    /// Open the tree in `backing_dir`, creating it first if it doesn't exist and that is
+    /// allowed.
+    pub fn build(self) -> Result<BTree<K, V>, Error> {
+        let storage: Arc<dyn Storage> = match self.mmap {
+            #[cfg(feature = "mmap")]
+            true => Arc::new(crate::MmapStorage),
+            _ => self.storage,
+        };
+        let storage = DurableStorage::wrap(storage, self.durability);
+        let storage = BackgroundStorage::wrap(storage, self.background_flush);
+        let exists = match self.namespace.as_deref() {
+            Some(namespace) => tree_file(&self.backing_dir, META_FILE, Some(namespace)).exists(),
+            None => self.backing_dir.exists(),
+        };
+        let mut tree = if self.create_if_missing && !exists {
+            let encoding = Encoding {
+                codec: self.codec.unwrap_or_default(),
+                compressed: self.compression,
+                encrypted: self.encryption_key.is_some(),
+                key: self.encryption_key,
+                external_values: self.external_values,
+            };
+            let naming = Naming::new(
+                self.file_prefix,
+                self.namespace,
+                self.file_extension,
+                self.deterministic_names,
+            )?;
+            let mut tree = BTree::create(
+                storage,
+                self.backing_dir,
+                self.capacity,
+                self.merge_threshold,
+                Natural,
+                encoding,
+                naming,
+            )?;
+            if self.bloom_filter {
+                tree.start_bloom_filter()?;
+            }
+            tree
+        } else {
+            let namespace = self.namespace.as_deref();
+            let key = self.encryption_key;
+            let tree = BTree::open_in(storage, self.backing_dir, namespace, Natural, key)?;
+            let found = tree.encoding.codec;
+            match self.codec {
+                Some(expected) if expected != found => {
+                    return Err(Error::CodecMismatch { found, expected })
+                }
+                _ => tree,
+            }
+        };
+        tree.set_cache_capacity(self.cache_capacity)?;
+        tree.set_cache_budget(self.cache_budget)?;
+        tree.set_write_ahead_log(self.write_ahead_log)?;
+        tree.set_copy_on_write(self.copy_on_write)?;
+        tree.set_version_history(self.version_history)?;
+
+        Ok(tree)
+    }
+}
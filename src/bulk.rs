@@ -0,0 +1,160 @@
+use std::cmp::Ordering;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Compare, Error, Node, NodeData, NodeRef};
+
+/// Builds a tree from the leaves up out of entries that arrive in increasing order of key.
+///
+/// Only the rightmost node of each level is held while it fills up. Once full, it is handed to
+/// the node cache, and the next entry becomes a separator in the level above instead, with a new
+/// node started to its right.
+struct Loader<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+    /// The node being filled on each level, from the leaves up, along with the name it will be
+    /// saved under. The last child of each internal node here is the node below it.
+    spine: Vec<(NodeRef, NodeData<K, V>)>,
+    /// How many keys a node takes before the next one is started. One short of full, so the
+    /// first insert into each node doesn't have to split it.
+    fill: usize,
+}
+
+/// Fill `tree`, which must be empty, with the entries of `sorted`, failing if they aren't in
+/// strictly increasing order of key.
+pub(crate) fn load<K, V, C, I>(tree: &mut BTree<K, V, C>, sorted: I) -> Result<(), Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+    I: IntoIterator<Item = (K, V)>,
+{
+    let capacity = tree.capacity;
+    let leaf =
+        BTree::<K, V, C>::new_node_name(&mut tree.free_nodes, &tree.backing_dir, &mut tree.naming);
+    let mut loader = Loader {
+        tree,
+        spine: vec![(leaf, NodeData::new(capacity))],
+        fill: capacity - 1,
+    };
+    for (key, value) in sorted {
+        let cmp = &loader.tree.cmp;
+        if loader
+            .last_key()
+            .is_some_and(|last| cmp.compare(last, &key) != Ordering::Less)
+        {
+            return Err(Error::Unsorted);
+        }
+        loader.tree.note_key(&key);
+        loader.push(0, key, value)?;
+        loader.tree.len += 1;
+    }
+
+    loader.finish()
+}
+
+impl<K, V, C> Loader<'_, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    /// The key pushed most recently, which is the last key of the lowest node on the spine that
+    /// has any. Every node below it was started after it was pushed.
+    fn last_key(&self) -> Option<&K> {
+        self.spine.iter().find_map(|(_, node)| node.keys.last())
+    }
+
+    /// Add an entry to the node being filled on `level`.
+    fn push(&mut self, level: usize, key: K, value: V) -> Result<(), Error> {
+        let (_, node) = &mut self.spine[level];
+        if node.keys.len() < self.fill {
+            node.keys.push(key);
+            node.values.push(value);
+            return Ok(());
+        }
+
+        // The node is full, so the entry goes up a level, with a new node started right of it.
+        if level + 1 == self.spine.len() {
+            let mut parent = Self::internal(self.tree.capacity);
+            parent.children_mut().push(self.spine[level].0.clone());
+            let parent_ref = self.new_node_name();
+            self.spine.push((parent_ref, parent));
+        }
+        self.push(level + 1, key, value)?;
+
+        let next = match self.spine[level].1.children {
+            Some(_) => Self::internal(self.tree.capacity),
+            None => NodeData::new(self.tree.capacity),
+        };
+        let next_ref = self.new_node_name();
+        let (full_ref, full) = mem::replace(&mut self.spine[level], (next_ref.clone(), next));
+        self.tree.node_cache.put(Node::create(full_ref, full))?;
+        self.spine[level + 1].1.children_mut().push(next_ref);
+
+        Ok(())
+    }
+
+    /// Hand the spine over to the tree, with the top of it as the root. The last node on a level
+    /// is the only one that can be short of keys, and it borrows from its left sibling. Going
+    /// from the top down means its parent has already done the same, so there is a separator to
+    /// borrow through.
+    fn finish(mut self) -> Result<(), Error> {
+        for level in (0..self.spine.len() - 1).rev() {
+            let (_, node) = &self.spine[level];
+            if node.keys.len() < node.min_keys() {
+                self.rebalance(level)?;
+            }
+        }
+
+        let (_, root) = self.spine.pop().unwrap();
+        for (path, data) in self.spine.drain(..) {
+            self.tree.node_cache.put(Node::create(path, data))?;
+        }
+        let root_node = &mut self.tree.root_node;
+        root_node.data = root;
+        root_node.dirty = true;
+
+        Ok(())
+    }
+
+    /// Even out the keys of the node on `level` and its left sibling.
+    fn rebalance(&mut self, level: usize) -> Result<(), Error> {
+        let (below, above) = self.spine.split_at_mut(level + 1);
+        let (_, node) = &mut below[level];
+        let (_, parent) = &mut above[0];
+
+        let children = parent.children();
+        let left_ref = children[children.len() - 2].clone();
+        let mut left = self.tree.node_cache.take(&left_ref)?;
+        let key = parent.keys.pop().unwrap();
+        let value = parent.values.pop().unwrap();
+
+        let right = mem::replace(node, NodeData::new(self.tree.capacity));
+        left.data.append(key, value, right);
+        let (key, value, right) = left.data.split();
+        *node = right;
+        parent.keys.push(key);
+        parent.values.push(value);
+        left.dirty = true;
+
+        self.tree.node_cache.put(left)
+    }
+
+    fn new_node_name(&mut self) -> NodeRef {
+        let tree = &mut *self.tree;
+        BTree::<K, V, C>::new_node_name(&mut tree.free_nodes, &tree.backing_dir, &mut tree.naming)
+    }
+
+    fn internal(capacity: usize) -> NodeData<K, V> {
+        let mut node = NodeData::new(capacity);
+        node.children = Some(Vec::with_capacity(capacity + 1));
+
+        node
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::Codec;
+use crate::storage::Storage;
+use crate::{remove_node, Encoding, Error, Node, NodeData, NodeError, NodeRef};
+
+/// The number of nodes a tree caches unless told otherwise.
+pub(crate) const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// How well the node cache has been serving a tree, as returned by `BTree::cache_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Accesses to a node that was already in memory.
+    pub hits: u64,
+    /// Accesses that had to read the node from disk.
+    pub misses: u64,
+    /// Nodes pushed out of the cache to make room, whether or not they had to be written.
+    pub evictions: u64,
+}
+
+/// The nodes below the root that are held in memory, keyed by the path of their file. Every
+/// access to a node other than the root goes through here, so a node is only read from disk
+/// when it isn't already cached.
+///
+/// This is a write-back cache: changed nodes are only marked dirty, and are written when they
+/// are evicted or when the cache is flushed. `LruCache` has no eviction callback, so every
+/// insertion goes through `push`, which hands back whatever was evicted to be saved. Deleted
+/// nodes are written back the same way, with their files only removed on the next flush.
+pub(crate) struct NodeCache<K, V> {
+    storage: Arc<dyn Storage>,
+    encoding: Encoding,
+    nodes: LruCache<NodeRef, Node<K, V>>,
+    /// How many nodes to hold, which `nodes` may be allowed to exceed while evictions are
+    /// deferred.
+    capacity: usize,
+    /// With a capacity of 0 nothing can be cached, but `get` still has to hand out a reference,
+    /// so the most recently used node is kept here until the next access.
+    uncached: Option<Node<K, V>>,
+    /// Nodes deleted since the last flush, whose files are still on disk.
+    deleted: Vec<NodeRef>,
+    stats: CacheStats,
+    /// How many serialized bytes the cached nodes may add up to, if the cache is sized by bytes
+    /// as well as by nodes.
+    budget: Option<usize>,
+    /// The serialized size of each cached node, and their total, kept only while there is a
+    /// budget.
+    sizes: HashMap<NodeRef, usize>,
+    bytes: usize,
+    /// The node `get` last handed out, which may have changed since, and so has to be measured
+    /// again before anything else is done with the cache. Nothing else can change while the
+    /// node is borrowed.
+    handed_out: Option<NodeRef>,
+    /// Whether evictions are deferred, which holds off the budget as well as the capacity.
+    deferred: bool,
+}
+
+impl<K, V> NodeCache<K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    pub(crate) fn new(storage: Arc<dyn Storage>, encoding: Encoding, capacity: usize) -> Self {
+        Self {
+            storage,
+            encoding,
+            nodes: LruCache::new(capacity),
+            capacity,
+            uncached: None,
+            deleted: Vec::new(),
+            stats: CacheStats::default(),
+            budget: None,
+            sizes: HashMap::new(),
+            bytes: 0,
+            handed_out: None,
+            deferred: false,
+        }
+    }
+
+    /// Get the node at `path`, loading it into the cache if it isn't there already.
+    pub(crate) fn get(&mut self, path: &NodeRef) -> Result<&mut Node<K, V>, Error> {
+        self.remeasure()?;
+        if self.nodes.cap() == 0 {
+            if self.is_uncached(path) {
+                self.stats.hits += 1;
+            } else {
+                self.stats.misses += 1;
+                let node = Node::load(&*self.storage, self.encoding, path)?;
+                self.push(node)?;
+            }
+            return Ok(self.uncached.as_mut().unwrap());
+        }
+
+        if self.nodes.contains(path) {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+            let node = Node::load(&*self.storage, self.encoding, path)?;
+            self.push(node)?;
+        }
+        if self.budget.is_some() {
+            self.handed_out = Some(path.clone());
+        }
+
+        Ok(self.nodes.get_mut(path).unwrap())
+    }
+
+    /// The node at `path` if it is cached, without loading it or counting it as a use.
+    pub(crate) fn peek(&self, path: &NodeRef) -> Option<&Node<K, V>> {
+        match &self.uncached {
+            Some(node) if &node.path == path => Some(node),
+            _ => self.nodes.peek(path),
+        }
+    }
+
+    /// Like `get`, but with the node's values loaded as well.
+    pub(crate) fn get_with_values(&mut self, path: &NodeRef) -> Result<&mut Node<K, V>, Error> {
+        let (storage, encoding) = (self.storage.clone(), self.encoding);
+        let node = self.get(path)?;
+        node.load_values(&*storage, encoding)?;
+
+        Ok(node)
+    }
+
+    /// Take the node at `path` out of the cache, loading it if it isn't there, so that it can be
+    /// modified alongside other nodes. Hand it back with `put` once done. The node comes with its
+    /// values loaded, since anything that modifies it may move them around.
+    pub(crate) fn take(&mut self, path: &NodeRef) -> Result<Node<K, V>, Error> {
+        self.remeasure()?;
+        self.forget_size(path);
+        let mut node = if self.is_uncached(path) {
+            self.stats.hits += 1;
+            self.uncached.take().unwrap()
+        } else {
+            match self.nodes.pop(path) {
+                Some(node) => {
+                    self.stats.hits += 1;
+                    node
+                }
+                None => {
+                    self.stats.misses += 1;
+                    Node::load(&*self.storage, self.encoding, path)?
+                }
+            }
+        };
+        node.load_values(&*self.storage, self.encoding)?;
+
+        Ok(node)
+    }
+
+    pub(crate) fn put(&mut self, node: Node<K, V>) -> Result<(), Error> {
+        self.remeasure()?;
+        self.push(node)
+    }
+
+    /// Take a node out of the tree for good, returning its data. Its file is removed on the next
+    /// flush.
+    pub(crate) fn delete(&mut self, node: Node<K, V>) -> NodeData<K, V> {
+        self.deleted.push(node.path);
+
+        node.data
+    }
+
+    /// Change how many nodes the cache holds, saving any dirty nodes that no longer fit.
+    pub(crate) fn resize(&mut self, capacity: usize) -> Result<(), Error> {
+        self.remeasure()?;
+        self.capacity = capacity;
+        self.limit(capacity)
+    }
+
+    /// Also hold the cached nodes to `budget` serialized bytes between them, or stop doing so
+    /// with `None`, saving any dirty nodes that no longer fit.
+    pub(crate) fn set_budget(&mut self, budget: Option<usize>) -> Result<(), Error> {
+        self.budget = budget;
+        self.sizes.clear();
+        self.bytes = 0;
+        self.handed_out = None;
+        if budget.is_some() {
+            let paths: Vec<NodeRef> = self.nodes.iter().map(|(path, _)| path.clone()).collect();
+            for path in paths {
+                self.measure(&path)?;
+            }
+        }
+
+        self.fit()
+    }
+
+    /// Stop evicting nodes until `resume_evictions` is called, so that no node is written in the
+    /// middle of an operation.
+    pub(crate) fn defer_evictions(&mut self) -> Result<(), Error> {
+        self.remeasure()?;
+        self.deferred = true;
+        self.limit(usize::MAX)
+    }
+
+    /// Shrink the cache back to its capacity after `defer_evictions`, saving any dirty nodes
+    /// that no longer fit.
+    pub(crate) fn resume_evictions(&mut self) -> Result<(), Error> {
+        self.remeasure()?;
+        self.deferred = false;
+        self.limit(self.capacity)
+    }
+
+    /// Drop every cached node without saving it, for when the tree is emptied, and return the
+    /// paths of the deleted nodes whose files have yet to be removed.
+    pub(crate) fn clear(&mut self) -> Vec<NodeRef> {
+        self.nodes.clear();
+        self.uncached = None;
+        self.sizes.clear();
+        self.bytes = 0;
+        self.handed_out = None;
+
+        mem::take(&mut self.deleted)
+    }
+
+    /// Evict every leaf, saving any that are dirty, and release the spare capacity of what is
+    /// left.
+    pub(crate) fn shrink_to_fit(&mut self) -> Result<(), Error> {
+        self.remeasure()?;
+        let leaves: Vec<NodeRef> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.is_leaf())
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in leaves {
+            let mut node = self.nodes.pop(&path).unwrap();
+            self.forget_size(&path);
+            self.stats.evictions += 1;
+            node.flush(&*self.storage, self.encoding)?;
+        }
+        if let Some(mut node) = self.uncached.take() {
+            self.stats.evictions += 1;
+            node.flush(&*self.storage, self.encoding)?;
+        }
+        // Sizes are measured with the nodes as they are, so releasing capacity leaves them be.
+        for (_, node) in self.nodes.iter_mut() {
+            node.data.shrink_to_fit();
+        }
+        self.sizes.shrink_to_fit();
+        self.deleted.shrink_to_fit();
+
+        Ok(())
+    }
+
+    /// Move the cached node at `from` to `to`, where it will be saved from then on.
+    pub(crate) fn rename(&mut self, from: &NodeRef, to: NodeRef) -> Result<(), Error> {
+        self.remeasure()?;
+        self.forget_size(from);
+        let mut node = if self.is_uncached(from) {
+            self.uncached.take().unwrap()
+        } else {
+            self.nodes.pop(from).unwrap()
+        };
+        node.path = to;
+
+        self.push(node)
+    }
+
+    /// Stop keeping track of the nodes deleted since the last flush, leaving their files where
+    /// they are.
+    pub(crate) fn forget_deleted(&mut self) {
+        self.deleted.clear();
+    }
+
+    /// Like `dirty`, but for changing the nodes.
+    pub(crate) fn dirty_mut(&mut self) -> impl Iterator<Item = &mut Node<K, V>> {
+        self.nodes
+            .iter_mut()
+            .map(|(_, node)| node)
+            .chain(self.uncached.iter_mut())
+            .filter(|node| node.dirty)
+    }
+
+    /// The nodes that have changed since they were last saved.
+    pub(crate) fn dirty(&self) -> impl Iterator<Item = &Node<K, V>> {
+        self.nodes
+            .iter()
+            .map(|(_, node)| node)
+            .chain(self.uncached.iter())
+            .filter(|node| node.dirty)
+    }
+
+    /// The paths of the nodes deleted since the last flush.
+    pub(crate) fn deleted(&self) -> &[NodeRef] {
+        &self.deleted
+    }
+
+    /// The number of nodes held, and the number that can be.
+    pub(crate) fn occupancy(&self) -> (usize, usize) {
+        (self.nodes.len(), self.capacity)
+    }
+
+    /// The serialized bytes the cached nodes add up to, if the cache has a budget.
+    pub(crate) fn bytes(&mut self) -> Result<Option<usize>, Error> {
+        self.remeasure()?;
+
+        Ok(self.budget.map(|_| self.bytes))
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub(crate) fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Save every dirty node in the cache, and remove the files of the deleted ones, returning
+    /// their paths.
+    pub(crate) fn flush(&mut self) -> Result<Vec<NodeRef>, Error> {
+        for (_, node) in self.nodes.iter_mut() {
+            node.flush(&*self.storage, self.encoding)?;
+        }
+        if let Some(node) = self.uncached.as_mut() {
+            node.flush(&*self.storage, self.encoding)?;
+        }
+        for path in &self.deleted {
+            remove_node::<V>(&*self.storage, self.encoding, path)?;
+        }
+
+        Ok(mem::take(&mut self.deleted))
+    }
+
+    fn limit(&mut self, limit: usize) -> Result<(), Error> {
+        while self.nodes.len() > limit {
+            self.evict()?;
+        }
+        self.nodes.resize(limit);
+
+        // A node held while nothing could be cached belongs in the cache proper once it has room.
+        if limit > 0 {
+            if let Some(node) = self.uncached.take() {
+                self.push(node)?;
+            }
+        }
+
+        self.fit()
+    }
+
+    /// Evict the least recently used nodes until the rest fit the budget, if there is one and
+    /// evictions aren't deferred. The most recently used node is always kept, even if it is
+    /// bigger than the whole budget, since it is the one about to be used.
+    fn fit(&mut self) -> Result<(), Error> {
+        let Some(budget) = self.budget else {
+            return Ok(());
+        };
+        while !self.deferred && self.bytes > budget && self.nodes.len() > 1 {
+            self.evict()?;
+        }
+
+        Ok(())
+    }
+
+    /// Push the least recently used node out of the cache, saving it if it is dirty.
+    fn evict(&mut self) -> Result<(), Error> {
+        let (path, mut node) = self.nodes.pop_lru().unwrap();
+        self.forget_size(&path);
+        self.stats.evictions += 1;
+        node.flush(&*self.storage, self.encoding)?;
+
+        Ok(())
+    }
+
+    /// Measure the node `get` last handed out again, in case it changed, and evict whatever no
+    /// longer fits the budget as a result.
+    fn remeasure(&mut self) -> Result<(), Error> {
+        if let Some(path) = self.handed_out.take() {
+            if self.nodes.contains(&path) {
+                self.measure(&path)?;
+                self.fit()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the serialized size of the cached node at `path`, counting its values only if
+    /// they have been loaded, since until then they take up no memory.
+    fn measure(&mut self, path: &NodeRef) -> Result<(), Error> {
+        let node = self.nodes.peek(path).unwrap();
+        let codec = self.encoding.codec;
+        let measure = || -> Result<usize, NodeError> {
+            let mut size = codec.encode(&node.data)?.len();
+            if node.values_loaded {
+                size += codec.encode(&node.data.values)?.len();
+            }
+            Ok(size)
+        };
+        let size = measure().map_err(|e| e.at(path))?;
+        self.forget_size(path);
+        self.sizes.insert(path.clone(), size);
+        self.bytes += size;
+
+        Ok(())
+    }
+
+    fn forget_size(&mut self, path: &NodeRef) {
+        if let Some(size) = self.sizes.remove(path) {
+            self.bytes -= size;
+        }
+    }
+
+    fn push(&mut self, node: Node<K, V>) -> Result<(), Error> {
+        if self.nodes.cap() == 0 {
+            if let Some(mut evicted) = self.uncached.replace(node) {
+                self.stats.evictions += 1;
+                evicted.flush(&*self.storage, self.encoding)?;
+            }
+            return Ok(());
+        }
+
+        let path = node.path.clone();
+        if let Some((evicted_path, mut evicted)) = self.nodes.push(path.clone(), node) {
+            // Pushing a node that was already cached hands back the old copy, whose size is
+            // about to be measured again anyway.
+            if evicted_path != path {
+                self.forget_size(&evicted_path);
+            }
+            self.stats.evictions += 1;
+            evicted.flush(&*self.storage, self.encoding)?;
+        }
+        if self.budget.is_some() {
+            self.measure(&path)?;
+        }
+
+        self.fit()
+    }
+
+    fn is_uncached(&self, path: &NodeRef) -> bool {
+        self.uncached.as_ref().map(|node| &node.path) == Some(path)
+    }
+}
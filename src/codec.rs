@@ -0,0 +1,88 @@
+use rmp_serde::Serializer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::NodeError;
+
+/// A serialization format for the files of a tree's nodes.
+pub(crate) trait Codec {
+    fn encode<T>(&self, value: &T) -> Result<Vec<u8>, NodeError>
+    where
+        T: Serialize;
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, NodeError>
+    where
+        T: DeserializeOwned;
+}
+
+/// MessagePack, the format the meta file and the write-ahead log are always written in.
+pub(crate) struct MsgpackCodec;
+
+/// Bincode, which is quicker to read and write than MessagePack.
+pub(crate) struct BincodeCodec;
+
+/// Which serialization format a tree's nodes are written in, chosen with `BTreeBuilder::codec`
+/// when the tree is created and recorded in its meta file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CodecKind {
+    #[default]
+    Msgpack,
+    Bincode,
+}
+
+impl Codec for MsgpackCodec {
+    fn encode<T>(&self, value: &T) -> Result<Vec<u8>, NodeError>
+    where
+        T: Serialize,
+    {
+        let mut buf = Vec::new();
+        value.serialize(&mut Serializer::new(&mut buf))?;
+
+        Ok(buf)
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, NodeError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(rmp_serde::from_slice(buf)?)
+    }
+}
+
+impl Codec for BincodeCodec {
+    fn encode<T>(&self, value: &T) -> Result<Vec<u8>, NodeError>
+    where
+        T: Serialize,
+    {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, NodeError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(bincode::deserialize(buf)?)
+    }
+}
+
+impl Codec for CodecKind {
+    fn encode<T>(&self, value: &T) -> Result<Vec<u8>, NodeError>
+    where
+        T: Serialize,
+    {
+        match self {
+            CodecKind::Msgpack => MsgpackCodec.encode(value),
+            CodecKind::Bincode => BincodeCodec.encode(value),
+        }
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, NodeError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            CodecKind::Msgpack => MsgpackCodec.decode(buf),
+            CodecKind::Bincode => BincodeCodec.decode(buf),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::cmp::Ordering;
+
+/// An order for a `BTree` to keep its keys in. Looking a key up by a borrowed form `Q`, as
+/// `BTree::get` does, needs the order to be able to compare values of `Q` too.
+///
+/// Any `Fn(&K, &K) -> Ordering` is an order for keys of type `K`, which is what a tree created
+/// with `BTree::new_by` uses. The order is never written to disk, so a tree has to be reopened
+/// with one that sorts its keys the same way, or it will stop finding them.
+pub trait Compare<Q: ?Sized> {
+    fn compare(&self, a: &Q, b: &Q) -> Ordering;
+}
+
+/// The order given by `Ord`, which every tree uses unless it is created with `BTree::new_by`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Natural;
+
+impl<Q> Compare<Q> for Natural
+where
+    Q: Ord + ?Sized,
+{
+    fn compare(&self, a: &Q, b: &Q) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<K, F> Compare<K> for F
+where
+    F: Fn(&K, &K) -> Ordering,
+{
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        self(a, b)
+    }
+}
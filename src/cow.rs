@@ -0,0 +1,435 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::iter;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::Storage;
+use crate::{
+    external, values_path, BTree, Compare, Encoding, Error, Lookup, Natural, Node, NodeRef,
+    PathStack, Step, Version,
+};
+
+impl<K, V, C> BTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    /// Open a reader of the tree as it is now, which sees no change made after this, and can be
+    /// read from another thread while the tree goes on changing. The tree is flushed first, and
+    /// has to write copy-on-write, so that nothing the reader reads is written over.
+    pub fn reader(&mut self) -> Result<Reader<K, V, C>, Error>
+    where
+        C: Clone,
+    {
+        if !self.copy_on_write {
+            return Err(Error::NotCopyOnWrite);
+        }
+        self.flush()?;
+        let root = self.root_node.path.clone();
+
+        Ok(self.open_reader(root, self.len, self.version))
+    }
+
+    /// Open a reader of `version` of the tree, as `reader` does for the current one. The current
+    /// version is always kept, and as many before it as `set_version_history` allows, until they
+    /// are released with `release_versions_before`. Any other version fails with
+    /// `Error::VersionUnavailable`.
+    pub fn snapshot_at(&self, version: u64) -> Result<Reader<K, V, C>, Error>
+    where
+        C: Clone,
+    {
+        if !self.copy_on_write {
+            return Err(Error::NotCopyOnWrite);
+        }
+        let kept = self
+            .versions
+            .iter()
+            .find(|kept| kept.number == version)
+            .ok_or(Error::VersionUnavailable(version))?;
+        let root = self.backing_dir.join(&kept.root);
+
+        Ok(self.open_reader(root, kept.len, version))
+    }
+
+    /// The number of the version the tree is at, which goes up by one each time a change is
+    /// flushed. With copy-on-write, that is once for every change.
+    pub fn current_version(&self) -> u64 {
+        self.version
+    }
+
+    /// Stop keeping the versions before `version` for `snapshot_at`, so that `gc` can remove
+    /// their files once no reader is reading them. The current version is always kept.
+    pub fn release_versions_before(&mut self, version: u64) -> Result<(), Error> {
+        self.check_writable()?;
+        let current = self.version;
+        self.versions
+            .retain(|kept| kept.number >= version || kept.number == current);
+
+        self.save_meta()
+    }
+
+    /// Keep up to `versions` versions before the current one for `snapshot_at`, dropping the
+    /// oldest as each change makes a new one, so that `gc` can remove their files once no reader
+    /// is reading them. None are kept unless this is called, so that a tree that is never read
+    /// back at an earlier version doesn't hold on to the files of every one. Like copy-on-write
+    /// itself, the setting isn't recorded in the backing directory, so it has to be given each
+    /// time the tree is opened for the tree to go on keeping earlier versions.
+    pub fn set_version_history(&mut self, versions: usize) -> Result<(), Error> {
+        self.check_writable()?;
+        self.version_history = versions;
+        if self.trim_versions() {
+            self.save_meta()?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop the oldest kept versions past `version_history`, and return whether there were any.
+    fn trim_versions(&mut self) -> bool {
+        let kept = self.version_history.saturating_add(1);
+        let excess = self.versions.len().saturating_sub(kept);
+        self.versions.drain(..excess);
+
+        excess > 0
+    }
+
+    /// The current version, to be kept for `snapshot_at`.
+    pub(crate) fn current(&self) -> Version {
+        Version {
+            number: self.version,
+            root: self
+                .root_node
+                .path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            len: self.len,
+        }
+    }
+
+    /// Move on to the next version, which with copy-on-write is kept along with as many before it
+    /// as `version_history` allows. The root has to be where the version will find it by now.
+    pub(crate) fn next_version(&mut self) {
+        self.version += 1;
+        if self.copy_on_write {
+            self.versions.push(self.current());
+            self.trim_versions();
+        }
+    }
+
+    /// Give each changed node whose file belongs to the last version a fresh file instead, and
+    /// so every node above it too, since each names its children by their files. Nodes already
+    /// in fresh files keep them, and the root is moved whenever anything below it is.
+    pub(crate) fn relocate(&mut self) -> Result<(), Error> {
+        self.relocated.clear();
+        let stale = |node: &&Node<K, V>| !node.fresh;
+        let changed: Vec<NodeRef> = self
+            .node_cache
+            .dirty()
+            .filter(stale)
+            .map(|node| node.path.clone())
+            .collect();
+        for path in &changed {
+            for ancestor in self.path_down_to(path)?.ancestors() {
+                // Every node that is saved has to have its values, since they are saved as well.
+                self.node_cache.get_with_values(ancestor)?.dirty = true;
+            }
+        }
+
+        let moving: Vec<NodeRef> = self
+            .node_cache
+            .dirty()
+            .filter(stale)
+            .map(|node| node.path.clone())
+            .collect();
+        for path in moving {
+            let to = Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming);
+            self.node_cache.rename(&path, to.clone())?;
+            self.relocated.insert(path, to);
+        }
+        if !self.relocated.is_empty() {
+            self.root_node.dirty = true;
+        }
+        if self.root_node.dirty && !self.root_node.fresh {
+            self.root_node.path =
+                Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming);
+        }
+
+        let relocated = &self.relocated;
+        let parents = self
+            .node_cache
+            .dirty_mut()
+            .chain(iter::once(&mut self.root_node));
+        for node in parents {
+            for child in node.data.children.iter_mut().flatten() {
+                if let Some(to) = relocated.get(child) {
+                    *child = to.clone();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where the node at `path` is now, which is elsewhere if the last flush moved it. `None`
+    /// stands for the root, which is always found the same way.
+    pub(crate) fn moved(&self, path: Option<NodeRef>) -> Option<NodeRef> {
+        path.map(|path| self.relocated.get(&path).cloned().unwrap_or(path))
+    }
+
+    /// With copy-on-write, keep the nodes changed outside `begin` and `commit`, such as through
+    /// `get_mut`, in the cache until the next flush, since writing one back before then would
+    /// write over the version readers see.
+    pub(crate) fn hold_changes(&mut self) -> Result<(), Error> {
+        if self.copy_on_write {
+            self.node_cache.defer_evictions()?;
+        }
+
+        Ok(())
+    }
+
+    /// The files of every node of the versions readers are still reading, and of those kept for
+    /// `snapshot_at`.
+    pub(crate) fn read_files(&mut self) -> Result<Vec<PathBuf>, Error> {
+        let readers = self.readers.get_mut().unwrap();
+        readers.retain(|reader| reader.strong_count() > 0);
+        let mut stack: Vec<NodeRef> = readers
+            .iter()
+            .filter_map(|reader| reader.upgrade())
+            .map(|root| (*root).clone())
+            .collect();
+        stack.extend(
+            self.versions
+                .iter()
+                .map(|kept| self.backing_dir.join(&kept.root)),
+        );
+        // Versions share most of their nodes, which only have to be visited once.
+        let mut visited = HashSet::new();
+        let mut files = Vec::new();
+        while let Some(path) = stack.pop() {
+            if !visited.insert(path.clone()) {
+                continue;
+            }
+            let node = Node::<K, V>::load(&*self.storage, self.encoding, &path)?;
+            stack.extend(node.data.children.iter().flatten().cloned());
+            files.extend(external::external_files::<V>(
+                &*self.storage,
+                self.encoding,
+                &path,
+            )?);
+            files.push(values_path(&path));
+            files.push(path);
+        }
+
+        Ok(files)
+    }
+
+    fn open_reader(&self, root: NodeRef, len: usize, version: u64) -> Reader<K, V, C>
+    where
+        C: Clone,
+    {
+        let root = Arc::new(root);
+        let mut readers = self.readers.lock().unwrap();
+        readers.retain(|reader| reader.strong_count() > 0);
+        readers.push(Arc::downgrade(&root));
+
+        Reader {
+            storage: self.storage.clone(),
+            encoding: self.encoding,
+            root,
+            len,
+            version,
+            cmp: self.cmp.clone(),
+            marker: PhantomData,
+        }
+    }
+
+    /// The way down from the root to the cached node at `path`. Any key of a node leads the way
+    /// down to it, so the first one is followed.
+    fn path_down_to(&mut self, path: &NodeRef) -> Result<PathStack, Error> {
+        // The node is taken out of the cache to keep hold of its key while the nodes above it
+        // are looked up.
+        let node = self.node_cache.take(path)?;
+        let found = self.path_of(&node);
+        self.node_cache.put(node)?;
+
+        found
+    }
+
+    fn path_of(&mut self, node: &Node<K, V>) -> Result<PathStack, Error> {
+        let lost = || Error::Invalid(format!("{} can't be reached", node.path.display()));
+        let key = node.data.keys.first().ok_or_else(lost)?;
+        let mut stack = PathStack::new();
+        let mut parent = &self.root_node;
+        let mut at = None;
+        loop {
+            let idx = match parent.data.lookup(key, &self.cmp) {
+                Lookup::Child(idx) => idx,
+                _ => return Err(lost()),
+            };
+            let next = parent.data.children()[idx].clone();
+            let mut step = Step::new(idx, parent.data.children().len());
+            step.node = at.replace(next.clone());
+            stack.push(step);
+            if next == node.path {
+                return Ok(stack);
+            }
+            parent = self.node_cache.get(&next)?;
+        }
+    }
+}
+
+/// A view of a `BTree` as it was when `BTree::reader` was called, which reads the tree's files
+/// directly rather than through the tree. With copy-on-write, those files are never written
+/// over, so the reader needs no lock, however the tree changes in the meantime. A reader holds
+/// on to the files of its version until it is dropped, after which `gc` can remove them.
+///
+/// Nothing is cached, so every lookup reads each node on its way down.
+pub struct Reader<K, V, C = Natural> {
+    storage: Arc<dyn Storage>,
+    encoding: Encoding,
+    root: Arc<NodeRef>,
+    len: usize,
+    version: u64,
+    cmp: C,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V, C> Reader<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    /// The number of entries in the version being read.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of the version being read.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The value for `key` in the version being read.
+    pub fn get<Q>(&self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        let mut node = self.load(&self.root)?;
+        loop {
+            match node.data.lookup(key, &self.cmp) {
+                Lookup::Found(idx) => {
+                    node.load_values(&*self.storage, self.encoding)?;
+                    return Ok(Some(node.data.values.swap_remove(idx)));
+                }
+                Lookup::Child(idx) => {
+                    let path = node.data.children()[idx].clone();
+                    node = self.load(&path)?;
+                }
+                Lookup::Missing => return Ok(None),
+            }
+        }
+    }
+
+    /// Iterate over the entries of the version being read, in ascending key order.
+    pub fn iter(&self) -> ReaderIter<'_, K, V, C> {
+        ReaderIter {
+            reader: self,
+            stack: Vec::new(),
+            started: false,
+        }
+    }
+
+    fn load(&self, path: &NodeRef) -> Result<Node<K, V>, Error> {
+        Node::load(&*self.storage, self.encoding, path)
+    }
+}
+
+/// An iterator over the entries of a `Reader`, created by `Reader::iter`.
+pub struct ReaderIter<'a, K, V, C = Natural> {
+    reader: &'a Reader<K, V, C>,
+    /// The nodes on the way down to the next entry, with their values, each along with the
+    /// index of the next of its keys to yield.
+    stack: Vec<(Node<K, V>, usize)>,
+    started: bool,
+}
+
+impl<K, V, C> ReaderIter<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    /// Push the nodes from the one at `path` down to the leftmost leaf below it.
+    fn descend(&mut self, path: NodeRef) -> Result<(), Error> {
+        let mut path = Some(path);
+        while let Some(next) = path.take() {
+            let mut node = self.reader.load(&next)?;
+            node.load_values(&*self.reader.storage, self.reader.encoding)?;
+            path = node
+                .data
+                .children
+                .as_ref()
+                .map(|children| children[0].clone());
+            self.stack.push((node, 0));
+        }
+
+        Ok(())
+    }
+}
+
+impl<K, V, C> Iterator for ReaderIter<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<(K, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.descend((*self.reader.root).clone()) {
+                self.stack.clear();
+                return Some(Err(e));
+            }
+        }
+
+        loop {
+            let (node, idx) = self.stack.last_mut()?;
+            if *idx == node.data.keys.len() {
+                self.stack.pop();
+                continue;
+            }
+            let entry = (node.data.keys[*idx].clone(), node.data.values[*idx].clone());
+            *idx += 1;
+            // Every key of an internal node is followed by the subtree to its right.
+            let child = node
+                .data
+                .children
+                .as_ref()
+                .map(|children| children[*idx].clone());
+            if let Some(child) = child {
+                if let Err(e) = self.descend(child) {
+                    self.stack.clear();
+                    return Some(Err(e));
+                }
+            }
+
+            return Some(Ok(entry));
+        }
+    }
+}
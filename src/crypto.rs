@@ -0,0 +1,126 @@
+use std::fmt;
+use std::path::Path;
+
+/// The key the files of an encrypted tree are encrypted with. It is never written anywhere, and
+/// is left out of anything printed.
+#[derive(Clone, Copy)]
+// Without the feature, a key can't be given, so nothing looks inside one.
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+pub(crate) struct Key(pub(crate) [u8; 32]);
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// What a file at `path` is bound to besides its contents, so that it can't be passed off as
+/// another file of the tree: its name, which is the same wherever it is copied or linked to.
+/// Nothing binds a file to the version of the tree it was written for, so a file can still be
+/// swapped for an older one of the same name, or the whole tree for an older copy of itself.
+// Unused without the feature, like the key.
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+fn associated_data(path: &Path, extra: &[u8]) -> Vec<u8> {
+    let name = path.file_name().unwrap_or_default().as_encoded_bytes();
+    // The length goes first, so that no name and extra bytes run together into another's.
+    let mut data = Vec::with_capacity(8 + name.len() + extra.len());
+    data.extend_from_slice(&(name.len() as u64).to_le_bytes());
+    data.extend_from_slice(name);
+    data.extend_from_slice(extra);
+
+    data
+}
+
+#[cfg(feature = "encryption")]
+mod aead {
+    use std::path::Path;
+
+    use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+    use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+
+    use super::{associated_data, Key};
+    use crate::NodeError;
+
+    /// The size of the nonce each file starts with.
+    const NONCE_SIZE: usize = 12;
+
+    /// `buf` encrypted and authenticated with ChaCha20-Poly1305 under a fresh random nonce, which
+    /// goes in front of it, to be written to the file at `path`.
+    pub(crate) fn seal(key: Key, path: &Path, buf: &[u8]) -> Result<Vec<u8>, NodeError> {
+        seal_with(key, buf, &associated_data(path, &[]))
+    }
+
+    /// What `seal` was given, as long as `key` is the key it sealed `file` with, `file` was
+    /// sealed for a file of the same name as `path` and nothing has changed since.
+    pub(crate) fn open(key: Key, path: &Path, file: &[u8]) -> Result<Vec<u8>, NodeError> {
+        open_with(key, file, &associated_data(path, &[]))
+    }
+
+    /// A nonce and tag that authenticate `buf`, which is written to the file at `path` as it is,
+    /// for files that have to be read without the key.
+    pub(crate) fn sign(key: Key, path: &Path, buf: &[u8]) -> Result<Vec<u8>, NodeError> {
+        seal_with(key, &[], &associated_data(path, buf))
+    }
+
+    /// Check what `sign` made of `buf` for a file named like `path`.
+    pub(crate) fn verify(
+        key: Key,
+        path: &Path,
+        buf: &[u8],
+        signature: &[u8],
+    ) -> Result<(), NodeError> {
+        open_with(key, signature, &associated_data(path, buf)).map(drop)
+    }
+
+    fn seal_with(key: Key, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, NodeError> {
+        let cipher = ChaCha20Poly1305::new(&key.0.into());
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = cipher
+            .encrypt(&nonce, Payload { msg, aad })
+            .map_err(|_| NodeError::Authentication)?;
+        let mut file = Vec::with_capacity(NONCE_SIZE + sealed.len());
+        file.extend_from_slice(&nonce);
+        file.extend(sealed);
+
+        Ok(file)
+    }
+
+    fn open_with(key: Key, file: &[u8], aad: &[u8]) -> Result<Vec<u8>, NodeError> {
+        if file.len() < NONCE_SIZE {
+            return Err(NodeError::Authentication);
+        }
+        let (nonce, msg) = file.split_at(NONCE_SIZE);
+        let cipher = ChaCha20Poly1305::new(&key.0.into());
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+            .map_err(|_| NodeError::Authentication)
+    }
+}
+
+// Without the feature, no tree can be created encrypted, and `Meta::load` refuses to open one
+// that was.
+#[cfg(not(feature = "encryption"))]
+mod aead {
+    use std::path::Path;
+
+    use super::Key;
+    use crate::NodeError;
+
+    pub(crate) fn seal(_: Key, _: &Path, _: &[u8]) -> Result<Vec<u8>, NodeError> {
+        unreachable!("encryption is disabled")
+    }
+
+    pub(crate) fn open(_: Key, _: &Path, _: &[u8]) -> Result<Vec<u8>, NodeError> {
+        unreachable!("encryption is disabled")
+    }
+
+    pub(crate) fn sign(_: Key, _: &Path, _: &[u8]) -> Result<Vec<u8>, NodeError> {
+        unreachable!("encryption is disabled")
+    }
+
+    pub(crate) fn verify(_: Key, _: &Path, _: &[u8], _: &[u8]) -> Result<(), NodeError> {
+        unreachable!("encryption is disabled")
+    }
+}
+
+pub(crate) use aead::{open, seal, sign, verify};
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Compare, Error, Natural, NodeRef};
+
+/// A position in a `BTree` that can be moved back and forth between entries, created by
+/// `BTree::cursor`.
+///
+/// The cursor keeps the path from the root to its entry, so stepping to a neighbouring entry
+/// only loads the nodes between the two. A cursor that steps past either end of the tree has no
+/// position until it is moved with `seek`.
+pub struct Cursor<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+    /// `(node, idx)` for each node from the root down, where `None` stands for the root. The
+    /// last frame is at the key `idx` of its node, and every other frame has descended into the
+    /// child `idx` of its node, which is the child just left of the key `idx`. Empty when the
+    /// cursor has no position.
+    stack: Vec<(Option<NodeRef>, usize)>,
+}
+
+impl<'a, K, V, C> Cursor<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    /// A cursor at the first entry of `tree`, or without a position if `tree` is empty.
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>) -> Result<Self, Error> {
+        let mut cursor = Self {
+            tree,
+            stack: Vec::new(),
+        };
+        cursor.descend(None, false)?;
+        cursor.climb_forward()?;
+
+        Ok(cursor)
+    }
+
+    /// Move to the entry with the smallest key greater than or equal to `key`, or lose the
+    /// cursor's position if there isn't one.
+    pub fn seek(&mut self, key: &K) -> Result<(), Error> {
+        self.stack.clear();
+
+        let mut path = None;
+        loop {
+            let (node, cmp) = self.tree.node_and_cmp(path.as_ref(), false)?;
+            match node.data.find(key, cmp) {
+                Ok(idx) => {
+                    self.stack.push((path, idx));
+                    return Ok(());
+                }
+                Err(idx) => {
+                    let child = node.data.children.as_ref().map(|c| c[idx].clone());
+                    self.stack.push((path, idx));
+                    match child {
+                        Some(child) => path = Some(child),
+                        None => return self.climb_forward(),
+                    }
+                }
+            }
+        }
+    }
+
+    /// The entry the cursor is at, if it has a position.
+    pub fn current(&mut self) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let (path, idx) = match self.stack.last() {
+            Some((path, idx)) => (path.clone(), *idx),
+            None => return Ok(None),
+        };
+        let node = self.tree.node_with_values(path.as_ref())?;
+
+        Ok(Some((
+            node.data.keys[idx].clone(),
+            node.data.values[idx].clone(),
+        )))
+    }
+
+    /// Move to the next entry and return it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let (path, idx) = match self.stack.last_mut() {
+            Some((path, idx)) => (path.clone(), idx),
+            None => return Ok(None),
+        };
+        let node = self.tree.node(path.as_ref())?;
+
+        // The next entry is either the first in the subtree right of the current key, or the
+        // next key along in this leaf or one of its ancestors.
+        *idx += 1;
+        match node.data.children.as_ref() {
+            Some(children) => {
+                let child = children[*idx].clone();
+                self.descend(Some(child), false)?;
+            }
+            None => self.climb_forward()?,
+        }
+
+        self.current()
+    }
+
+    /// Move to the previous entry and return it.
+    pub fn prev(&mut self) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let (path, idx) = match self.stack.last_mut() {
+            Some((path, idx)) => (path.clone(), idx),
+            None => return Ok(None),
+        };
+        let node = self.tree.node(path.as_ref())?;
+
+        // The previous entry is either the last in the subtree left of the current key, or the
+        // previous key along in this leaf or one of its ancestors.
+        match node.data.children.as_ref() {
+            Some(children) => {
+                let child = children[*idx].clone();
+                self.descend(Some(child), true)?;
+            }
+            None if *idx > 0 => *idx -= 1,
+            None => self.climb_back(),
+        }
+
+        self.current()
+    }
+
+    /// Push the path from `path` down to its first or last entry.
+    fn descend(&mut self, mut path: Option<NodeRef>, last: bool) -> Result<(), Error> {
+        loop {
+            let node = self.tree.node(path.as_ref())?;
+            let len = node.data.keys.len();
+            match node.data.children.as_ref() {
+                Some(children) => {
+                    let idx = if last { len } else { 0 };
+                    let child = children[idx].clone();
+                    self.stack.push((path, idx));
+                    path = Some(child);
+                }
+                None => {
+                    // Only the root of an empty tree has no key to land on, which
+                    // `climb_forward` deals with.
+                    let idx = if last { len.saturating_sub(1) } else { 0 };
+                    self.stack.push((path, idx));
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Pop frames that have moved past the last key of their node, leaving the cursor at the
+    /// first ancestor with a key still to come, if any.
+    fn climb_forward(&mut self) -> Result<(), Error> {
+        while let Some((path, idx)) = self.stack.last() {
+            let (path, idx) = (path.clone(), *idx);
+            if idx < self.tree.node(path.as_ref())?.data.keys.len() {
+                break;
+            }
+            self.stack.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Pop the frame of a leaf with no keys left before the current one, leaving the cursor at
+    /// the first ancestor with a key before the child it descended into, if any.
+    fn climb_back(&mut self) {
+        self.stack.pop();
+        while let Some((_, idx)) = self.stack.last_mut() {
+            if *idx > 0 {
+                *idx -= 1;
+                return;
+            }
+            self.stack.pop();
+        }
+    }
+}
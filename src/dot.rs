@@ -0,0 +1,64 @@
+use std::fmt::{Debug, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Compare, Error};
+
+/// Render `tree` as a Graphviz graph, loading every node without its values. Each node is a
+/// record of its keys, named after its file.
+pub(crate) fn render<K, V, C>(tree: &mut BTree<K, V, C>) -> Result<String, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Debug,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    let mut dot = String::from("digraph btree {\n    node [shape=record];\n");
+    // `None` stands for the root.
+    let mut stack = vec![None];
+    while let Some(path) = stack.pop() {
+        let node = tree.node(path.as_ref())?;
+        let id = node_id(&node.path);
+        let keys: Vec<String> = node
+            .data
+            .keys
+            .iter()
+            .map(|key| escape(&format!("{:?}", key)))
+            .collect();
+        // Writing to a `String` can't fail.
+        writeln!(dot, "    \"{}\" [label=\"{}\"];", id, keys.join("|")).unwrap();
+
+        for (idx, child) in node.data.children.iter().flatten().enumerate() {
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                id,
+                node_id(child),
+                idx
+            )
+            .unwrap();
+            stack.push(Some(child.clone()));
+        }
+    }
+    dot.push_str("}\n");
+
+    Ok(dot)
+}
+
+/// The name of a node in the graph, which is the name of its file.
+fn node_id(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+/// Escape the characters that mean something in a record label.
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        if matches!(c, '"' | '\\' | '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::storage::{FileData, Storage};
+
+/// When a tree asks for what it has written to be made durable, chosen with
+/// `BTreeBuilder::durability`. Whatever isn't synced sits in the operating system's page cache
+/// until it gets around to writing it out, and is lost if the power fails first.
+///
+/// The write-ahead log is synced as it is written whatever this is, since a log that might not
+/// be on disk protects nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Never sync, which is the fastest.
+    #[default]
+    None,
+    /// Sync every file as soon as it is written.
+    Fsync,
+    /// Sync every file written since the last flush at the end of the next one. A crash can
+    /// still lose the changes since the last flush, but the flush itself is durable once it
+    /// returns.
+    FsyncBarrier,
+}
+
+/// Storage that syncs what is written through it to `inner` as `durability` asks.
+///
+/// Syncing a file only makes its contents durable, not its name, so the directory a file was
+/// created in, moved into or removed from is synced as well, or the file could be lost, or
+/// found back under its old name, after a crash.
+pub(crate) struct DurableStorage {
+    inner: Arc<dyn Storage>,
+    durability: Durability,
+    /// The files written since the last barrier, for `Durability::FsyncBarrier`.
+    unsynced: Mutex<HashSet<PathBuf>>,
+    /// The directories whose files have been created, moved or removed since the last barrier,
+    /// for `Durability::FsyncBarrier`.
+    unsynced_dirs: Mutex<HashSet<PathBuf>>,
+}
+
+impl DurableStorage {
+    /// `inner`, syncing as `durability` asks. Storage that never syncs needs no wrapping.
+    pub(crate) fn wrap(inner: Arc<dyn Storage>, durability: Durability) -> Arc<dyn Storage> {
+        match durability {
+            Durability::None => inner,
+            _ => Arc::new(Self {
+                inner,
+                durability,
+                unsynced: Mutex::new(HashSet::new()),
+                unsynced_dirs: Mutex::new(HashSet::new()),
+            }),
+        }
+    }
+
+    /// Sync the directory holding `path`, whose entry for it has changed, now or at the next
+    /// barrier as `durability` asks.
+    fn dir_changed(&self, path: &Path) -> io::Result<()> {
+        let Some(dir) = path.parent() else {
+            return Ok(());
+        };
+        match self.durability {
+            Durability::None => {}
+            Durability::Fsync => self.inner.sync(dir)?,
+            Durability::FsyncBarrier => {
+                self.unsynced_dirs.lock().unwrap().insert(dir.to_path_buf());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for DurableStorage {
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.inner.create_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+    fn read_data(&self, path: &Path) -> io::Result<FileData> {
+        self.inner.read_data(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        match self.durability {
+            Durability::None => self.inner.write(path, data)?,
+            Durability::Fsync => {
+                let created = self.inner.size(path).is_err();
+                self.inner.write(path, data)?;
+                self.inner.sync(path)?;
+                if created {
+                    self.dir_changed(path)?;
+                }
+            }
+            Durability::FsyncBarrier => {
+                self.inner.write(path, data)?;
+                self.unsynced.lock().unwrap().insert(path.to_path_buf());
+                // The directory is synced only once at the barrier, so it is cheap to do whether
+                // the file is new or not.
+                self.dir_changed(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inner.rename(from, to)?;
+        // Files are written to a temporary name and then moved into place, so what is left to
+        // sync has moved too.
+        {
+            let mut unsynced = self.unsynced.lock().unwrap();
+            if unsynced.remove(from) {
+                unsynced.insert(to.to_path_buf());
+            }
+        }
+
+        self.dir_changed(to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.inner.remove(path)?;
+        self.unsynced.lock().unwrap().remove(path);
+
+        self.dir_changed(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.inner.list(path)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.inner.sync(path)
+    }
+
+    fn link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inner.link(from, to)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        self.inner.size(path)
+    }
+
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>> {
+        self.inner.lock(path, shared)
+    }
+
+    fn barrier(&self) -> io::Result<()> {
+        let unsynced: Vec<PathBuf> = self.unsynced.lock().unwrap().drain().collect();
+        for path in unsynced {
+            self.inner.sync(&path)?;
+        }
+        // The files have to be durable before the names that point at them are.
+        let unsynced: Vec<PathBuf> = self.unsynced_dirs.lock().unwrap().drain().collect();
+        for dir in unsynced {
+            self.inner.sync(&dir)?;
+        }
+
+        self.inner.barrier()
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Compare, Error, Natural, NodeRef, Slot};
+
+/// A view into a single entry of a `BTree`, which may be there or not, created by
+/// `BTree::entry`.
+///
+/// The entry remembers the node the search for its key ended in, so acting on it doesn't search
+/// the tree again.
+pub enum Entry<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    Occupied(OccupiedEntry<'a, K, V, C>),
+    Vacant(VacantEntry<'a, K, V, C>),
+}
+
+/// An entry that is in the tree.
+pub struct OccupiedEntry<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+    key: K,
+    slot: Slot,
+}
+
+/// An entry that isn't in the tree.
+pub struct VacantEntry<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+    key: K,
+    /// The leaf the key belongs in, where `None` stands for the root.
+    leaf: Option<NodeRef>,
+}
+
+impl<'a, K, V, C> Entry<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Insert `default` if the entry is vacant, and return the entry's value either way.
+    pub fn or_insert(self, default: V) -> Result<&'a mut V, Error> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Insert the result of `default` if the entry is vacant, and return the entry's value
+    /// either way. `default` is only called if the entry is vacant.
+    pub fn or_insert_with<F>(self, default: F) -> Result<&'a mut V, Error>
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modify the entry's value with `f` if it is occupied.
+    pub fn and_modify<F>(self, f: F) -> Result<Self, Error>
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.tree.begin()?;
+                f(entry.get_mut()?);
+                entry.tree.commit()?;
+                entry.slot.path = entry.tree.moved(entry.slot.path.take());
+                Ok(Entry::Occupied(entry))
+            }
+            Entry::Vacant(entry) => Ok(Entry::Vacant(entry)),
+        }
+    }
+}
+
+impl<'a, K, V, C> OccupiedEntry<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>, key: K, slot: Slot) -> Self {
+        Self { tree, key, slot }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// The entry's value. Reading it doesn't count as changing its node.
+    pub fn get(&mut self) -> Result<&V, Error> {
+        let node = self.tree.node_with_values(self.slot.path.as_ref())?;
+
+        Ok(&node.data.values[self.slot.idx])
+    }
+
+    /// The entry's value, for changing in place. The node holding it is written back like any
+    /// other changed node, so with the write-ahead log on the change is only logged along with
+    /// the next `insert` or `remove`.
+    pub fn get_mut(&mut self) -> Result<&mut V, Error> {
+        value_mut(self.tree, &self.slot)
+    }
+
+    /// Like `get_mut`, but borrowing from the tree rather than the entry.
+    pub fn into_mut(self) -> Result<&'a mut V, Error> {
+        value_mut(self.tree, &self.slot)
+    }
+
+    /// Like `into_mut`, but leaving the node holding the value unchanged.
+    pub(crate) fn into_ref(self) -> Result<&'a V, Error> {
+        let node = self.tree.node_with_values(self.slot.path.as_ref())?;
+
+        Ok(&node.data.values[self.slot.idx])
+    }
+
+    /// Take the entry out of the tree, and return its value.
+    pub fn remove(self) -> Result<V, Error> {
+        self.remove_entry().map(|(_, value)| value)
+    }
+
+    /// Take the entry out of the tree, and return its key as it was stored along with its
+    /// value. Removing rebalances the nodes on the way down to the entry, so unlike the other
+    /// operations on an entry, this searches the tree again from the root.
+    pub fn remove_entry(self) -> Result<(K, V), Error> {
+        let removed = self.tree.remove_entry(&self.key)?;
+
+        Ok(removed.expect("an occupied entry is in the tree"))
+    }
+}
+
+impl<'a, K, V, C> VacantEntry<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>, key: K, leaf: Option<NodeRef>) -> Self {
+        Self { tree, key, leaf }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Insert `value` for the entry's key, and return it for changing in place.
+    pub fn insert(self, value: V) -> Result<&'a mut V, Error> {
+        let tree = self.tree;
+        tree.begin()?;
+        tree.note_key(&self.key);
+
+        // A leaf with room takes the entry directly. A full one has to be split, along with any
+        // full nodes above it, which only an insert from the root can do.
+        let (leaf, cmp) = tree.node_and_cmp(self.leaf.as_ref(), true)?;
+        let slot = if leaf.data.is_full() {
+            let (slot, _) = tree.insert_entry(self.key, value)?;
+            slot
+        } else {
+            let idx = leaf.data.find(&self.key, cmp).unwrap_err();
+            leaf.data
+                .insert(self.key, value, cmp)
+                .map_err(|e| e.at(&leaf.path))?;
+            leaf.dirty = true;
+            Slot {
+                path: self.leaf,
+                idx,
+            }
+        };
+        tree.len += 1;
+        tree.commit()?;
+        let slot = Slot {
+            path: tree.moved(slot.path),
+            idx: slot.idx,
+        };
+
+        value_mut(tree, &slot)
+    }
+}
+
+/// The value in `slot`, marking its node as changed since the caller can change the value.
+fn value_mut<'t, K, V, C>(tree: &'t mut BTree<K, V, C>, slot: &Slot) -> Result<&'t mut V, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree.hold_changes()?;
+    let node = tree.node_with_values(slot.path.as_ref())?;
+    node.dirty = true;
+
+    Ok(&mut node.data.values[slot.idx])
+}
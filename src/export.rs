@@ -0,0 +1,106 @@
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use rmp_serde::Serializer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{bulk, BTree, Compare, Error};
+
+/// The version of the export format, which is independent of how a tree lays out its own files.
+const EXPORT_VERSION: u32 = 1;
+
+/// What an export starts with, ahead of its entries.
+#[derive(Deserialize, Serialize)]
+struct Header {
+    version: u32,
+    capacity: usize,
+    len: usize,
+}
+
+/// Write `tree` to `w` as a header followed by each entry in ascending key order, each one a
+/// MessagePack value preceded by its length as a little-endian `u64`.
+pub(crate) fn export<K, V, C, W>(tree: &mut BTree<K, V, C>, mut w: W) -> Result<(), Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Clone,
+    V: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Compare<K>,
+    W: Write,
+{
+    let header = Header {
+        version: EXPORT_VERSION,
+        capacity: tree.capacity,
+        len: tree.len,
+    };
+    write_record(&mut w, &header)?;
+    for entry in tree.iter() {
+        write_record(&mut w, &entry?)?;
+    }
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Create a tree in `backing_dir` from an export read from `r`, with the capacity the exported
+/// tree had, bulk loading its entries as they are read.
+pub(crate) fn import<K, V, R>(backing_dir: PathBuf, mut r: R) -> Result<BTree<K, V>, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Ord,
+    V: for<'de> Deserialize<'de> + Serialize,
+    R: Read,
+{
+    let header: Header = read_record(&mut r)?;
+    if header.version != EXPORT_VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: header.version,
+            expected: EXPORT_VERSION,
+        });
+    }
+
+    let mut tree = BTree::new(backing_dir, header.capacity)?;
+    // The loader takes plain entries, so the first one that can't be read ends the stream and
+    // is reported once the loader is done.
+    let mut error = None;
+    let entries = (0..header.len).map_while(|_| match read_record(&mut r) {
+        Ok(entry) => Some(entry),
+        Err(e) => {
+            error = Some(e);
+            None
+        }
+    });
+    bulk::load(&mut tree, entries)?;
+    match error {
+        Some(e) => Err(e),
+        None => Ok(tree),
+    }
+}
+
+fn write_record<W, T>(w: &mut W, value: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: Serialize,
+{
+    let mut buf = Vec::new();
+    value.serialize(&mut Serializer::new(&mut buf))?;
+    w.write_all(&(buf.len() as u64).to_le_bytes())?;
+    w.write_all(&buf)?;
+
+    Ok(())
+}
+
+fn read_record<R, T>(r: &mut R) -> Result<T, Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut len = [0; 8];
+    r.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    let mut buf = Vec::new();
+    r.take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(rmp_serde::from_slice(&buf)?)
+}
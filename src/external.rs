@@ -0,0 +1,190 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::Codec;
+use crate::storage::Storage;
+use crate::{
+    marked_path, read_node_file, remove_if_exists, values_path, write_node_file, Encoding, Error,
+    NodeError, NodeRef, EXTERNAL_VALUE_MARKER,
+};
+
+/// A value as the values file of a tree with a threshold for external values holds it: either
+/// the value itself, or the file it was moved out to because it encoded to more than that.
+#[derive(Deserialize, Serialize)]
+enum StoredValue<V> {
+    Inline(V),
+    External(NodeRef),
+}
+
+/// A value of a node that is kept in a file of its own, as it was when the node's values were
+/// last read or written.
+pub(crate) struct ExternalValue {
+    file: PathBuf,
+    fingerprint: u64,
+}
+
+/// Write `values` to the values file of the node at `path`. If the tree has a threshold, each
+/// value that encodes to more than that is kept in a file of its own. Of the values in
+/// `external`, those that haven't changed keep the files they are in, as long as the node's
+/// last values file still names them, and the rest are written to new ones. The files the last
+/// values file named that the new one doesn't are removed once it is in place, and `external`
+/// is left naming the values in files of their own from then on.
+pub(crate) fn save_values<V>(
+    storage: &dyn Storage,
+    encoding: Encoding,
+    path: &Path,
+    values: &[V],
+    external: &mut Vec<ExternalValue>,
+) -> Result<(), Error>
+where
+    V: Serialize + DeserializeOwned,
+{
+    let Some(threshold) = encoding.external_values else {
+        return write_node_file(storage, encoding, &values_path(path), &values);
+    };
+    // A values file that can't be read leaves the files it named for `gc`.
+    let replaced = external_files::<V>(storage, encoding, path).unwrap_or_default();
+    let mut unchanged: HashMap<u64, PathBuf> = external
+        .drain(..)
+        .filter(|value| replaced.contains(&value.file))
+        .map(|value| (value.fingerprint, value.file))
+        .collect();
+
+    // New files are numbered rather than named at random, so that the same changes always leave
+    // the same files behind. None takes the name of a file the last values file named, which
+    // may still be needed if the new one is never put in place.
+    let mut taken: HashSet<PathBuf> = replaced.iter().cloned().collect();
+    let mut next = 1;
+    let mut stored = Vec::with_capacity(values.len());
+    for value in values {
+        let buf = encoding.codec.encode(value).map_err(|e| e.at(path))?;
+        if buf.len() <= threshold {
+            stored.push(StoredValue::Inline(value));
+            continue;
+        }
+        let fingerprint = fingerprint(&buf);
+        let file = match unchanged.remove(&fingerprint) {
+            Some(file) => file,
+            None => {
+                let file = loop {
+                    let file = marked_path(path, &format!("{EXTERNAL_VALUE_MARKER}{next:07}"));
+                    next += 1;
+                    if taken.insert(file.clone()) {
+                        break file;
+                    }
+                };
+                write_node_file(storage, encoding, &file, value)?;
+                file
+            }
+        };
+        stored.push(StoredValue::External(file.clone()));
+        external.push(ExternalValue { file, fingerprint });
+    }
+    write_node_file(storage, encoding, &values_path(path), &stored)?;
+    for file in replaced {
+        let kept = stored
+            .iter()
+            .any(|value| matches!(value, StoredValue::External(kept) if *kept == file));
+        if !kept {
+            remove_if_exists(storage, &file)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the values of the node at `path` from its values file, and from any files they were
+/// moved out to, along with which values those were.
+pub(crate) fn load_values<V>(
+    storage: &dyn Storage,
+    encoding: Encoding,
+    path: &Path,
+) -> Result<(Vec<V>, Vec<ExternalValue>), Error>
+where
+    V: Serialize + DeserializeOwned,
+{
+    if encoding.external_values.is_none() {
+        let values = read_node_file(storage, encoding, &values_path(path))?;
+        return Ok((values, Vec::new()));
+    }
+    let stored: Vec<StoredValue<V>> = read_node_file(storage, encoding, &values_path(path))?;
+
+    let mut values = Vec::with_capacity(stored.len());
+    let mut external = Vec::new();
+    for value in stored {
+        match value {
+            StoredValue::Inline(value) => values.push(value),
+            StoredValue::External(file) => {
+                let file = beside(path, &file);
+                let value = read_node_file(storage, encoding, &file)?;
+                let buf = encoding.codec.encode(&value).map_err(|e| e.at(&file))?;
+                let fingerprint = fingerprint(&buf);
+                external.push(ExternalValue { file, fingerprint });
+                values.push(value);
+            }
+        }
+    }
+
+    Ok((values, external))
+}
+
+/// The files the values file of the node at `path` moved values out to. There are none for a
+/// tree without a threshold, or for a node that hasn't been written yet.
+pub(crate) fn external_files<V>(
+    storage: &dyn Storage,
+    encoding: Encoding,
+    path: &Path,
+) -> Result<Vec<PathBuf>, Error>
+where
+    V: DeserializeOwned,
+{
+    if encoding.external_values.is_none() {
+        return Ok(Vec::new());
+    }
+    let stored: Vec<StoredValue<V>> = match read_node_file(storage, encoding, &values_path(path)) {
+        Err(Error::Node {
+            source: NodeError::Io(e),
+            ..
+        }) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        stored => stored?,
+    };
+
+    Ok(stored
+        .into_iter()
+        .filter_map(|value| match value {
+            StoredValue::External(external) => Some(beside(path, &external)),
+            StoredValue::Inline(_) => None,
+        })
+        .collect())
+}
+
+/// Whether the file at `path` holds a value moved out of its node's values file, rather than
+/// anything of a node's own.
+pub(crate) fn is_external(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        name.to_string_lossy()
+            .contains(&format!(".{EXTERNAL_VALUE_MARKER}"))
+    })
+}
+
+/// A fingerprint of the encoding of a value, to tell whether the value has changed since its
+/// file was written. Fingerprints are only ever compared within the process, so the hasher is
+/// keyed at random, which keeps values chosen to collide from being taken for one another.
+fn fingerprint(buf: &[u8]) -> u64 {
+    static HASHER: OnceLock<RandomState> = OnceLock::new();
+
+    HASHER.get_or_init(RandomState::new).hash_one(buf)
+}
+
+/// The file `external` names, as it was named when the node at `path` was written. A tree read
+/// from anywhere else, such as a snapshot, finds it next to the node instead.
+fn beside(path: &Path, external: &Path) -> PathBuf {
+    path.with_file_name(external.file_name().unwrap())
+}
@@ -0,0 +1,47 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::Node;
+
+/// A mutable handle to a value in a `BTree`, created by `BTree::get_mut`.
+///
+/// The node holding the value is marked as changed when the guard is dropped, if the value was
+/// borrowed mutably, so the change is written back like any other. It holds the tree borrowed
+/// for as long as it lives.
+pub struct ValueGuard<'a, K, V> {
+    node: &'a mut Node<K, V>,
+    idx: usize,
+    changed: bool,
+}
+
+impl<'a, K, V> ValueGuard<'a, K, V> {
+    pub(crate) fn new(node: &'a mut Node<K, V>, idx: usize) -> Self {
+        Self {
+            node,
+            idx,
+            changed: false,
+        }
+    }
+}
+
+impl<K, V> Deref for ValueGuard<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.node.data.values[self.idx]
+    }
+}
+
+impl<K, V> DerefMut for ValueGuard<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.changed = true;
+        &mut self.node.data.values[self.idx]
+    }
+}
+
+impl<K, V> Drop for ValueGuard<'_, K, V> {
+    fn drop(&mut self) {
+        if self.changed {
+            self.node.dirty = true;
+        }
+    }
+}
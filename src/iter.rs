@@ -0,0 +1,470 @@
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Compare, Error, Natural, NodeData, NodeRef};
+
+/// An iterator over the entries of a `BTree` in ascending key order, created by `BTree::iter`.
+///
+/// Rather than recursing, the iterator keeps an explicit stack of the nodes it is partway
+/// through, so its memory use grows with the height of the tree and not with the call stack.
+/// Nodes are loaded lazily through the tree's node cache as the iterator reaches them.
+pub struct Iter<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+    stack: Vec<Frame>,
+    /// The entry `peek` looked at, which `next` yields before moving on. `Some(None)` means
+    /// `peek` found the iterator at its end.
+    peeked: Option<Option<Result<(K, V), Error>>>,
+}
+
+/// An iterator over the entries of a `BTree` in descending key order, created by
+/// `BTree::iter_rev`. It works like `Iter`, starting from the last child of each node instead
+/// of the first.
+pub struct IterRev<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+    stack: Vec<Frame>,
+}
+
+/// An iterator over the keys of a `BTree` in ascending order, created by `BTree::keys`.
+pub struct Keys<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    iter: Iter<'a, K, V, C>,
+}
+
+/// An iterator over the values of a `BTree` in ascending order of their keys, created by
+/// `BTree::values`.
+pub struct Values<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    iter: Iter<'a, K, V, C>,
+}
+
+/// An iterator over the entries of a `BTree` in ascending key order that owns the tree, created
+/// by its `IntoIterator` impl. It walks the tree as `Iter` does, and drops the tree once it is
+/// dropped itself, leaving the backing directory where it is.
+pub struct IntoIter<K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: BTree<K, V, C>,
+    stack: Vec<Frame>,
+}
+
+/// An iterator over a range of the entries of a `BTree` in ascending key order, created by
+/// `BTree::range`.
+pub struct Range<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    iter: Iter<'a, K, V, C>,
+    /// The start bound, until the iterator has been positioned at it on the first call to
+    /// `next`.
+    start: Option<Bound<K>>,
+    end: Bound<K>,
+}
+
+/// A node the iterator is partway through. For a leaf, `idx` is the next key to yield. For an
+/// internal node, `idx` is the next child to descend into, so once the iterator comes back up
+/// from child `idx - 1` the next key to yield is `idx - 1`.
+///
+/// `IterRev` counts `idx` from the other end instead, so for a node with `n` keys, `idx` is
+/// the number of keys or children it has moved past from the right.
+struct Frame {
+    /// `None` stands for the root node.
+    path: Option<NodeRef>,
+    idx: usize,
+}
+
+impl<'a, K, V, C> Iter<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>) -> Self {
+        let stack = vec![Frame { path: None, idx: 0 }];
+
+        Self {
+            tree,
+            stack,
+            peeked: None,
+        }
+    }
+
+    /// The entry `next` will yield, without moving past it. Peeking again, or calling `next`
+    /// after, doesn't read anything more from the tree.
+    pub fn peek(&mut self) -> Option<&Result<(K, V), Error>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.peeked
+            .get_or_insert_with(|| advance(self.tree, &mut self.stack, true, clone_entry))
+            .as_ref()
+    }
+}
+
+impl<K, V, C> Iter<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    /// Move on to the next entry, returning whatever `read` takes from it, given the node it is
+    /// in and its index there. Each node's values are only loaded if `read` needs them.
+    pub(crate) fn advance<T, F>(&mut self, with_values: bool, read: F) -> Option<Result<T, Error>>
+    where
+        F: FnMut(&NodeData<K, V>, usize) -> T,
+    {
+        advance(self.tree, &mut self.stack, with_values, read)
+    }
+
+    /// Position the iterator so that the next entry it yields is the first one after `start`.
+    fn seek(&mut self, start: Bound<&K>) -> Result<(), Error> {
+        self.stack.clear();
+
+        let mut path = None;
+        loop {
+            let (node, cmp) = self.tree.node_and_cmp(path.as_ref(), false)?;
+            let data = &node.data;
+            let (idx, exact) = match start {
+                Bound::Included(key) => match data.find(key, cmp) {
+                    Ok(idx) => (idx, true),
+                    Err(idx) => (idx, false),
+                },
+                Bound::Excluded(key) => match data.find(key, cmp) {
+                    Ok(idx) => (idx + 1, false),
+                    Err(idx) => (idx, false),
+                },
+                Bound::Unbounded => (0, false),
+            };
+
+            if node.is_leaf() {
+                self.stack.push(Frame { path, idx });
+                return Ok(());
+            }
+
+            // Either way the next key to yield from this node is `idx`, which is what the frame
+            // of an internal node that has already descended into child `idx` means.
+            let child = data.children()[idx].clone();
+            self.stack.push(Frame { path, idx: idx + 1 });
+            if exact {
+                return Ok(());
+            }
+            path = Some(child);
+        }
+    }
+}
+
+impl<K, V, C> Iterator for Iter<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<(K, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some(entry) => entry,
+            None => self.advance(true, clone_entry),
+        }
+    }
+}
+
+impl<K, V, C> IntoIterator for BTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<(K, V), Error>;
+    type IntoIter = IntoIter<K, V, C>;
+
+    fn into_iter(self) -> IntoIter<K, V, C> {
+        IntoIter {
+            tree: self,
+            stack: vec![Frame { path: None, idx: 0 }],
+        }
+    }
+}
+
+impl<K, V, C> Iterator for IntoIter<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<(K, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Entries are cloned out rather than moved, since the tree's cached nodes are still
+        // flushed when it is dropped along with the iterator.
+        advance(&mut self.tree, &mut self.stack, true, clone_entry)
+    }
+}
+
+impl<'a, K, V, C> Keys<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>) -> Self {
+        Self {
+            iter: Iter::new(tree),
+        }
+    }
+}
+
+impl<K, V, C> Iterator for Keys<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    type Item = Result<K, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.advance(false, |data, idx| data.keys[idx].clone())
+    }
+}
+
+impl<'a, K, V, C> Values<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>) -> Self {
+        Self {
+            iter: Iter::new(tree),
+        }
+    }
+}
+
+impl<K, V, C> Iterator for Values<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<V, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .advance(true, |data, idx| data.values[idx].clone())
+    }
+}
+
+impl<'a, K, V, C> IterRev<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>) -> Self {
+        let stack = vec![Frame { path: None, idx: 0 }];
+
+        Self { tree, stack }
+    }
+}
+
+impl<K, V, C> Iterator for IterRev<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<(K, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            let node = match self.tree.node_with_values(frame.path.as_ref()) {
+                Ok(node) => node,
+                Err(e) => {
+                    self.stack.clear();
+                    return Some(Err(e));
+                }
+            };
+            let data = &node.data;
+            let len = data.keys.len();
+
+            if node.is_leaf() {
+                if frame.idx < len {
+                    let idx = len - 1 - frame.idx;
+                    let entry = (data.keys[idx].clone(), data.values[idx].clone());
+                    frame.idx += 1;
+                    return Some(Ok(entry));
+                }
+                self.stack.pop();
+                continue;
+            }
+
+            if frame.idx > len {
+                self.stack.pop();
+                continue;
+            }
+
+            // Coming back up from child `len - frame.idx + 1`, the next key is the one just
+            // left of it.
+            let entry = frame.idx.checked_sub(1).map(|_| {
+                let idx = len - frame.idx;
+                (data.keys[idx].clone(), data.values[idx].clone())
+            });
+            let child = Frame {
+                path: Some(data.children()[len - frame.idx].clone()),
+                idx: 0,
+            };
+            frame.idx += 1;
+            self.stack.push(child);
+
+            if let Some(entry) = entry {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+impl<'a, K, V, C> Range<'a, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    pub(crate) fn new(tree: &'a mut BTree<K, V, C>, start: Bound<K>, end: Bound<K>) -> Self {
+        let iter = Iter::new(tree);
+
+        Self {
+            iter,
+            start: Some(start),
+            end,
+        }
+    }
+}
+
+impl<K, V, C> Iterator for Range<'_, K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+{
+    type Item = Result<(K, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(start) = self.start.take() {
+            if let Err(e) = self.iter.seek(start.as_ref()) {
+                self.iter.stack.clear();
+                return Some(Err(e));
+            }
+        }
+
+        let (key, value) = match self.iter.next()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e)),
+        };
+        let cmp = &self.iter.tree.cmp;
+        let in_range = match &self.end {
+            Bound::Included(end) => cmp.compare(&key, end) != Ordering::Greater,
+            Bound::Excluded(end) => cmp.compare(&key, end) == Ordering::Less,
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.iter.stack.clear();
+            return None;
+        }
+
+        Some(Ok((key, value)))
+    }
+}
+
+/// Move on from the position `stack` describes in `tree` to the next entry, as `Iter::advance`
+/// does, for any iterator that walks the tree that way.
+fn advance<K, V, C, T, F>(
+    tree: &mut BTree<K, V, C>,
+    stack: &mut Vec<Frame>,
+    with_values: bool,
+    mut read: F,
+) -> Option<Result<T, Error>>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+    F: FnMut(&NodeData<K, V>, usize) -> T,
+{
+    loop {
+        let frame = stack.last_mut()?;
+        let node = if with_values {
+            tree.node_with_values(frame.path.as_ref())
+        } else {
+            tree.node(frame.path.as_ref())
+        };
+        let node = match node {
+            Ok(node) => node,
+            Err(e) => {
+                stack.clear();
+                return Some(Err(e));
+            }
+        };
+        let data = &node.data;
+
+        if node.is_leaf() {
+            if frame.idx < data.keys.len() {
+                let entry = read(data, frame.idx);
+                frame.idx += 1;
+                return Some(Ok(entry));
+            }
+            stack.pop();
+            continue;
+        }
+
+        if frame.idx > data.keys.len() {
+            stack.pop();
+            continue;
+        }
+
+        let entry = frame.idx.checked_sub(1).map(|idx| read(data, idx));
+        let child = Frame {
+            path: Some(data.children()[frame.idx].clone()),
+            idx: 0,
+        };
+        frame.idx += 1;
+        stack.push(child);
+
+        if let Some(entry) = entry {
+            return Some(Ok(entry));
+        }
+    }
+}
+
+/// The entry at `idx` in `data`, as the iterators over whole entries yield it.
+fn clone_entry<K, V>(data: &NodeData<K, V>, idx: usize) -> (K, V)
+where
+    K: Clone,
+    V: Clone,
+{
+    (data.keys[idx].clone(), data.values[idx].clone())
+}
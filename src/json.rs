@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{BTree, Compare, Error};
+
+/// How each entry is written, as `{"key": ..., "value": ...}`.
+#[derive(Serialize)]
+struct EntryRef<'a, K, V> {
+    key: &'a K,
+    value: &'a V,
+}
+
+#[derive(Deserialize)]
+struct JsonEntry<K, V> {
+    key: K,
+    value: V,
+}
+
+/// Write the entries of `tree` to `w` as a JSON array in ascending key order, one entry at a
+/// time.
+pub(crate) fn write<K, V, C, W>(tree: &mut BTree<K, V, C>, mut w: W) -> Result<(), Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Clone,
+    V: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Compare<K>,
+    W: Write,
+{
+    w.write_all(b"[")?;
+    for (idx, entry) in tree.iter().enumerate() {
+        let (key, value) = entry?;
+        if idx > 0 {
+            w.write_all(b",")?;
+        }
+        serde_json::to_writer(
+            &mut w,
+            &EntryRef {
+                key: &key,
+                value: &value,
+            },
+        )?;
+    }
+    w.write_all(b"]")?;
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Create a tree in `backing_dir` holding the entries of the JSON array read from `r`, inserting
+/// each one as it is parsed.
+pub(crate) fn read<K, V, R>(
+    backing_dir: PathBuf,
+    capacity: usize,
+    r: R,
+) -> Result<BTree<K, V>, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Ord,
+    V: for<'de> Deserialize<'de> + Serialize,
+    R: Read,
+{
+    let mut tree = BTree::new(backing_dir, capacity)?;
+    let mut de = serde_json::Deserializer::from_reader(r);
+    let mut error = None;
+    let inserter = Inserter {
+        tree: &mut tree,
+        error: &mut error,
+        marker: PhantomData,
+    };
+    let result = inserter.deserialize(&mut de).and_then(|()| de.end());
+    // An error inserting an entry stops the parse, but is the one to report.
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result?;
+
+    Ok(tree)
+}
+
+/// Inserts each entry of a JSON array into `tree` as it is parsed, so the array is never held
+/// in memory as a whole.
+struct Inserter<'a, K, V>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Ord,
+    V: for<'de> Deserialize<'de> + Serialize,
+{
+    tree: &'a mut BTree<K, V>,
+    /// The first error from the tree itself, which the parser can only carry as a message.
+    error: &'a mut Option<Error>,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'de, K, V> DeserializeSeed<'de> for Inserter<'_, K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, K, V> Visitor<'de> for Inserter<'_, K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of entries")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(JsonEntry { key, value }) = seq.next_element::<JsonEntry<K, V>>()? {
+            if let Err(e) = self.tree.insert(key, value) {
+                let message = e.to_string();
+                *self.error = Some(e);
+                return Err(de::Error::custom(message));
+            }
+        }
+
+        Ok(())
+    }
+}
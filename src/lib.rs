@@ -1,29 +1,334 @@
-use std::fs::{DirBuilder, File, OpenOptions};
-use std::io::{self, Seek};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::io::{self, Read, Write};
 use std::mem;
-use std::path::PathBuf;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
 
-use lru::LruCache;
 use rmp_serde::Serializer;
+use serde::de::{self, DeserializeOwned, Deserializer};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use builder::DEFAULT_CAPACITY;
+use cache::{NodeCache, DEFAULT_CACHE_CAPACITY};
+use codec::Codec;
+use crypto::Key;
+use external::ExternalValue;
+use storage::MemoryStorage;
+
+mod background;
+mod bloom;
+mod builder;
+mod bulk;
+mod cache;
+mod codec;
+mod compare;
+mod cow;
+mod crypto;
+mod cursor;
+mod dot;
+mod durability;
+mod entry;
+mod export;
+mod external;
+mod guard;
+mod iter;
+#[cfg(feature = "json")]
+mod json;
+mod multimap;
+mod path;
+mod prefix;
+mod repair;
+mod scrub;
+mod set;
+mod stats;
+mod storage;
+mod sync;
+mod transaction;
+mod wal;
+
+use bloom::BloomFilter;
+pub use builder::BTreeBuilder;
+pub use cache::CacheStats;
+pub use codec::CodecKind;
+pub use compare::{Compare, Natural};
+pub use cow::{Reader, ReaderIter};
+pub use cursor::Cursor;
+pub use durability::Durability;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use guard::ValueGuard;
+pub use iter::{IntoIter, Iter, IterRev, Keys, Range, Values};
+pub use multimap::BTreeMultiMap;
+pub use path::{PathStack, Step};
+pub use repair::RepairReport;
+pub use scrub::ScrubReport;
+pub use set::{BTreeSet, SetIter, SetRange};
+pub use stats::{DiskUsage, LevelStats, TreeStats};
+#[cfg(feature = "mmap")]
+pub use storage::MmapStorage;
+pub use storage::{FileData, FileStorage, Storage};
+pub use sync::SyncBTree;
+pub use transaction::Txn;
 
 type NodeRef = PathBuf;
 
-pub struct BTree<K, V> {
+/// The version of the on-disk format written by this crate, recorded in the meta file.
+const FORMAT_VERSION: u32 = 15;
+/// The smallest number of keys a node can hold. Any fewer, and splitting a full node would leave
+/// one side of it empty.
+const MIN_CAPACITY: usize = 3;
+/// The merge threshold of trees created without one, at which nodes are kept as full as a
+/// classic B-tree keeps them.
+pub(crate) const DEFAULT_MERGE_THRESHOLD: f32 = 0.5;
+const META_FILE: &str = "meta";
+/// The file a tree holds a lock on while it is open, so that no other tree opens the same
+/// backing directory to write to it.
+const LOCK_FILE: &str = "LOCK";
+const ROOT_NODE: &str = "root";
+/// The file a tree with a Bloom filter keeps it in.
+const BLOOM_FILE: &str = "bloom";
+/// The extension of the file a node or the meta file is written to before it replaces the
+/// current version.
+const TEMP_EXTENSION: &str = "tmp";
+/// The extension of the file next to a node's own that holds its values.
+const VALUES_EXTENSION: &str = "values";
+/// What the name of a file a value was moved out to has in place of `values`, followed by a
+/// number that sets it apart from the node's other values.
+const EXTERNAL_VALUE_MARKER: &str = "value-";
+/// The size of the header each of a node's files starts with, which holds a CRC32 of the rest
+/// of the file.
+const CHECKSUM_SIZE: usize = 4;
+
+pub struct BTree<K, V, C = Natural>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    storage: Arc<dyn Storage>,
+    backing_dir: PathBuf,
+    capacity: usize,
+    /// The fraction of `capacity` below which removing from a node merges it with a sibling,
+    /// unless it can borrow a key from one instead.
+    merge_threshold: f32,
+    len: usize,
     root_node: Node<K, V>,
-    node_cache: LruCache<NodeRef, Node<K, V>>,
+    node_cache: NodeCache<K, V>,
+    /// Names of deleted nodes whose files have been removed, for new nodes to take before any
+    /// fresh name is made up.
+    free_nodes: Vec<NodeRef>,
+    write_ahead_log: bool,
+    /// Whether changed nodes are written to fresh files rather than over their old ones, so
+    /// that readers of an earlier version keep seeing it.
+    copy_on_write: bool,
+    /// Where the last flush moved each node it gave a fresh file, so that a path held across a
+    /// change can still be followed.
+    relocated: HashMap<NodeRef, NodeRef>,
+    /// The roots of the versions readers are reading, whose files `gc` has to keep.
+    readers: Mutex<Vec<Weak<NodeRef>>>,
+    /// The number of the version the tree is at, which each flush that changes anything moves
+    /// on by one.
+    version: u64,
+    /// With copy-on-write, the versions kept for `snapshot_at`: the current one, and as many
+    /// before it as `version_history` allows, other than those released with
+    /// `release_versions_before`.
+    versions: Vec<Version>,
+    /// How many versions before the current one copy-on-write keeps, as set by
+    /// `set_version_history`.
+    version_history: usize,
+    /// Set by `open_read_only`, after which nothing the tree does writes to the backing
+    /// directory.
+    read_only: bool,
+    /// Set while `transaction` runs, during which each change is held in the cache rather than
+    /// committed on its own.
+    transaction: bool,
+    /// Set for a tree created with `BTreeBuilder::bloom_filter`, and consulted by `get` and
+    /// `contains_key` before searching for a key.
+    bloom: Option<BloomFilter>,
+    /// Held for as long as the tree is open, and released when it is dropped.
+    _lock: Option<File>,
+    encoding: Encoding,
+    naming: Naming,
+    /// The order the keys are kept in.
+    cmp: C,
 }
 
 struct Node<K, V> {
-    file: File,
+    path: PathBuf,
     data: NodeData<K, V>,
+    /// Whether `data` has changed since it was last written to its file.
+    dirty: bool,
+    /// Whether `data.values` has been read from the node's values file. Until it has, it is
+    /// empty.
+    values_loaded: bool,
+    /// Whether the node's file is new since the tree was last flushed, so that no version a
+    /// reader could be reading refers to it, and it can be written over.
+    fresh: bool,
 }
 
+/// The contents of a node. The node's file only holds its keys and children, and its values are
+/// kept in a separate file, so that looking through keys never has to read them.
 #[derive(Deserialize, Serialize)]
 struct NodeData<K, V> {
+    capacity: usize,
     keys: Vec<K>,
+    #[serde(skip, default = "Vec::new")]
     values: Vec<V>,
     children: Option<Vec<NodeRef>>,
+    /// The values that were in files of their own when `values` was last read or written, so
+    /// that saving the node again only writes out those that have changed.
+    #[serde(skip, default = "Vec::new")]
+    external: Vec<ExternalValue>,
+}
+
+/// Tree-level settings that aren't recorded in any node, stored in the `meta` file.
+#[derive(Deserialize, Serialize)]
+struct Meta {
+    version: u32,
+    capacity: usize,
+    merge_threshold: f32,
+    root: String,
+    len: usize,
+    /// The file names in `BTree::free_nodes`.
+    free: Vec<String>,
+    encoding: Encoding,
+    naming: Naming,
+    /// `BTree::version`.
+    current_version: u64,
+    /// `BTree::versions`.
+    versions: Vec<Version>,
+    /// If the tree has a Bloom filter, the generation its file has to be at to match the tree.
+    bloom_filter: Option<u64>,
+}
+
+/// A version of the tree kept for `BTree::snapshot_at`.
+#[derive(Clone, Deserialize, Serialize)]
+struct Version {
+    number: u64,
+    /// The file name of the version's root.
+    root: String,
+    len: usize,
+}
+
+/// How the files of a tree's nodes are encoded. This is fixed when the tree is created, and
+/// recorded in the meta file so that the nodes can be read back.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+struct Encoding {
+    codec: CodecKind,
+    /// Whether each file is compressed with zstd once serialized, which takes the `compression`
+    /// feature.
+    compressed: bool,
+    /// Whether each file is encrypted once serialized and compressed, which takes the
+    /// `encryption` feature.
+    encrypted: bool,
+    /// The key an encrypted tree's files are encrypted with, which is given each time the tree
+    /// is opened, and so never recorded with the rest.
+    #[serde(skip)]
+    key: Option<Key>,
+    /// The most bytes a value can encode to and still be kept in its node's values file, past
+    /// which it gets a file of its own. Without one, every value is kept there.
+    external_values: Option<usize>,
+}
+
+/// How the files of a tree's nodes are named: a prefix, and optionally a namespace and an
+/// extension, around each node's own name, which is `root` for the root and a UUID or the next
+/// number in sequence for the rest. This is fixed when the tree is created, and recorded in the
+/// meta file so that nodes created after the tree is reopened are named the same way.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct Naming {
+    prefix: String,
+    /// Set for a tree that shares its backing directory with others, whose meta file, lock and
+    /// write-ahead log are named after it as well.
+    namespace: Option<String>,
+    extension: Option<String>,
+    /// Set for a tree that names its nodes in sequence, to the number the next new node is
+    /// named after, so that the same changes always lead to the same files.
+    next_node: Option<u64>,
+}
+
+/// What `remove` is looking for. Removing the smallest or largest entry of a subtree is how an
+/// internal node finds a replacement for a separator key it is about to lose.
+enum Target<'a, Q: ?Sized> {
+    Key(&'a Q),
+    Min,
+    Max,
+}
+
+// Derived, these would need `Q: Copy`, which an unsized key like `str` can't be.
+impl<Q: ?Sized> Clone for Target<'_, Q> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Q: ?Sized> Copy for Target<'_, Q> {}
+
+/// Where an entry is stored.
+struct Slot {
+    /// The node holding the entry, where `None` stands for the root.
+    path: Option<NodeRef>,
+    idx: usize,
+}
+
+/// A leaf `extend` can insert into directly, along with the separator keys either side of it in
+/// its ancestors. Any key strictly between the two belongs in the leaf.
+struct Finger<K> {
+    /// `None` stands for the root.
+    leaf: Option<NodeRef>,
+    lower: Option<K>,
+    upper: Option<K>,
+}
+
+impl<K> Finger<K>
+where
+    K: Clone,
+{
+    fn root() -> Self {
+        Self {
+            leaf: None,
+            lower: None,
+            upper: None,
+        }
+    }
+
+    /// Narrow the finger down to the child `idx` of `data`. Each level down narrows the range it
+    /// covers, so a separator found deeper replaces the one above it.
+    fn descend<V>(&mut self, data: &NodeData<K, V>, idx: usize) {
+        let step = Step::new(idx, data.children().len());
+        if let Some(left) = step.left_sibling() {
+            self.lower = Some(data.keys[left].clone());
+        }
+        if step.right_sibling().is_some() {
+            self.upper = Some(data.keys[idx].clone());
+        }
+        self.leaf = Some(data.children()[idx].clone());
+    }
+
+    fn covers<C>(&self, key: &K, cmp: &C) -> bool
+    where
+        C: Compare<K>,
+    {
+        self.lower
+            .as_ref()
+            .is_none_or(|lower| cmp.compare(lower, key) == Ordering::Less)
+            && self
+                .upper
+                .as_ref()
+                .is_none_or(|upper| cmp.compare(key, upper) == Ordering::Less)
+    }
+}
+
+/// Where a search for a key ends up within a single node.
+enum Lookup {
+    Found(usize),
+    Child(usize),
+    Missing,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -32,18 +337,80 @@ pub enum Error {
     Io(#[from] io::Error),
     #[error("A serialization error occurred.")]
     Serialization(#[from] rmp_serde::encode::Error),
+    #[error("A deserialization error occurred.")]
+    Deserialization(#[from] rmp_serde::decode::Error),
+    #[error("An error occurred while operating on the node at {}.", path.display())]
+    Node {
+        path: PathBuf,
+        #[source]
+        source: NodeError,
+    },
+    #[error("The key is not in the tree.")]
+    KeyNotFound,
+    #[error(
+        "The tree was written in format version {found}, but only version {expected} is supported."
+    )]
+    UnsupportedVersion { found: u32, expected: u32 },
+    #[error("A node capacity of {0} is too small, it must be at least {MIN_CAPACITY}.")]
+    InvalidCapacity(usize),
+    #[error("A merge threshold of {0} is out of range, it must be above 0 and at most 0.5.")]
+    InvalidMergeThreshold(f32),
+    #[error("The entries to bulk load are not in strictly increasing order of key.")]
+    Unsorted,
+    #[error("Room for {0} more entries can't be reserved, since the tree couldn't count them.")]
+    TooManyEntries(usize),
+    #[error("The tree is malformed: {0}")]
+    Invalid(String),
+    #[error("The tree's nodes are compressed, but this crate was built without compression.")]
+    CompressionDisabled,
+    #[error("The tree's nodes are encrypted, but this crate was built without encryption.")]
+    EncryptionDisabled,
+    #[error("The tree's nodes are encrypted, so it can only be opened with their key.")]
+    KeyRequired,
+    #[error("A key to decrypt the tree's nodes was given, but they aren't encrypted.")]
+    NotEncrypted,
+    #[error("The tree was opened read-only, so it can't be changed.")]
+    ReadOnly,
+    #[error("Readers need the tree to write copy-on-write, which it doesn't.")]
+    NotCopyOnWrite,
+    #[error("Version {0} of the tree isn't kept, if it ever was.")]
+    VersionUnavailable(u64),
+    #[error("The tree is already open elsewhere.")]
+    Locked,
+    #[error("The backing directory doesn't exist.")]
+    Missing,
+    #[error("The backing directory exists, but holds no tree.")]
+    NotInitialized,
+    #[error("{0:?} can't be part of a file name, since it is empty or holds a `.` or `/`.")]
+    InvalidFileName(String),
+    #[error("The tree's nodes are written with {found:?}, but {expected:?} was asked for.")]
+    CodecMismatch {
+        found: CodecKind,
+        expected: CodecKind,
+    },
+    #[cfg(feature = "json")]
+    #[error("A JSON error occurred.")]
+    Json(#[from] serde_json::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
-enum NodeError {
-    #[error("Cannot insert into the node because it is too full.")]
-    NeedsSplit,
+pub enum NodeError {
+    #[error("Cannot insert into the node because it is full.")]
+    Full,
     #[error("An I/O error occurred.")]
     Io(#[from] io::Error),
     #[error("A serialization error occurred.")]
     Serialization(#[from] rmp_serde::encode::Error),
     #[error("A deserialization error occurred.")]
     Deserialization(#[from] rmp_serde::decode::Error),
+    #[error("A bincode error occurred.")]
+    Bincode(#[from] bincode::Error),
+    #[error("The checksum of {} does not match its contents.", path.display())]
+    ChecksumMismatch { path: PathBuf },
+    #[error(
+        "The node can't be decrypted with the key given, or was changed since it was written."
+    )]
+    Authentication,
 }
 
 impl<K, V> BTree<K, V>
@@ -51,118 +418,2979 @@ where
     K: for<'a> Deserialize<'a> + Serialize + Ord,
     V: for<'a> Deserialize<'a> + Serialize,
 {
-    pub fn new(dir: PathBuf, capacity: usize) -> Result<Self, Error> {
-        DirBuilder::new().create(&dir)?;
-        let mut root_node = dir.clone();
-        root_node.push("root");
-        let root_node = match Node::new(root_node, capacity) {
-            Ok(node) => node,
-            Err(NodeError::Io(e)) => {
-                return Err(Error::Io(e));
+    /// Create a tree in `backing_dir`, which must not exist yet. Each node holds up to
+    /// `capacity` keys, which must be at least 3.
+    pub fn new(backing_dir: PathBuf, capacity: usize) -> Result<Self, Error> {
+        Self::new_by(backing_dir, capacity, Natural)
+    }
+
+    /// Create a tree that keeps its nodes in memory rather than in files, and so doesn't outlive
+    /// the `BTree` itself.
+    pub fn new_in_memory(capacity: usize) -> Result<Self, Error> {
+        Self::create(
+            Arc::new(MemoryStorage::default()),
+            PathBuf::new(),
+            capacity,
+            DEFAULT_MERGE_THRESHOLD,
+            Natural,
+            Encoding::default(),
+            Naming::default(),
+        )
+    }
+
+    /// Create a tree at `backing_dir` holding the entries of `sorted`, which must be in strictly
+    /// increasing order of key. Rather than inserting them one at a time, this builds the tree
+    /// from the leaves up, filling every node but the last on each level to one key short of
+    /// capacity. If the entries turn out not to be sorted, whatever was built is left behind in
+    /// `backing_dir`.
+    pub fn build_sorted<I>(backing_dir: PathBuf, capacity: usize, sorted: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut tree = Self::new(backing_dir, capacity)?;
+        bulk::load(&mut tree, sorted)?;
+
+        Ok(tree)
+    }
+
+    /// Create a tree in `backing_dir`, which must not exist yet, from what `export` wrote to
+    /// `r`. The tree has the capacity of the one exported, and is built from the leaves up as
+    /// `build_sorted` does. If the export turns out to be cut short or out of order, whatever
+    /// was built is left behind in `backing_dir`.
+    pub fn import<R>(backing_dir: PathBuf, r: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        export::import(backing_dir, r)
+    }
+
+    /// Create a tree in `backing_dir`, which must not exist yet, from a JSON array of
+    /// `{"key": ..., "value": ...}` objects read from `r`, such as `to_json_writer` writes. The
+    /// entries can come in any order, and each is inserted as soon as it is read, so a later one
+    /// replaces an earlier one with the same key.
+    #[cfg(feature = "json")]
+    pub fn from_json_reader<R>(backing_dir: PathBuf, capacity: usize, r: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        json::read(backing_dir, capacity, r)
+    }
+
+    /// Salvage what can still be read of a damaged tree in `backing_dir`, and rebuild it from
+    /// that. Every node file is read, whether or not it can be reached from the root, since a
+    /// damaged node may be the only way to the ones below it, and the entries of each one that
+    /// can be read are bulk loaded into a new tree with the same settings, whose files then
+    /// replace the old one's. The entries of unreadable nodes are lost, and the report says how
+    /// many there were. Only the tree's own files are touched, so that any trees in namespaces
+    /// of the same directory are left as they were, and `BTreeBuilder::repair` repairs one of
+    /// those.
+    ///
+    /// This is a best effort: a node file left behind by a crash may bring back entries that
+    /// had since been removed or changed. The meta file has to be readable, and the tree can't
+    /// be open elsewhere while it is repaired. An encrypted tree can't be repaired, and fails
+    /// with `Error::KeyRequired`.
+    pub fn repair(backing_dir: PathBuf) -> Result<RepairReport, Error> {
+        repair::repair::<K, V>(backing_dir, None)
+    }
+
+    /// Create a tree in a fresh directory under the system temp directory and insert every entry
+    /// of `iter` into it. This stands in for `FromIterator`, which has no way to report an error.
+    /// The directory is left behind when the tree is dropped.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Clone,
+    {
+        let backing_dir = env::temp_dir().join(format!("btree-{}", Uuid::new_v4()));
+        let mut tree = Self::new(backing_dir, DEFAULT_CAPACITY)?;
+        tree.extend(iter)?;
+
+        Ok(tree)
+    }
+
+    /// Reopen a tree previously created with `new` in `backing_dir`. A write-ahead log left
+    /// behind by a crash is replayed first if it was committed, and discarded if not. Nothing is
+    /// ever created: a missing `backing_dir` fails with `Error::Missing`, and one that holds no
+    /// tree with `Error::NotInitialized`.
+    pub fn open(backing_dir: PathBuf) -> Result<Self, Error> {
+        Self::open_by(backing_dir, Natural)
+    }
+
+    /// Open the tree in `backing_dir` for reading only, as a backup or reporting tool might.
+    /// Any number of trees can be open read-only at once, but not alongside one open to write
+    /// to. Anything that would change the tree fails with
+    /// `Error::ReadOnly`, and nodes are only ever read into the cache, never written back.
+    /// Since taking over a write-ahead log would mean writing, any log left by a crash is left
+    /// alone, and a change it records may be seen partly applied.
+    pub fn open_read_only(backing_dir: PathBuf) -> Result<Self, Error> {
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage);
+        check_exists(&*storage, &backing_dir, None)?;
+        let lock = lock(&*storage, &backing_dir, None, true)?;
+
+        Self::load(storage, backing_dir, None, Natural, lock, true, None)
+    }
+}
+
+impl<K, V, C> BTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    /// Like `new`, but keeping the keys in the order `cmp` puts them in rather than the one
+    /// given by `Ord`. The order isn't recorded in the backing directory, so the tree has to be
+    /// reopened with `open_by` and a comparator that orders keys the same way.
+    pub fn new_by(backing_dir: PathBuf, capacity: usize, cmp: C) -> Result<Self, Error> {
+        Self::create(
+            Arc::new(FileStorage),
+            backing_dir,
+            capacity,
+            DEFAULT_MERGE_THRESHOLD,
+            cmp,
+            Encoding::default(),
+            Naming::default(),
+        )
+    }
+
+    fn create(
+        storage: Arc<dyn Storage>,
+        backing_dir: PathBuf,
+        capacity: usize,
+        merge_threshold: f32,
+        cmp: C,
+        encoding: Encoding,
+        naming: Naming,
+    ) -> Result<Self, Error> {
+        if capacity < MIN_CAPACITY {
+            return Err(Error::InvalidCapacity(capacity));
+        }
+        if !(merge_threshold > 0.0 && merge_threshold <= 0.5) {
+            return Err(Error::InvalidMergeThreshold(merge_threshold));
+        }
+
+        let namespace = naming.namespace.as_deref();
+        match storage.create_dir(&backing_dir) {
+            // A tree in a namespace can go in a directory that has others in already, as long as
+            // none of them is in the same namespace.
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && namespace.is_some() => {
+                let meta_path = tree_file(&backing_dir, META_FILE, namespace);
+                if storage.read(&meta_path).is_ok() {
+                    return Err(e.into());
+                }
             }
-            _ => unreachable!(),
+            result => result?,
+        }
+        let lock = lock(&*storage, &backing_dir, namespace, false)?;
+        let mut root_node = Node::new(naming.path(&backing_dir, ROOT_NODE), capacity);
+        root_node.save(&*storage, encoding)?;
+        let node_cache = NodeCache::new(storage.clone(), encoding, DEFAULT_CACHE_CAPACITY);
+
+        let tree = Self {
+            storage,
+            backing_dir,
+            capacity,
+            merge_threshold,
+            len: 0,
+            root_node,
+            node_cache,
+            free_nodes: Vec::new(),
+            write_ahead_log: false,
+            copy_on_write: false,
+            relocated: HashMap::new(),
+            readers: Mutex::default(),
+            version: 0,
+            versions: Vec::new(),
+            version_history: 0,
+            read_only: false,
+            transaction: false,
+            bloom: None,
+            _lock: lock,
+            encoding,
+            naming,
+            cmp,
         };
-        let node_cache = LruCache::new(256);
+        tree.save_meta()?;
 
-        Ok(Self {
+        Ok(tree)
+    }
+
+    /// Reopen a tree previously created with `new_by` in `backing_dir`, as `open` does. `cmp`
+    /// must order keys the same way as the comparator the tree was created with.
+    pub fn open_by(backing_dir: PathBuf, cmp: C) -> Result<Self, Error> {
+        Self::open_in(Arc::new(FileStorage), backing_dir, None, cmp, None)
+    }
+
+    /// Reopen the tree in `namespace` of `backing_dir` from `storage` to write to it, with `key`
+    /// if it is encrypted.
+    fn open_in(
+        storage: Arc<dyn Storage>,
+        backing_dir: PathBuf,
+        namespace: Option<&str>,
+        cmp: C,
+        key: Option<Key>,
+    ) -> Result<Self, Error> {
+        check_exists(&*storage, &backing_dir, namespace)?;
+        let lock = lock(&*storage, &backing_dir, namespace, false)?;
+        // An encrypted log that can't be decrypted is taken to be cut short and thrown away, so
+        // the key is checked against the meta file first, which is only ever replaced whole.
+        Meta::load(
+            &*storage,
+            &tree_file(&backing_dir, META_FILE, namespace),
+            key,
+        )?;
+        wal::recover::<K, V>(&*storage, &backing_dir, namespace, key)?;
+
+        Self::load(storage, backing_dir, namespace, cmp, lock, false, key)
+    }
+
+    /// Read the tree in `namespace` of `backing_dir` from `storage`, once any write-ahead log
+    /// has been dealt with, holding `lock` until it is dropped.
+    fn load(
+        storage: Arc<dyn Storage>,
+        backing_dir: PathBuf,
+        namespace: Option<&str>,
+        cmp: C,
+        lock: Option<File>,
+        read_only: bool,
+        key: Option<Key>,
+    ) -> Result<Self, Error> {
+        let Meta {
+            capacity,
+            merge_threshold,
+            root,
+            len,
+            free,
+            encoding,
+            naming,
+            current_version,
+            versions,
+            bloom_filter,
+            ..
+        } = Meta::load(
+            &*storage,
+            &tree_file(&backing_dir, META_FILE, namespace),
+            key,
+        )?;
+        let free_nodes = free.iter().map(|name| backing_dir.join(name)).collect();
+        let mut root_node = Node::load(&*storage, encoding, &backing_dir.join(root))?;
+        root_node.load_values(&*storage, encoding)?;
+        let node_cache = NodeCache::new(storage.clone(), encoding, DEFAULT_CACHE_CAPACITY);
+
+        let mut tree = Self {
+            storage,
+            backing_dir,
+            capacity,
+            merge_threshold,
+            len,
             root_node,
             node_cache,
-        })
+            free_nodes,
+            write_ahead_log: false,
+            copy_on_write: false,
+            relocated: HashMap::new(),
+            readers: Mutex::default(),
+            version: current_version,
+            versions,
+            version_history: 0,
+            read_only,
+            transaction: false,
+            bloom: None,
+            _lock: lock,
+            encoding,
+            naming,
+            cmp,
+        };
+        if let Some(generation) = bloom_filter {
+            tree.load_bloom_filter(generation)?;
+        }
+
+        Ok(tree)
+    }
+
+    /// The number of entries in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     /// If the key was already present, return the old value. If the key was not present, return
     /// None.
-    pub fn insert(&mut self, key: K, mut value: V) -> Option<V> {
-        let mut curr_node = &mut self.root_node;
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
+        self.begin()?;
+        let (_, old) = self.insert_entry(key, value)?;
+        if old.is_none() {
+            self.len += 1;
+        }
+        self.commit()?;
+
+        Ok(old)
+    }
+
+    /// Insert every entry of `iter`, as `insert` would one at a time. Entries whose keys fall in
+    /// the leaf the last one went into, as runs of sorted keys mostly do, go straight into that
+    /// leaf without searching from the root again.
+    pub fn extend<I>(&mut self, iter: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Clone,
+    {
+        let mut finger: Option<Finger<K>> = None;
+        for (key, value) in iter {
+            self.begin()?;
+            self.note_key(&key);
+
+            let covered = finger
+                .as_ref()
+                .filter(|finger| finger.covers(&key, &self.cmp));
+            let leaf = match covered {
+                Some(finger) => Some(self.node_and_cmp(finger.leaf.as_ref(), true)?),
+                None => None,
+            };
+            match leaf.filter(|(leaf, _)| !leaf.data.is_full()) {
+                Some((leaf, cmp)) => {
+                    let old = leaf
+                        .data
+                        .insert(key, value, cmp)
+                        .map_err(|e| e.at(&leaf.path))?;
+                    leaf.dirty = true;
+                    if old.is_none() {
+                        self.len += 1;
+                    }
+                }
+                // A full leaf has to be split, which only an insert from the root can do. The
+                // path it takes down is the next finger, unless the key ends up in an internal
+                // node instead of a leaf, as when it replaces the value of a separator.
+                None => {
+                    let mut next = Finger::root();
+                    let (slot, old) =
+                        self.insert_entry_with(key, value, |data, idx| next.descend(data, idx))?;
+                    if old.is_none() {
+                        self.len += 1;
+                    }
+                    let in_leaf = self.node(slot.path.as_ref())?.is_leaf();
+                    finger = Some(next).filter(|next| in_leaf && next.leaf == slot.path);
+                }
+            }
+
+            self.commit()?;
+            if let Some(finger) = finger.as_mut() {
+                finger.leaf = self.moved(finger.leaf.take());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert an entry without updating `len`, returning where it ended up along with the value
+    /// it replaced.
+    fn insert_entry(&mut self, key: K, value: V) -> Result<(Slot, Option<V>), Error> {
+        self.insert_entry_with(key, value, |_, _| ())
+    }
 
-        while !curr_node.is_leaf() {
-            match curr_node.data.keys[..].binary_search(&key) {
+    /// Like `insert_entry`, calling `visit` with each internal node on the way down and the index
+    /// of the child the insert goes on into.
+    fn insert_entry_with<F>(
+        &mut self,
+        key: K,
+        value: V,
+        mut visit: F,
+    ) -> Result<(Slot, Option<V>), Error>
+    where
+        F: FnMut(&NodeData<K, V>, usize),
+    {
+        self.note_key(&key);
+        if self.root_node.data.is_full() {
+            self.split_root()?;
+        }
+
+        // `None` stands for the root node, which is owned by the tree rather than the cache.
+        // Children are taken out of the cache while we work on them and put back afterwards.
+        let mut curr_node: Option<Node<K, V>> = None;
+        loop {
+            let node = match curr_node.as_mut() {
+                Some(node) => node,
+                None => &mut self.root_node,
+            };
+
+            if node.is_leaf() {
+                let idx = node.data.find(&key, &self.cmp).unwrap_or_else(|idx| idx);
+                let old = node
+                    .data
+                    .insert(key, value, &self.cmp)
+                    .map_err(|e| e.at(&node.path))?;
+                node.dirty = true;
+                let path = curr_node.as_ref().map(|node| node.path.clone());
+                self.release(curr_node)?;
+                return Ok((Slot { path, idx }, old));
+            }
+
+            let mut idx = match node.data.find(&key, &self.cmp) {
                 Ok(idx) => {
-                    mem::swap(&mut value, &mut curr_node.data.values[idx]);
-                    return Some(value);
-                }
-                Err(idx) => {
-                    let child = curr_node.data.children.as_ref().unwrap()[idx].clone();
-                    if self.node_cache.contains(&child) {
-                        curr_node = self.node_cache.get_mut(&child).unwrap();
-                    } else {
-                        let node = Node::load(&child).expect("TODO");
-                        self.node_cache.push(child.clone(), node);
-                        curr_node = self.node_cache.get_mut(&child).unwrap();
+                    let old = mem::replace(&mut node.data.values[idx], value);
+                    node.dirty = true;
+                    let path = curr_node.as_ref().map(|node| node.path.clone());
+                    self.release(curr_node)?;
+                    return Ok((Slot { path, idx }, Some(old)));
+                }
+                Err(idx) => idx,
+            };
+
+            let mut next = self.node_cache.take(&node.data.children()[idx])?;
+            if next.data.is_full() {
+                let sibling_ref =
+                    Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming);
+                let sibling = Self::split_child(node, idx, &mut next, sibling_ref)?;
+                match self.cmp.compare(&key, &node.data.keys[idx]) {
+                    Ordering::Equal => {
+                        let old = mem::replace(&mut node.data.values[idx], value);
+                        node.dirty = true;
+                        self.node_cache.put(sibling)?;
+                        self.node_cache.put(next)?;
+                        let path = curr_node.as_ref().map(|node| node.path.clone());
+                        self.release(curr_node)?;
+                        return Ok((Slot { path, idx }, Some(old)));
+                    }
+                    Ordering::Less => {
+                        self.node_cache.put(sibling)?;
+                    }
+                    Ordering::Greater => {
+                        idx += 1;
+                        let child = mem::replace(&mut next, sibling);
+                        self.node_cache.put(child)?;
                     }
                 }
             }
+            debug_assert_eq!(next.path, node.data.children()[idx]);
+            visit(&node.data, idx);
+
+            self.release(curr_node.replace(next))?;
         }
+    }
 
-        curr_node.insert_if_space(key, value).expect("TODO");
-        curr_node.save().expect("TODO");
+    /// Get the value for `key`, which may be any borrowed form of the tree's key type, as with
+    /// `std::collections::BTreeMap`.
+    pub fn get<Q>(&mut self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: Serialize + ?Sized,
+        V: Clone,
+    {
+        if !self.might_contain(key) {
+            return Ok(None);
+        }
 
-        None
+        let mut path = None;
+        loop {
+            // Borrowing the cache and the storage separately lets the values of the node the key
+            // is found in be loaded without looking the node up again.
+            let node = match path.as_ref() {
+                Some(path) => self.node_cache.get(path)?,
+                None => &mut self.root_node,
+            };
+            match node.data.lookup(key, &self.cmp) {
+                Lookup::Found(idx) => {
+                    node.load_values(&*self.storage, self.encoding)?;
+                    return Ok(Some(node.data.values[idx].clone()));
+                }
+                Lookup::Child(idx) => path = Some(node.data.children()[idx].clone()),
+                Lookup::Missing => return Ok(None),
+            }
+        }
     }
-}
 
-impl<K, V> Node<K, V>
-where
-    K: for<'a> Deserialize<'a> + Serialize + Ord,
-    V: for<'a> Deserialize<'a> + Serialize,
-{
-    fn reset_file(&mut self) -> Result<(), NodeError> {
-        self.file.set_len(0)?;
-        self.file.rewind()?;
+    /// The tree's answer to `map[&key]`, for when a missing key is as much an error as one the
+    /// tree can't read. Rather than panic as `std::collections::BTreeMap`'s indexing does, it
+    /// gives `Error::KeyNotFound`. A real `Index` can't be implemented, since reading a value can
+    /// fail and needs `&mut self`.
+    pub fn index<Q>(&mut self, key: &Q) -> Result<V, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: Serialize + ?Sized,
+        V: Clone,
+    {
+        self.get(key)?.ok_or(Error::KeyNotFound)
+    }
+
+    /// Get the values for each of `keys` at once, in the same order as `keys`. The keys are
+    /// sorted first, so that those that share a node find it with a single descent, instead of
+    /// one from the root per key as `get` would.
+    pub fn get_many(&mut self, keys: &[&K]) -> Result<Vec<Option<V>>, Error>
+    where
+        V: Clone,
+    {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|&a, &b| self.cmp.compare(keys[a], keys[b]));
+        let mut found = vec![None; keys.len()];
+        self.get_sorted(None, keys, &order, &mut found)?;
+
+        Ok(found)
+    }
+
+    /// Look for the keys picked out of `keys` by `order`, which runs through them in sorted
+    /// order, under the node at `path`. Each value found is put in `found` at the position of its
+    /// key in `keys`. The keys that fall under one child are next to each other in `order`, so
+    /// each child is visited once, with all of them.
+    fn get_sorted(
+        &mut self,
+        path: Option<&NodeRef>,
+        keys: &[&K],
+        order: &[usize],
+        found: &mut [Option<V>],
+    ) -> Result<(), Error>
+    where
+        V: Clone,
+    {
+        let (node, cmp) = self.node_and_cmp(path, false)?;
+        let mut hits = Vec::new();
+        // The index of each child to descend into, along with the range of `order` under it.
+        let mut groups: Vec<(usize, usize, usize)> = Vec::new();
+        for (pos, &key_idx) in order.iter().enumerate() {
+            match node.data.lookup(keys[key_idx], cmp) {
+                Lookup::Found(idx) => hits.push((key_idx, idx)),
+                Lookup::Child(idx) => match groups.last_mut() {
+                    Some((child, _, end)) if *child == idx => *end = pos + 1,
+                    _ => groups.push((idx, pos, pos + 1)),
+                },
+                Lookup::Missing => {}
+            }
+        }
+        let groups: Vec<(NodeRef, usize, usize)> = groups
+            .into_iter()
+            .map(|(idx, start, end)| (node.data.children()[idx].clone(), start, end))
+            .collect();
+
+        if !hits.is_empty() {
+            let node = self.node_with_values(path)?;
+            for (key_idx, idx) in hits {
+                found[key_idx] = Some(node.data.values[idx].clone());
+            }
+        }
+        for (child, start, end) in groups {
+            self.get_sorted(Some(&child), keys, &order[start..end], found)?;
+        }
 
         Ok(())
     }
 
-    fn new(path: PathBuf, capacity: usize) -> Result<Self, NodeError> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create_new(true)
-            .open(path)?;
-        let data = NodeData {
-            keys: Vec::with_capacity(capacity),
-            values: Vec::with_capacity(capacity),
-            children: None,
+    /// The entry with the smallest key, found by following the first child of each node down to
+    /// a leaf.
+    pub fn first_key_value(&mut self) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.outermost(Target::Min)
+    }
+
+    /// The entry with the largest key, found by following the last child of each node down to a
+    /// leaf.
+    pub fn last_key_value(&mut self) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.outermost(Target::Max)
+    }
+
+    fn outermost(&mut self, target: Target<'_, K>) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let slot = match self.outermost_slot(target)? {
+            Some(slot) => slot,
+            None => return Ok(None),
         };
+        let node = self.node_with_values(slot.path.as_ref())?;
 
-        Ok(Node { file, data })
+        Ok(Some((
+            node.data.keys[slot.idx].clone(),
+            node.data.values[slot.idx].clone(),
+        )))
     }
 
-    fn save(&mut self) -> Result<(), NodeError> {
-        self.reset_file()?;
+    /// Where the smallest or largest entry is stored, unless the tree is empty.
+    fn outermost_slot(&mut self, target: Target<'_, K>) -> Result<Option<Slot>, Error> {
+        let last = match target {
+            Target::Min => false,
+            Target::Max => true,
+            Target::Key(_) => unreachable!("only the smallest or largest entry is outermost"),
+        };
 
-        self.data.serialize(&mut Serializer::new(&mut self.file))?;
+        let mut path = None;
+        loop {
+            let node = self.node(path.as_ref())?;
+            match node.data.children.as_ref() {
+                Some(children) => {
+                    let idx = if last { children.len() - 1 } else { 0 };
+                    path = Some(children[idx].clone());
+                }
+                // Only an empty tree has an empty leaf, in which case this finds nothing.
+                None if node.data.keys.is_empty() => return Ok(None),
+                None => {
+                    let idx = if last { node.data.keys.len() - 1 } else { 0 };
+                    return Ok(Some(Slot { path, idx }));
+                }
+            }
+        }
+    }
 
-        Ok(())
+    /// The entry with the smallest key, for reading, changing or removing in place, as taking
+    /// the smallest entry off a priority queue would.
+    pub fn first_entry(&mut self) -> Result<Option<OccupiedEntry<'_, K, V, C>>, Error>
+    where
+        K: Clone,
+    {
+        self.outermost_entry(Target::Min)
     }
 
-    // TODO: This should return Result<Option<V>, NodeError> to handle the case of the value
-    // already existing.
-    fn insert_if_space(&mut self, key: K, value: V) -> Result<(), NodeError> {
-        let idx = &self.data.keys[..].binary_search(&key);
+    /// Like `first_entry`, but for the entry with the largest key.
+    pub fn last_entry(&mut self) -> Result<Option<OccupiedEntry<'_, K, V, C>>, Error>
+    where
+        K: Clone,
+    {
+        self.outermost_entry(Target::Max)
+    }
 
-        match idx {
-            Ok(idx) => Ok(self.data.values[*idx] = value),
-            Err(idx) => {
-                if self.data.keys.len() < self.data.keys.capacity() {
-                    self.data.keys.insert(*idx, key);
-                    self.data.values.insert(*idx, value);
-                    Ok(())
-                } else {
-                    Err(NodeError::NeedsSplit)
+    fn outermost_entry(
+        &mut self,
+        target: Target<'_, K>,
+    ) -> Result<Option<OccupiedEntry<'_, K, V, C>>, Error>
+    where
+        K: Clone,
+    {
+        self.check_writable()?;
+        let slot = match self.outermost_slot(target)? {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+        let key = self.node(slot.path.as_ref())?.data.keys[slot.idx].clone();
+
+        Ok(Some(OccupiedEntry::new(self, key, slot)))
+    }
+
+    /// The entry with the largest key less than or equal to `key`.
+    pub fn floor(&mut self, key: &K) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.closest(key, Target::Max, true)
+    }
+
+    /// The entry with the smallest key greater than or equal to `key`.
+    pub fn ceiling(&mut self, key: &K) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.closest(key, Target::Min, true)
+    }
+
+    /// The entry with the largest key strictly less than `key`, whether or not `key` is in the
+    /// tree.
+    pub fn predecessor(&mut self, key: &K) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.closest(key, Target::Max, false)
+    }
+
+    /// The entry with the smallest key strictly greater than `key`, whether or not `key` is in
+    /// the tree.
+    pub fn successor(&mut self, key: &K) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.closest(key, Target::Min, false)
+    }
+
+    /// Find `key` if `inclusive`, or failing that the closest entry on one side of it: the
+    /// largest entry below it for `Target::Max`, or the smallest above it for `Target::Min`. Each
+    /// node on the way down narrows the range the answer can be in, so the best candidate so far
+    /// is always the one found deepest.
+    fn closest(
+        &mut self,
+        key: &K,
+        side: Target<'_, K>,
+        inclusive: bool,
+    ) -> Result<Option<(K, V)>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let below = match side {
+            Target::Max => true,
+            Target::Min => false,
+            Target::Key(_) => unreachable!("the closest entry is either below or above a key"),
+        };
+
+        let mut best = None;
+        let mut path = None;
+        loop {
+            let (node, cmp) = self.node_and_cmp(path.as_ref(), false)?;
+            let idx = match node.data.find(key, cmp) {
+                Ok(idx) if inclusive => {
+                    best = Some(Slot { path, idx });
+                    break;
                 }
+                // Passing over `key` itself, the search goes on as if it were missing, and would
+                // go just before the keys above it.
+                Ok(idx) if below => idx,
+                Ok(idx) => idx + 1,
+                Err(idx) => idx,
+            };
+
+            // The keys either side of where `key` would go are the closest in this node.
+            if below && idx > 0 {
+                best = Some(Slot {
+                    path: path.clone(),
+                    idx: idx - 1,
+                });
+            } else if !below && idx < node.data.keys.len() {
+                best = Some(Slot {
+                    path: path.clone(),
+                    idx,
+                });
+            }
+
+            match node.data.children.as_ref() {
+                Some(children) => path = Some(children[idx].clone()),
+                None => break,
             }
         }
+
+        let slot = match best {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+        let node = self.node_with_values(slot.path.as_ref())?;
+        let key = node.data.keys[slot.idx].clone();
+        let value = node.data.values[slot.idx].clone();
+
+        Ok(Some((key, value)))
     }
 
-    fn is_leaf(&self) -> bool {
-        self.data.children.is_none()
+    /// Get a handle to the value for `key` that can change it in place. As with changes made
+    /// through an entry, the write-ahead log only records the change along with the next
+    /// `insert` or `remove`.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Result<Option<ValueGuard<'_, K, V>>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        self.check_writable()?;
+        let slot = match self.search(key)? {
+            Ok(slot) => slot,
+            Err(_) => return Ok(None),
+        };
+        self.hold_changes()?;
+        let node = self.node_with_values(slot.path.as_ref())?;
+
+        Ok(Some(ValueGuard::new(node, slot.idx)))
+    }
+
+    /// Change every value in place, by calling `f` on each entry in ascending order of key. Each
+    /// node is loaded once, and every node is changed, so this costs about as much as one pass
+    /// over the tree, where `get_mut` for each key would search from the root every time. The
+    /// changes are made as one, so with the write-ahead log or copy-on-write the whole tree is
+    /// held in memory until they are written together.
+    pub fn update_values<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&K, &mut V),
+    {
+        self.begin()?;
+        // `(node, idx)` for each node on the way down, where `None` stands for the root and
+        // `idx` is the child to go into next. The key before that child comes first.
+        let mut stack = vec![(None, 0)];
+        while let Some((path, idx)) = stack.pop() {
+            let node = self.node_with_values(path.as_ref())?;
+            let data = &mut node.data;
+            let children = match data.children.as_ref() {
+                Some(children) => children,
+                None => {
+                    for (key, value) in data.keys.iter().zip(&mut data.values) {
+                        f(key, value);
+                    }
+                    node.dirty |= !data.keys.is_empty();
+                    continue;
+                }
+            };
+            let Some(child) = children.get(idx).cloned() else {
+                continue;
+            };
+            if let Some(before) = idx.checked_sub(1) {
+                f(&data.keys[before], &mut data.values[before]);
+                node.dirty = true;
+            }
+            stack.push((path, idx + 1));
+            stack.push((Some(child), 0));
+        }
+
+        self.commit()
+    }
+
+    /// Get the entry for `key`, to inspect or change it in place, or to insert it if it's
+    /// missing, without searching the tree again.
+    pub fn entry(&mut self, key: K) -> Result<Entry<'_, K, V, C>, Error> {
+        self.check_writable()?;
+        let entry = match self.search(&key)? {
+            Ok(slot) => Entry::Occupied(OccupiedEntry::new(self, key, slot)),
+            Err(leaf) => Entry::Vacant(VacantEntry::new(self, key, leaf)),
+        };
+
+        Ok(entry)
+    }
+
+    /// Get the value for `key`, or if it's missing, insert the result of `f` and get that,
+    /// searching the tree only once. `f` is only called if the key is missing, and finding the
+    /// key doesn't count as changing its node.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> Result<&V, Error>
+    where
+        F: FnOnce() -> V,
+    {
+        match self.entry(key)? {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => Ok(entry.insert(f())?),
+        }
+    }
+
+    /// Find where `key` is stored, or if it isn't, the leaf it belongs in.
+    fn search<Q>(&mut self, key: &Q) -> Result<Result<Slot, Option<NodeRef>>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        let mut path = None;
+        loop {
+            let (node, cmp) = self.node_and_cmp(path.as_ref(), false)?;
+            match node.data.lookup(key, cmp) {
+                Lookup::Found(idx) => return Ok(Ok(Slot { path, idx })),
+                Lookup::Child(idx) => path = Some(node.data.children()[idx].clone()),
+                Lookup::Missing => return Ok(Err(path)),
+            }
+        }
+    }
+
+    /// The way down from the root to the node holding `key`, or if it isn't in the tree, to the
+    /// leaf it belongs in. Mostly useful for debugging the shape of a tree.
+    pub fn path_to<Q>(&mut self, key: &Q) -> Result<PathStack, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        let mut stack = PathStack::new();
+        let mut path = None;
+        loop {
+            let (node, cmp) = self.node_and_cmp(path.as_ref(), false)?;
+            match node.data.lookup(key, cmp) {
+                Lookup::Child(idx) => {
+                    let next = node.data.children()[idx].clone();
+                    let mut step = Step::new(idx, node.data.children().len());
+                    step.node = path.replace(next);
+                    stack.push(step);
+                }
+                Lookup::Found(_) | Lookup::Missing => return Ok(stack),
+            }
+        }
+    }
+
+    /// Whether the tree holds an entry for `key`. Unlike `get`, this doesn't need to clone the
+    /// value.
+    pub fn contains_key<Q>(&mut self, key: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: Serialize + ?Sized,
+    {
+        if !self.might_contain(key) {
+            return Ok(false);
+        }
+
+        let mut path = None;
+        loop {
+            let (node, cmp) = self.node_and_cmp(path.as_ref(), false)?;
+            match node.data.lookup(key, cmp) {
+                Lookup::Found(_) => return Ok(true),
+                Lookup::Child(idx) => path = Some(node.data.children()[idx].clone()),
+                Lookup::Missing => return Ok(false),
+            }
+        }
+    }
+
+    /// Iterate over the entries of the tree in ascending key order.
+    pub fn iter(&mut self) -> Iter<'_, K, V, C>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        Iter::new(self)
+    }
+
+    /// A cursor at the first entry of the tree, which can be moved to any key and stepped either
+    /// way from there.
+    pub fn cursor(&mut self) -> Result<Cursor<'_, K, V, C>, Error> {
+        Cursor::new(self)
+    }
+
+    /// Iterate over the keys of the tree in ascending order, without cloning any values. Values
+    /// are kept in files of their own, which aren't read.
+    pub fn keys(&mut self) -> Keys<'_, K, V, C>
+    where
+        K: Clone,
+    {
+        Keys::new(self)
+    }
+
+    /// Iterate over the values of the tree in ascending order of their keys, without cloning
+    /// any keys.
+    pub fn values(&mut self) -> Values<'_, K, V, C>
+    where
+        V: Clone,
+    {
+        Values::new(self)
+    }
+
+    /// Iterate over the entries of the tree in descending key order.
+    pub fn iter_rev(&mut self) -> IterRev<'_, K, V, C>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        IterRev::new(self)
+    }
+
+    /// Iterate over the entries of the tree whose keys fall within `range`, in ascending key
+    /// order. A range whose start is after its end yields nothing.
+    pub fn range<R>(&mut self, range: R) -> Range<'_, K, V, C>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+        V: Clone,
+    {
+        let start = range.start_bound().cloned();
+        let end = range.end_bound().cloned();
+
+        Range::new(self, start, end)
+    }
+
+    /// Put the entries whose keys fall within `range` in `out`, in ascending key order, returning
+    /// how many there were. Unless `append` is set, `out` is cleared first, so that one buffer
+    /// can be reused across many queries without allocating each time. If reading a node fails,
+    /// `out` keeps the entries found before it.
+    pub fn range_into<R>(
+        &mut self,
+        range: R,
+        out: &mut Vec<(K, V)>,
+        append: bool,
+    ) -> Result<usize, Error>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+        V: Clone,
+    {
+        if !append {
+            out.clear();
+        }
+        let before = out.len();
+        for entry in self.range(range) {
+            out.push(entry?);
+        }
+
+        Ok(out.len() - before)
+    }
+
+    /// The number of keys that fall within `range`, counted from the keys of the nodes alone, so
+    /// no values are loaded.
+    pub fn range_count<R>(&mut self, range: R) -> Result<usize, Error>
+    where
+        R: RangeBounds<K>,
+    {
+        let mut count = 0;
+        self.visit_range(&range, |keys| count += keys.len())?;
+
+        Ok(count)
+    }
+
+    /// Remove every entry whose key falls within `range`, returning how many there were. The
+    /// keys are found as `range_count` finds them, and removed as one change, so with the
+    /// write-ahead log enabled they are logged together. A range that covers the whole tree
+    /// clears it instead.
+    pub fn remove_range<R>(&mut self, range: R) -> Result<usize, Error>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+    {
+        self.check_writable()?;
+        let mut keys = Vec::new();
+        self.visit_range(&range, |found| keys.extend_from_slice(found))?;
+
+        self.remove_keys(keys)
+    }
+
+    /// Remove every entry whose key falls outside `keep`, returning how many there were, such as
+    /// to keep only a sliding window of a time series. The keys on either side of `keep` are
+    /// found as `remove_range` finds them, and removed together as one change.
+    pub fn retain_range<R>(&mut self, keep: R) -> Result<usize, Error>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+    {
+        self.check_writable()?;
+        // The two sides of an empty or inverted window would overlap, and between them cover the
+        // whole tree.
+        let empty = match (keep.start_bound(), keep.end_bound()) {
+            (Bound::Included(start), Bound::Included(end)) => {
+                self.cmp.compare(start, end) == Ordering::Greater
+            }
+            (
+                Bound::Included(start) | Bound::Excluded(start),
+                Bound::Included(end) | Bound::Excluded(end),
+            ) => self.cmp.compare(start, end) != Ordering::Less,
+            _ => false,
+        };
+        if empty {
+            let removed = self.len;
+            if removed > 0 {
+                self.clear()?;
+            }
+            return Ok(removed);
+        }
+
+        let mut keys = Vec::new();
+        let below = match keep.start_bound() {
+            Bound::Included(start) => Some(Bound::Excluded(start)),
+            Bound::Excluded(start) => Some(Bound::Included(start)),
+            Bound::Unbounded => None,
+        };
+        if let Some(end) = below {
+            self.visit_range(&(Bound::Unbounded, end), |found| {
+                keys.extend_from_slice(found)
+            })?;
+        }
+        let above = match keep.end_bound() {
+            Bound::Included(end) => Some(Bound::Excluded(end)),
+            Bound::Excluded(end) => Some(Bound::Included(end)),
+            Bound::Unbounded => None,
+        };
+        if let Some(start) = above {
+            self.visit_range(&(start, Bound::Unbounded), |found| {
+                keys.extend_from_slice(found)
+            })?;
+        }
+
+        self.remove_keys(keys)
     }
 
-    fn load(path: &NodeRef) -> Result<Self, NodeError> {
-        let file = OpenOptions::new().read(true).write(true).open(path)?;
-        let data = rmp_serde::from_read(file.try_clone()?)?;
+    /// Remove the entries of `keys`, which are each in the tree once, as one change, returning
+    /// how many were removed.
+    fn remove_keys(&mut self, keys: Vec<K>) -> Result<usize, Error> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        if keys.len() == self.len {
+            self.clear()?;
+            return Ok(keys.len());
+        }
+
+        self.begin()?;
+        let min_keys = self.min_keys();
+        let mut removed = 0;
+        for key in &keys {
+            let entry = Self::remove_from(
+                &mut self.node_cache,
+                &mut self.root_node,
+                &self.cmp,
+                min_keys,
+                Target::Key(key),
+            )?;
+            if self.root_node.data.keys.is_empty() && !self.root_node.is_leaf() {
+                self.collapse_root()?;
+            }
+            if entry.is_some() {
+                self.len -= 1;
+                removed += 1;
+            }
+        }
+        self.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Call `f` with the keys of each node that fall within `range`, in no particular order.
+    /// Only the nodes the ends of the range cut through are searched. Every key in a node
+    /// between them is in the range, as is every key under a child between them.
+    fn visit_range<R, F>(&mut self, range: &R, mut f: F) -> Result<(), Error>
+    where
+        R: RangeBounds<K>,
+        F: FnMut(&[K]),
+    {
+        let (start, end) = (range.start_bound(), range.end_bound());
+        // Each node still to visit, along with whether the start and the end of the range cut
+        // through it.
+        let mut stack = vec![(None, true, true)];
+        while let Some((path, at_start, at_end)) = stack.pop() {
+            let (node, cmp) = self.node_and_cmp(path.as_ref(), false)?;
+            let keys = &node.data.keys;
+            let lo = match start {
+                Bound::Included(start) if at_start => {
+                    keys.partition_point(|key| cmp.compare(key, start) == Ordering::Less)
+                }
+                Bound::Excluded(start) if at_start => {
+                    keys.partition_point(|key| cmp.compare(key, start) != Ordering::Greater)
+                }
+                _ => 0,
+            };
+            let hi = match end {
+                Bound::Included(end) if at_end => {
+                    keys.partition_point(|key| cmp.compare(key, end) != Ordering::Greater)
+                }
+                Bound::Excluded(end) if at_end => {
+                    keys.partition_point(|key| cmp.compare(key, end) == Ordering::Less)
+                }
+                _ => keys.len(),
+            };
+            // A key both past the end and before the start means the range is empty.
+            if hi < lo {
+                continue;
+            }
+
+            f(&keys[lo..hi]);
+            if let Some(children) = node.data.children.as_ref() {
+                for (idx, child) in children.iter().enumerate().take(hi + 1).skip(lo) {
+                    stack.push((
+                        Some(child.clone()),
+                        at_start && idx == lo,
+                        at_end && idx == hi,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// If the key was present, remove it and return its value. If the key was not present, return
+    /// None.
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        Ok(self.remove_entry(key)?.map(|(_, value)| value))
+    }
+
+    /// Like `remove`, but returning the key as it was stored along with its value, which may
+    /// differ from `key` in ways the comparator doesn't see.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Result<Option<(K, V)>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        self.begin()?;
+        let min_keys = self.min_keys();
+        let removed = Self::remove_from(
+            &mut self.node_cache,
+            &mut self.root_node,
+            &self.cmp,
+            min_keys,
+            Target::Key(key),
+        )?;
+
+        if self.root_node.data.keys.is_empty() && !self.root_node.is_leaf() {
+            self.collapse_root()?;
+        }
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        self.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Render the tree as a Graphviz DOT graph, for debugging. Each node is drawn as a record of
+    /// its keys, identified by its file name, with an edge to each of its children labelled by
+    /// the child's index.
+    pub fn to_dot(&mut self) -> Result<String, Error>
+    where
+        K: Debug,
+    {
+        dot::render(self)
+    }
+
+    /// Remove every file in the backing directory that the tree no longer refers to, such as one
+    /// left behind by a change that was interrupted, and return how many were removed. The tree
+    /// is flushed first, and nothing else can hold it, a cursor or iterator included, while this
+    /// runs. The files of earlier versions a `Reader` is still reading are kept.
+    pub fn gc(&mut self) -> Result<usize, Error> {
+        self.check_writable()?;
+        self.flush()?;
+
+        let live = self.live_files()?;
+        let mut removed = 0;
+        for path in self.storage.list(&self.backing_dir)? {
+            let name = path.file_name().unwrap().to_string_lossy();
+            // Anything that isn't named as this tree names its files belongs to another tree.
+            if self.naming.owns(&name) && !live.contains(&path) {
+                self.storage.remove(&path)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Every file the tree still needs: the files of the tree as a whole, those of each node
+    /// reachable from the root, and those of the earlier versions that readers are reading or
+    /// that are kept for `snapshot_at`.
+    fn live_files(&mut self) -> Result<HashSet<PathBuf>, Error> {
+        let mut live: HashSet<PathBuf> = [META_FILE, LOCK_FILE, wal::WAL_FILE, BLOOM_FILE]
+            .iter()
+            .map(|name| self.tree_file(name))
+            .collect();
+        let mut nodes = Vec::new();
+        // `None` stands for the root.
+        let mut stack = vec![None];
+        while let Some(path) = stack.pop() {
+            let node = self.node(path.as_ref())?;
+            nodes.push(node.path.clone());
+            stack.extend(node.data.children.iter().flatten().cloned().map(Some));
+        }
+        for path in nodes {
+            live.extend(external::external_files::<V>(
+                &*self.storage,
+                self.encoding,
+                &path,
+            )?);
+            live.insert(values_path(&path));
+            live.insert(path);
+        }
+        live.extend(self.read_files()?);
+
+        Ok(live)
+    }
+
+    /// How many nodes and keys each level of the tree holds, and how full its nodes are, from
+    /// the root at index 0 down to the leaves, loading every node to find out. A level much
+    /// emptier than the others, such as after many removals, is a sign the tree is worth
+    /// compacting.
+    pub fn fill_histogram(&mut self) -> Result<Vec<LevelStats>, Error> {
+        stats::levels(self)
+    }
+
+    /// How much storage the tree takes up, by file size, going by the files in the backing
+    /// directory. Changes that haven't been flushed yet don't count, and neither do files of
+    /// other trees sharing the directory.
+    pub fn disk_usage(&mut self) -> Result<DiskUsage, Error> {
+        stats::disk_usage(self)
+    }
+
+    /// Rewrite the tree as densely as `build_sorted` would build it from the same entries, to
+    /// fill back up the nodes that removals have left half empty. Every node goes in a fresh
+    /// file, so the new tree only takes over once the meta file is replaced to name its root,
+    /// and if that doesn't happen the old one is left as it was. The old files are removed by
+    /// `gc` afterwards. Every entry is held in memory while the tree is rebuilt.
+    pub fn compact(&mut self) -> Result<(), Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.check_writable()?;
+        self.flush()?;
+        let entries: Vec<(K, V)> = self.iter().collect::<Result<_, _>>()?;
+
+        self.node_cache.clear();
+        let root_path =
+            Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming);
+        self.root_node = Node::new(root_path, self.capacity);
+        self.len = 0;
+        // Leaving out every key that has been removed since it was last rebuilt.
+        self.reset_bloom_filter(entries.len());
+        bulk::load(self, entries)?;
+        self.flush()?;
+        self.gc()?;
+
+        Ok(())
+    }
+
+    /// Copy the tree into `new_dir`, which mustn't exist yet, and open the copy, which has
+    /// nothing to do with this tree from then on. The tree is flushed first, and only the nodes
+    /// reachable from the root are copied, so the copy starts with an empty free list. Node
+    /// files name their children by path, so each is written out again rather than copied as
+    /// it is.
+    pub fn clone_to(&mut self, new_dir: PathBuf) -> Result<Self, Error>
+    where
+        C: Clone,
+    {
+        self.flush()?;
+        self.storage.create_dir(&new_dir)?;
+        let namespace = self.naming.namespace.as_deref();
+        let lock = lock(&*self.storage, &new_dir, namespace, false)?;
+
+        let rebase = |path: &Path| new_dir.join(path.file_name().unwrap());
+        let mut stack = vec![self.root_node.path.clone()];
+        while let Some(path) = stack.pop() {
+            let mut node = Node::<K, V>::load(&*self.storage, self.encoding, &path)?;
+            node.load_values(&*self.storage, self.encoding)?;
+            if let Some(children) = node.data.children.as_mut() {
+                stack.extend(children.iter().cloned());
+                for child in children.iter_mut() {
+                    *child = rebase(child);
+                }
+            }
+            save_node(
+                &*self.storage,
+                self.encoding,
+                &rebase(&path),
+                &mut node.data,
+            )?;
+        }
+        let meta = Meta {
+            free: Vec::new(),
+            versions: Vec::new(),
+            ..self.meta()
+        };
+        meta.save(&*self.storage, &tree_file(&new_dir, META_FILE, namespace))?;
+
+        let cmp = self.cmp.clone();
+        let key = self.encoding.key;
+        Self::load(
+            self.storage.clone(),
+            new_dir,
+            namespace,
+            cmp,
+            lock,
+            false,
+            key,
+        )
+    }
+
+    /// Open a read-only view of the tree as it is now in `snapshot_dir`, which mustn't exist
+    /// yet, and which later changes to this tree don't show up in. The tree is flushed first.
+    /// Every file of the tree is only ever replaced whole, never written over, so rather than
+    /// copy anything the snapshot links the files of the nodes reachable from the root into its
+    /// directory, and keeps the versions it linked once this tree replaces them.
+    pub fn snapshot(&mut self, snapshot_dir: PathBuf) -> Result<Self, Error>
+    where
+        C: Clone,
+    {
+        self.flush()?;
+        self.storage.create_dir(&snapshot_dir)?;
+        let namespace = self.naming.namespace.clone();
+        let namespace = namespace.as_deref();
+        let lock_path = tree_file(&snapshot_dir, LOCK_FILE, namespace);
+        self.storage.write(&lock_path, &[])?;
+        let lock = lock(&*self.storage, &snapshot_dir, namespace, true)?;
+
+        let mut files = vec![self.tree_file(META_FILE)];
+        let mut nodes = Vec::new();
+        // `None` stands for the root.
+        let mut stack = vec![None];
+        while let Some(path) = stack.pop() {
+            let node = self.node(path.as_ref())?;
+            nodes.push(node.path.clone());
+            stack.extend(node.data.children.iter().flatten().cloned().map(Some));
+        }
+        for path in nodes {
+            files.extend(external::external_files::<V>(
+                &*self.storage,
+                self.encoding,
+                &path,
+            )?);
+            files.push(values_path(&path));
+            files.push(path);
+        }
+        for file in files {
+            let to = snapshot_dir.join(file.file_name().unwrap());
+            self.storage.link(&file, &to)?;
+        }
+
+        let cmp = self.cmp.clone();
+        Self::load(
+            self.storage.clone(),
+            snapshot_dir,
+            namespace,
+            cmp,
+            lock,
+            true,
+            self.encoding.key,
+        )
+    }
+
+    /// Write the whole tree to `w` as one stream, which `import` can make a tree from again
+    /// elsewhere. The stream starts with the tree's capacity and the version of the format, and
+    /// goes on with each entry in ascending key order, each one prefixed with its length.
+    pub fn export<W>(&mut self, w: W) -> Result<(), Error>
+    where
+        K: Clone,
+        V: Clone,
+        W: Write,
+    {
+        export::export(self, w)
+    }
+
+    /// Write the entries of the tree to `w` as a JSON array of `{"key": ..., "value": ...}`
+    /// objects in ascending key order, for other tools to read. Entries are written as they are
+    /// read from the tree, so the tree is never held in memory as a whole.
+    #[cfg(feature = "json")]
+    pub fn to_json_writer<W>(&mut self, w: W) -> Result<(), Error>
+    where
+        K: Clone,
+        V: Clone,
+        W: Write,
+    {
+        json::write(self, w)
+    }
+
+    /// Describe the shape of the tree, loading every node to do so.
+    pub fn stats(&mut self) -> Result<TreeStats, Error> {
+        stats::collect(self)
+    }
+
+    /// Check that the tree is well-formed, loading every node to do so, and describe the first
+    /// problem found as `Error::Invalid`. In a valid tree, the keys of each node are sorted and
+    /// lie between the separators either side of it in its parent, every node other than the
+    /// root is as full as the merge threshold asks, every internal node has one more child than
+    /// it has keys, all leaves are at the same depth, and the tree holds `len` entries.
+    ///
+    /// Every node is read through the cache, so a node already cached isn't read from storage
+    /// again. `scrub` reads every file back.
+    pub fn validate(&mut self) -> Result<(), Error>
+    where
+        K: Clone,
+    {
+        let mut leaf_depth = None;
+        let len = self.validate_node(None, None, None, 0, &mut leaf_depth)?;
+        if len != self.len {
+            return Err(Error::Invalid(format!(
+                "the tree holds {} entries, but its length is {}",
+                len, self.len
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check the tree for damage without stopping at the first problem, for scrubbing a tree
+    /// every so often: every file of every node is read back from storage and its checksum
+    /// checked, and once all of them pass, the tree is checked as `validate` does. The tree is
+    /// flushed first. Unlike `repair`, nothing is changed, whatever turns up.
+    pub fn scrub(&mut self) -> Result<ScrubReport, Error>
+    where
+        K: Clone,
+    {
+        scrub::scrub(self)
+    }
+
+    /// Check the subtree at `path` as `validate` does, given the separators either side of it,
+    /// and return the number of entries in it. `leaf_depth` is the depth of the first leaf found,
+    /// which every other leaf must match.
+    fn validate_node(
+        &mut self,
+        path: Option<&NodeRef>,
+        lower: Option<&K>,
+        upper: Option<&K>,
+        depth: usize,
+        leaf_depth: &mut Option<usize>,
+    ) -> Result<usize, Error>
+    where
+        K: Clone,
+    {
+        let capacity = self.capacity;
+        let min_keys = self.min_keys();
+        let (node, cmp) = self.node_and_cmp(path, true)?;
+        let data = &node.data;
+        let less = |a: &K, b: &K| cmp.compare(a, b) == Ordering::Less;
+        let invalid = |problem: &str| {
+            Err(Error::Invalid(format!(
+                "node {} {}",
+                node.path.display(),
+                problem
+            )))
+        };
+
+        if data.values.len() != data.keys.len() {
+            return invalid("has a different number of keys and values");
+        }
+        if data.keys.len() > capacity {
+            return invalid(&format!("has more than {} keys", capacity));
+        }
+        if path.is_some() && data.keys.len() < min_keys {
+            return invalid(&format!("has fewer than {} keys", min_keys));
+        }
+        if !data.keys.windows(2).all(|pair| less(&pair[0], &pair[1])) {
+            return invalid("has keys out of order");
+        }
+        if let (Some(lower), Some(first)) = (lower, data.keys.first()) {
+            if !less(lower, first) {
+                return invalid("has a key not above the separator before it");
+            }
+        }
+        if let (Some(upper), Some(last)) = (upper, data.keys.last()) {
+            if !less(last, upper) {
+                return invalid("has a key not below the separator after it");
+            }
+        }
+
+        let children = match data.children.as_ref() {
+            Some(children) => children,
+            None => {
+                return match *leaf_depth.get_or_insert(depth) {
+                    leaf_depth if leaf_depth == depth => Ok(data.keys.len()),
+                    _ => invalid("is a leaf at a different depth from the first"),
+                };
+            }
+        };
+        if children.len() != data.keys.len() + 1 {
+            return invalid("does not have one more child than it has keys");
+        }
+        if data.keys.is_empty() {
+            return invalid("is an internal node with no keys");
+        }
+
+        let keys = data.keys.clone();
+        let children = children.clone();
+        let mut len = keys.len();
+        for (idx, child) in children.iter().enumerate() {
+            let lower = if idx == 0 {
+                lower
+            } else {
+                Some(&keys[idx - 1])
+            };
+            let upper = keys.get(idx).or(upper);
+            len += self.validate_node(Some(child), lower, upper, depth + 1, leaf_depth)?;
+        }
+
+        Ok(len)
+    }
+
+    /// Remove every entry, leaving an empty root. The root and the meta file are written first,
+    /// so that if this is interrupted, the files of the other nodes are at worst left for `gc`.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.check_writable()?;
+        let mut paths = Vec::new();
+        // `None` stands for the root.
+        let mut stack = vec![None];
+        while let Some(path) = stack.pop() {
+            let node = self.node(path.as_ref())?;
+            stack.extend(node.data.children.iter().flatten().cloned().map(Some));
+            paths.extend(path);
+        }
+        paths.extend(self.node_cache.clear());
+
+        // Readers may still be reading the old version, so with copy-on-write the empty root
+        // goes in a file of its own, and the old files are left for `gc`.
+        let root_path = if self.copy_on_write {
+            Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming)
+        } else {
+            self.root_node.path.clone()
+        };
+        self.root_node = Node::new(root_path, self.capacity);
+        self.root_node.save(&*self.storage, self.encoding)?;
+        self.len = 0;
+        self.reset_bloom_filter(0);
+        self.save_bloom_filter()?;
+        self.next_version();
+        if self.copy_on_write {
+            return self.save_meta();
+        }
+        self.free_nodes.extend(paths.iter().cloned());
+        self.save_meta()?;
+        for path in &paths {
+            remove_node::<V>(&*self.storage, self.encoding, path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove every entry for which `f` returns false. `f` is given each key and value where they
+    /// are stored, in ascending order of key, and the entries it rejects are removed once it has
+    /// seen them all.
+    pub fn retain<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&K, &V) -> bool,
+        K: Clone,
+    {
+        self.extract_if(|key, value| !f(key, value))?;
+
+        Ok(())
+    }
+
+    /// Remove every entry for which `f` returns true, and return them in ascending order of key.
+    /// As with `retain`, `f` sees every entry before any is removed.
+    pub fn extract_if<F>(&mut self, mut f: F) -> Result<Vec<(K, V)>, Error>
+    where
+        F: FnMut(&K, &V) -> bool,
+        K: Clone,
+    {
+        let mut matched = Vec::new();
+        let mut iter = Iter::new(self);
+        while let Some(key) = iter.advance(true, |data, idx| {
+            let (key, value) = (&data.keys[idx], &data.values[idx]);
+            f(key, value).then(|| key.clone())
+        }) {
+            matched.extend(key?);
+        }
+
+        let mut extracted = Vec::with_capacity(matched.len());
+        for key in matched {
+            extracted.extend(self.remove_entry(&key)?);
+        }
+
+        Ok(extracted)
+    }
+
+    /// Move the entries with keys of `key` and above into a new tree in `backing_dir`, leaving
+    /// the rest in this one, as `std::collections::BTreeMap::split_off` does. The new tree has
+    /// the same capacity, order and encoding as this one, and is built from the leaves up as
+    /// `build_sorted` does.
+    pub fn split_off(&mut self, key: &K, backing_dir: PathBuf) -> Result<Self, Error>
+    where
+        K: Clone,
+        V: Clone,
+        C: Clone,
+    {
+        self.check_writable()?;
+        let upper: Vec<(K, V)> = self.range(key.clone()..).collect::<Result<_, _>>()?;
+        let mut other = Self::create(
+            self.storage.clone(),
+            backing_dir,
+            self.capacity,
+            self.merge_threshold,
+            self.cmp.clone(),
+            self.encoding,
+            self.naming.clone(),
+        )?;
+        bulk::load(&mut other, upper)?;
+
+        for key in other.keys() {
+            self.remove(&key?)?;
+        }
+
+        Ok(other)
+    }
+
+    /// Move every entry of `other` into this tree, leaving `other` empty. Where both trees have
+    /// a key, the value from `other` wins, as with `std::collections::BTreeMap::append`.
+    pub fn append(&mut self, other: &mut Self) -> Result<(), Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.check_writable()?;
+        other.check_writable()?;
+        for entry in other.iter() {
+            let (key, value) = entry?;
+            self.insert(key, value)?;
+        }
+
+        other.clear()
+    }
+
+    /// Change how many nodes below the root are kept in memory, saving any changed nodes that
+    /// no longer fit. The default is 256. A capacity of 0 effectively disables caching, so every
+    /// access to a node reads it from disk and every change is written as soon as the next node
+    /// is accessed.
+    pub fn set_cache_capacity(&mut self, capacity: usize) -> Result<(), Error> {
+        self.node_cache.resize(capacity)
+    }
+
+    /// Get ready for about `additional` more entries, such as before a large `extend`. The cache
+    /// grows to hold as many nodes as the tree could then have, so that nodes aren't evicted
+    /// and read back again while the entries go in, and a Bloom filter is sized for them once
+    /// rather than rebuilt each time it fills, up to the most keys a filter is ever sized for.
+    /// The cache doesn't shrink back once the entries are in: it stays this large, and may hold
+    /// that many nodes in memory, until `set_cache_capacity` sets it back. This changes nothing
+    /// about what the tree holds, so leaving it out only costs time. Asking for more entries
+    /// than the tree could count fails with `Error::TooManyEntries`.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        let keys = self
+            .len
+            .checked_add(additional)
+            .ok_or(Error::TooManyEntries(additional))?;
+        // Every key lives in one node, and every node but the root holds at least the minimum.
+        let nodes = keys.div_ceil(self.min_keys());
+        let (_, capacity) = self.node_cache.occupancy();
+        if nodes > capacity {
+            self.node_cache.resize(nodes)?;
+        }
+
+        self.reserve_bloom_filter(keys)
+    }
+
+    /// Also limit the nodes below the root that are kept in memory to `bytes` between them, as
+    /// measured by serializing each one, or stop doing so with `None`. Nodes vary in size with
+    /// their keys and values, so this bounds the memory the cache takes where a capacity in
+    /// nodes can't. The capacity still applies as well. The most recently used node is kept
+    /// however big it is.
+    pub fn set_cache_budget(&mut self, bytes: Option<usize>) -> Result<(), Error> {
+        self.node_cache.set_budget(bytes)
+    }
+
+    /// The number of nodes below the root that are held in memory.
+    pub fn cached_nodes(&self) -> usize {
+        self.node_cache.occupancy().0
+    }
+
+    /// Flush the tree and give back the memory it holds on to beyond what it needs to go on, as
+    /// a long-lived process may want to after a batch of work. The leaves are dropped from the
+    /// cache, leaving the internal nodes every search passes through, and spare capacity is
+    /// released from the nodes kept and the tree's own bookkeeping.
+    pub fn shrink_to_fit(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.node_cache.shrink_to_fit()?;
+        self.root_node.data.shrink_to_fit();
+        self.free_nodes.shrink_to_fit();
+        self.relocated.shrink_to_fit();
+
+        Ok(())
+    }
+
+    /// The serialized bytes the nodes in the cache add up to, if it has a budget.
+    pub fn cache_bytes(&mut self) -> Result<Option<usize>, Error> {
+        self.node_cache.bytes()
+    }
+
+    /// How often nodes below the root were found in the cache rather than read from disk, since
+    /// the tree was opened or the statistics were last reset.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.node_cache.stats()
+    }
+
+    pub fn reset_cache_stats(&mut self) {
+        self.node_cache.reset_stats();
+    }
+
+    /// Turn the write-ahead log on or off. With it on, each `insert` and `remove` is written out
+    /// in full before it returns, first to a log in the backing directory and then to the nodes
+    /// themselves, so that a crash can't leave the tree half-changed. This makes writes slower,
+    /// since nothing is left to be written back later.
+    pub fn set_write_ahead_log(&mut self, enabled: bool) -> Result<(), Error> {
+        // Whatever was changed without the log is written out without it.
+        self.flush()?;
+        self.write_ahead_log = enabled;
+
+        Ok(())
+    }
+
+    /// Turn copy-on-write on or off. With it on, each change is written to fresh files for the
+    /// nodes it touches, along with every node above them up to a fresh root, and takes effect
+    /// when the meta file is replaced to name the new root. Nothing a `Reader` is reading is
+    /// ever written over, and each change makes a version of its own, which is kept for
+    /// `snapshot_at` as `set_version_history` allows. The files of earlier versions stay until
+    /// `gc` finds that neither a reader nor a kept version needs them. Like the write-ahead log,
+    /// this makes writes slower, since each change is flushed on its own.
+    pub fn set_copy_on_write(&mut self, enabled: bool) -> Result<(), Error> {
+        self.flush()?;
+        if enabled == self.copy_on_write {
+            return Ok(());
+        }
+        self.check_writable()?;
+        self.copy_on_write = enabled;
+        if enabled {
+            self.versions.push(self.current());
+        } else {
+            // Nodes are written over from now on, so no earlier version survives.
+            self.versions.clear();
+        }
+
+        self.save_meta()
+    }
+
+    /// Write every node that has changed since it was last saved, along with the tree's
+    /// metadata. Changes are otherwise only written when a node is evicted from the cache or the
+    /// tree is dropped.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.write_out(true)
+    }
+
+    /// Write out every change as `flush` does, and the Bloom filter as well if `save_filter`.
+    /// The filter is written whole, so it is left out of the flush that commits each change with
+    /// the write-ahead log or copy-on-write. Until it is next written, the meta file names a
+    /// generation the filter's file doesn't have, so after a crash it is built again.
+    fn write_out(&mut self, save_filter: bool) -> Result<(), Error> {
+        // A read-only tree has nothing to write, and mustn't touch the meta file either.
+        if self.read_only {
+            return Ok(());
+        }
+
+        let changed = self.root_node.dirty
+            || self.node_cache.dirty().next().is_some()
+            || !self.node_cache.deleted().is_empty();
+        if self.copy_on_write {
+            self.node_cache.defer_evictions()?;
+            self.relocate()?;
+            // Readers may still be reading the deleted nodes, so their files are left for `gc`.
+            self.node_cache.forget_deleted();
+        }
+        if changed {
+            self.next_version();
+        }
+        let removed = self.node_cache.flush()?;
+        self.free_nodes.extend(removed);
+        self.root_node.flush(&*self.storage, self.encoding)?;
+        if save_filter {
+            self.save_bloom_filter()?;
+        }
+        // With copy-on-write, this is what swaps in the new version.
+        self.save_meta()?;
+        self.storage.barrier()?;
+        if self.copy_on_write {
+            self.node_cache.resume_evictions()?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush the tree and close it, returning any error from writing it out. Dropping a tree
+    /// flushes it too, but has no way to report a failure.
+    pub fn close(mut self) -> Result<(), Error> {
+        self.flush()
+    }
+
+    fn meta(&self) -> Meta {
+        let name = |path: &NodeRef| path.file_name().unwrap().to_string_lossy().into_owned();
+
+        Meta {
+            version: FORMAT_VERSION,
+            capacity: self.capacity,
+            merge_threshold: self.merge_threshold,
+            root: name(&self.root_node.path),
+            len: self.len,
+            free: self.free_nodes.iter().map(name).collect(),
+            encoding: self.encoding,
+            naming: self.naming.clone(),
+            current_version: self.version,
+            versions: self.versions.clone(),
+            bloom_filter: self.bloom.as_ref().map(BloomFilter::generation),
+        }
+    }
+
+    fn save_meta(&self) -> Result<(), Error> {
+        self.meta().save(&*self.storage, &self.tree_file(META_FILE))
+    }
+
+    /// The path of the file of the tree as a whole called `name`, in the tree's namespace.
+    fn tree_file(&self, name: &str) -> PathBuf {
+        tree_file(&self.backing_dir, name, self.naming.namespace.as_deref())
+    }
+
+    /// Called before each change to the tree. With the write-ahead log or copy-on-write enabled,
+    /// hold every change in memory until `commit`.
+    fn begin(&mut self) -> Result<(), Error> {
+        self.check_writable()?;
+        self.grow_bloom_filter()?;
+        if self.write_ahead_log || self.copy_on_write || self.transaction {
+            self.node_cache.defer_evictions()?;
+        }
+
+        Ok(())
+    }
+
+    /// Fail with `Error::ReadOnly` if the tree was opened read-only.
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        Ok(())
+    }
+
+    /// Called after each change to the tree. With the write-ahead log enabled, record every
+    /// node the change touched in the log before writing any of them, so that the change as a
+    /// whole survives a crash or doesn't happen at all.
+    ///
+    /// With copy-on-write, the change is flushed as a version of its own instead, which needs
+    /// no log, since nothing refers to it until the meta file is replaced.
+    ///
+    /// Inside `transaction`, nothing is committed until the transaction as a whole is.
+    fn commit(&mut self) -> Result<(), Error> {
+        if self.transaction {
+            return Ok(());
+        }
+        if self.copy_on_write {
+            return self.write_out(false);
+        }
+        if !self.write_ahead_log {
+            return Ok(());
+        }
+
+        let root = Some(&self.root_node).filter(|node| node.dirty);
+        let nodes = self.node_cache.dirty().chain(root);
+        wal::log(
+            &*self.storage,
+            &self.backing_dir,
+            self.naming.namespace.as_deref(),
+            nodes,
+            self.node_cache.deleted(),
+            // The flush that follows moves the tree on to the next version.
+            &Meta {
+                current_version: self.version + 1,
+                ..self.meta()
+            },
+        )?;
+        self.write_out(false)?;
+        wal::clear(
+            &*self.storage,
+            &self.backing_dir,
+            self.naming.namespace.as_deref(),
+        )?;
+
+        self.node_cache.resume_evictions()
+    }
+
+    /// Move the current root into a fresh file and replace it with an empty internal node whose
+    /// only child is the old root, then split that child. This is the only way the tree grows in
+    /// height.
+    fn split_root(&mut self) -> Result<(), Error> {
+        let root_path = self.root_node.path.clone();
+        let old_root_ref =
+            Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming);
+
+        let mut new_root = Node::new(root_path, self.capacity);
+        new_root.data.children = Some(vec![old_root_ref.clone()]);
+        // The new root overwrites the old one's file when it is saved.
+        new_root.fresh = self.root_node.fresh;
+        let mut old_root = mem::replace(&mut self.root_node, new_root);
+        old_root.path = old_root_ref;
+        old_root.fresh = true;
+
+        let sibling_ref =
+            Self::new_node_name(&mut self.free_nodes, &self.backing_dir, &mut self.naming);
+        let sibling = Self::split_child(&mut self.root_node, 0, &mut old_root, sibling_ref)?;
+        self.node_cache.put(old_root)?;
+        self.node_cache.put(sibling)?;
+
+        Ok(())
+    }
+
+    /// Split the full child at `idx` of `parent`, moving the median up into `parent`. The upper
+    /// half of the child is written to a new node at `sibling_ref`, which is returned.
+    fn split_child(
+        parent: &mut Node<K, V>,
+        idx: usize,
+        child: &mut Node<K, V>,
+        sibling_ref: NodeRef,
+    ) -> Result<Node<K, V>, Error> {
+        let (key, value, data) = child.data.split();
+        let mut sibling = Node::create(sibling_ref, data);
+
+        parent.data.keys.insert(idx, key);
+        parent.data.values.insert(idx, value);
+        parent
+            .data
+            .children_mut()
+            .insert(idx + 1, sibling.path.clone());
+
+        sibling.dirty = true;
+        child.dirty = true;
+        parent.dirty = true;
+
+        Ok(sibling)
+    }
+
+    /// Replace an empty internal root with its only child. This is the only way the tree shrinks
+    /// in height.
+    fn collapse_root(&mut self) -> Result<(), Error> {
+        let child = self.node_cache.take(&self.root_node.data.children()[0])?;
+        let data = self.node_cache.delete(child);
+
+        self.root_node.data = data;
+        self.root_node.dirty = true;
+
+        Ok(())
+    }
+
+    /// Remove `target` from the subtree rooted at `node`. Every node descended into is first
+    /// topped up so that it can lose a key without dropping below the minimum occupancy, so the
+    /// removal never has to walk back up the tree.
+    fn remove_from<Q>(
+        node_cache: &mut NodeCache<K, V>,
+        node: &mut Node<K, V>,
+        cmp: &C,
+        min_keys: usize,
+        target: Target<'_, Q>,
+    ) -> Result<Option<(K, V)>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        if node.is_leaf() {
+            let idx = match target {
+                Target::Key(key) => match node.data.find(key, cmp) {
+                    Ok(idx) => idx,
+                    Err(_) => return Ok(None),
+                },
+                Target::Min if !node.data.keys.is_empty() => 0,
+                Target::Max if !node.data.keys.is_empty() => node.data.keys.len() - 1,
+                _ => return Ok(None),
+            };
+            let removed = node.data.remove(idx);
+            node.dirty = true;
+            return Ok(Some(removed));
+        }
+
+        let idx = match target {
+            Target::Key(key) => match node.data.find(key, cmp) {
+                Ok(idx) => {
+                    return Self::remove_separator(node_cache, node, cmp, min_keys, idx, key)
+                }
+                Err(idx) => idx,
+            },
+            Target::Min => 0,
+            Target::Max => node.data.keys.len(),
+        };
+
+        let idx = Self::fill_child(node_cache, node, idx, min_keys)?;
+        let mut child = node_cache.take(&node.data.children()[idx])?;
+        let removed = Self::remove_from(node_cache, &mut child, cmp, min_keys, target);
+        node_cache.put(child)?;
+
+        removed
+    }
+
+    /// Remove the key at `idx` of the internal node `node`, replacing it with its in-order
+    /// predecessor or successor if either child can spare one, and otherwise merging the two
+    /// children around it and continuing the removal in the merged node.
+    fn remove_separator<Q>(
+        node_cache: &mut NodeCache<K, V>,
+        node: &mut Node<K, V>,
+        cmp: &C,
+        min_keys: usize,
+        idx: usize,
+        key: &Q,
+    ) -> Result<Option<(K, V)>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        let mut left = node_cache.take(&node.data.children()[idx])?;
+        if left.data.keys.len() > min_keys {
+            let replacement =
+                Self::remove_from(node_cache, &mut left, cmp, min_keys, Target::<Q>::Max);
+            node_cache.put(left)?;
+            let removed = node.data.replace(idx, replacement?.unwrap());
+            node.dirty = true;
+            return Ok(Some(removed));
+        }
+
+        let mut right = node_cache.take(&node.data.children()[idx + 1])?;
+        if right.data.keys.len() > min_keys {
+            node_cache.put(left)?;
+            let replacement =
+                Self::remove_from(node_cache, &mut right, cmp, min_keys, Target::<Q>::Min);
+            node_cache.put(right)?;
+            let removed = node.data.replace(idx, replacement?.unwrap());
+            node.dirty = true;
+            return Ok(Some(removed));
+        }
+
+        Self::merge_children(node_cache, node, idx, &mut left, right)?;
+        let removed = Self::remove_from(node_cache, &mut left, cmp, min_keys, Target::Key(key));
+        node_cache.put(left)?;
+
+        removed
+    }
+
+    /// The fewest keys a node other than the root may hold, given the merge threshold. Merging
+    /// two nodes with that many keys and the separator between them has to fit in one node, so
+    /// this is never more than `NodeData::min_keys`, and never less than one, so that every
+    /// node has a key to find it by.
+    fn min_keys(&self) -> usize {
+        let min = (self.merge_threshold * self.capacity as f32) as usize;
+        min.clamp(1, (self.capacity - 1) / 2)
+    }
+
+    /// Make sure the child at `idx` of `node` has more than `min_keys` keys, either by
+    /// borrowing a key from a sibling through `node` or by merging with a sibling. Return the
+    /// index of the child to descend into, which moves left if it was merged into its left
+    /// sibling.
+    fn fill_child(
+        node_cache: &mut NodeCache<K, V>,
+        node: &mut Node<K, V>,
+        idx: usize,
+        min_keys: usize,
+    ) -> Result<usize, Error> {
+        let mut child = node_cache.take(&node.data.children()[idx])?;
+        if child.data.keys.len() > min_keys {
+            node_cache.put(child)?;
+            return Ok(idx);
+        }
+
+        let step = Step::new(idx, node.data.children().len());
+        if let Some(left_idx) = step.left_sibling() {
+            let mut left = node_cache.take(&node.data.children()[left_idx])?;
+            if left.data.keys.len() > min_keys {
+                let (key, value, grandchild) = left.data.pop_last();
+                let (key, value) = node.data.replace(left_idx, (key, value));
+                child.data.push_first(key, value, grandchild);
+
+                left.dirty = true;
+                child.dirty = true;
+                node.dirty = true;
+                node_cache.put(left)?;
+                node_cache.put(child)?;
+                return Ok(idx);
+            }
+            node_cache.put(left)?;
+        }
+
+        if let Some(right_idx) = step.right_sibling() {
+            let mut right = node_cache.take(&node.data.children()[right_idx])?;
+            if right.data.keys.len() > min_keys {
+                let (key, value, grandchild) = right.data.pop_first();
+                let (key, value) = node.data.replace(idx, (key, value));
+                child.data.push_last(key, value, grandchild);
+
+                right.dirty = true;
+                child.dirty = true;
+                node.dirty = true;
+                node_cache.put(right)?;
+                node_cache.put(child)?;
+                return Ok(idx);
+            }
+
+            Self::merge_children(node_cache, node, idx, &mut child, right)?;
+            node_cache.put(child)?;
+            return Ok(idx);
+        }
+
+        let mut left = node_cache.take(&node.data.children()[idx - 1])?;
+        Self::merge_children(node_cache, node, idx - 1, &mut left, child)?;
+        node_cache.put(left)?;
+
+        Ok(idx - 1)
+    }
+
+    /// Merge the children at `idx` and `idx + 1` of `parent`, along with the key separating them,
+    /// into `left`. `right` is deleted.
+    fn merge_children(
+        node_cache: &mut NodeCache<K, V>,
+        parent: &mut Node<K, V>,
+        idx: usize,
+        left: &mut Node<K, V>,
+        right: Node<K, V>,
+    ) -> Result<(), Error> {
+        let data = node_cache.delete(right);
+
+        let (key, value) = parent.data.remove(idx);
+        parent.data.children_mut().remove(idx + 1);
+        left.data.append(key, value, data);
+
+        left.dirty = true;
+        parent.dirty = true;
+
+        Ok(())
+    }
+
+    /// Get the node at `path`, or the root if `path` is `None`.
+    fn node(&mut self, path: Option<&NodeRef>) -> Result<&mut Node<K, V>, Error> {
+        match path {
+            Some(path) => self.load_cached(path),
+            None => Ok(&mut self.root_node),
+        }
+    }
+
+    /// Like `node`, but with the node's values loaded as well. The root always has its values.
+    fn node_with_values(&mut self, path: Option<&NodeRef>) -> Result<&mut Node<K, V>, Error> {
+        match path {
+            Some(path) => self.node_cache.get_with_values(path),
+            None => Ok(&mut self.root_node),
+        }
+    }
+
+    /// Like `node`, or `node_with_values` if `with_values` is set, but also returning the
+    /// comparator, which would otherwise be out of reach for as long as the node is borrowed.
+    fn node_and_cmp(
+        &mut self,
+        path: Option<&NodeRef>,
+        with_values: bool,
+    ) -> Result<(&mut Node<K, V>, &C), Error> {
+        let node = match path {
+            Some(path) if with_values => self.node_cache.get_with_values(path)?,
+            Some(path) => self.node_cache.get(path)?,
+            None => &mut self.root_node,
+        };
+
+        Ok((node, &self.cmp))
+    }
+
+    fn load_cached(&mut self, path: &NodeRef) -> Result<&mut Node<K, V>, Error> {
+        self.node_cache.get(path)
+    }
+
+    /// Return a node taken out of the cache by `insert`. The root is not cached.
+    fn release(&mut self, node: Option<Node<K, V>>) -> Result<(), Error> {
+        match node {
+            Some(node) => self.node_cache.put(node),
+            None => Ok(()),
+        }
+    }
+
+    /// A name for a new node, reusing the name of a deleted one if there is any.
+    fn new_node_name(
+        free_nodes: &mut Vec<NodeRef>,
+        backing_dir: &Path,
+        naming: &mut Naming,
+    ) -> NodeRef {
+        free_nodes.pop().unwrap_or_else(|| {
+            let name = naming.new_name();
+            naming.path(backing_dir, &name)
+        })
+    }
+}
+
+impl<K, V, C> Drop for BTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+// The `Debug` impls summarize nodes rather than print them, so that the output stays small
+// however big the tree, and doesn't need the keys or values to be `Debug`.
+impl<K, V, C> Debug for BTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (cached, cache_capacity) = self.node_cache.occupancy();
+        f.debug_struct("BTree")
+            .field("backing_dir", &self.backing_dir)
+            .field("capacity", &self.capacity)
+            .field("len", &self.len)
+            .field("cached", &cached)
+            .field("cache_capacity", &cache_capacity)
+            .field("read_only", &self.read_only)
+            .field("root", &self.root_node)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V> Debug for Node<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("path", &self.path)
+            .field("data", &self.data)
+            .field("dirty", &self.dirty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V> Debug for NodeData<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeData")
+            .field("keys", &self.keys.len())
+            .field("leaf", &self.children.is_none())
+            .field("children", &self.children.as_ref().map_or(0, Vec::len))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Write `value` to a temporary file next to `path` and then rename it over `path`, so that
+/// `path` always holds either the old or the new contents in full, even if writing is
+/// interrupted.
+fn save_atomically(storage: &dyn Storage, path: &Path, buf: &[u8]) -> io::Result<()> {
+    let temp_path = add_extension(path, TEMP_EXTENSION);
+    storage.write(&temp_path, buf)?;
+    storage.rename(&temp_path, path)
+}
+
+/// The values file of the node at `path`, named after the node's file with `.values` added
+/// before any extension, so that it keeps the extension the tree's files are given.
+fn values_path(path: &Path) -> PathBuf {
+    marked_path(path, VALUES_EXTENSION)
+}
+
+/// The path of the node file at `path` with `.{marker}` added before any extension.
+fn marked_path(path: &Path, marker: &str) -> PathBuf {
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => {
+            let mut name = stem.to_owned();
+            name.push(".");
+            name.push(marker);
+            name.push(".");
+            name.push(extension);
+            path.with_file_name(name)
+        }
+        _ => add_extension(path, marker),
+    }
+}
+
+/// `path` with `extension` added on to the end of its file name. Unlike `Path::with_extension`,
+/// this keeps any extension `path` already has, so `root.values` and `root` don't end up sharing
+/// a temporary file.
+fn add_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+
+    PathBuf::from(path)
+}
+
+/// Write a node's keys and children to `path`, and its values to the values file next to it.
+fn save_node<K, V>(
+    storage: &dyn Storage,
+    encoding: Encoding,
+    path: &Path,
+    data: &mut NodeData<K, V>,
+) -> Result<(), Error>
+where
+    K: Serialize,
+    V: Serialize + DeserializeOwned,
+{
+    external::save_values(storage, encoding, path, &data.values, &mut data.external)?;
+    write_node_file(storage, encoding, path, data)
+}
+
+/// Write `value` to one of a node's files at `path`, encoded and checksummed.
+fn write_node_file<T>(
+    storage: &dyn Storage,
+    encoding: Encoding,
+    path: &Path,
+    value: &T,
+) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let write = || -> Result<(), NodeError> {
+        save_atomically(storage, path, &with_checksum(encoding.encode(path, value)?))?;
+        Ok(())
+    };
+
+    write().map_err(|e| e.at(path))
+}
+
+/// Read what `write_node_file` wrote to `path`.
+fn read_node_file<T>(storage: &dyn Storage, encoding: Encoding, path: &Path) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let read = || -> Result<T, NodeError> {
+        let buf = storage.read_data(path)?;
+        encoding.decode(path, checked(path, &buf)?)
+    };
+
+    read().map_err(|e| e.at(path))
+}
+
+/// `buf` behind a header holding its checksum, as each of a node's files is written. The
+/// checksum covers the bytes on disk, after any compression, so a damaged file is caught before
+/// anything tries to make sense of it.
+fn with_checksum(buf: Vec<u8>) -> Vec<u8> {
+    let mut file = Vec::with_capacity(CHECKSUM_SIZE + buf.len());
+    file.extend_from_slice(&crc32fast::hash(&buf).to_le_bytes());
+    file.extend(buf);
+
+    file
+}
+
+/// The part of the node file read from `path` after its header, as long as it matches the
+/// checksum in the header. A file too short to have a header was cut off, so it fails too.
+fn checked<'a>(path: &Path, file: &'a [u8]) -> Result<&'a [u8], NodeError> {
+    if file.len() >= CHECKSUM_SIZE {
+        let (header, buf) = file.split_at(CHECKSUM_SIZE);
+        if header == crc32fast::hash(buf).to_le_bytes() {
+            return Ok(buf);
+        }
+    }
+
+    Err(NodeError::ChecksumMismatch {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Lock `namespace` of `backing_dir` through the lock file in it, failing with `Error::Locked` if
+/// another tree holds a lock that conflicts.
+fn lock(
+    storage: &dyn Storage,
+    backing_dir: &Path,
+    namespace: Option<&str>,
+    shared: bool,
+) -> Result<Option<File>, Error> {
+    match storage.lock(&tree_file(backing_dir, LOCK_FILE, namespace), shared) {
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(Error::Locked),
+        result => Ok(result?),
+    }
+}
+
+/// Fail with `Error::Missing` if `backing_dir` doesn't exist, or `Error::NotInitialized` if
+/// it holds no tree in `namespace`, before opening the tree leaves a lock file behind or fails
+/// with whatever file happens to be missing.
+fn check_exists(
+    storage: &dyn Storage,
+    backing_dir: &Path,
+    namespace: Option<&str>,
+) -> Result<(), Error> {
+    let files = match storage.list(backing_dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(Error::Missing),
+        files => files?,
+    };
+    if !files.contains(&tree_file(backing_dir, META_FILE, namespace)) {
+        return Err(Error::NotInitialized);
+    }
+
+    Ok(())
+}
+
+/// Remove the files of a deleted node, including any its values were moved out to.
+fn remove_node<V>(storage: &dyn Storage, encoding: Encoding, path: &Path) -> Result<(), Error>
+where
+    V: DeserializeOwned,
+{
+    // A values file that can't be read leaves the files it named for `gc`.
+    let external = external::external_files::<V>(storage, encoding, path).unwrap_or_default();
+    remove_if_exists(storage, path)?;
+    remove_if_exists(storage, &values_path(path))?;
+    for file in external {
+        remove_if_exists(storage, &file)?;
+    }
+
+    Ok(())
+}
+
+/// Remove the file at `path`, which might never have been written if its node was created and
+/// deleted between flushes.
+fn remove_if_exists(storage: &dyn Storage, path: &Path) -> io::Result<()> {
+    match storage.remove(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// The path in `backing_dir` of the file of a tree as a whole called `name`, such as its meta
+/// file, which is named after the tree's namespace if it has one.
+fn tree_file(backing_dir: &Path, name: &str, namespace: Option<&str>) -> PathBuf {
+    match namespace {
+        Some(namespace) => backing_dir.join(format!("{name}.{namespace}")),
+        None => backing_dir.join(name),
+    }
+}
+
+impl Meta {
+    /// Write the meta file to `path`. It has to be readable without the key, to say whether
+    /// the tree needs one, so an encrypted tree's is signed rather than encrypted.
+    fn save(&self, storage: &dyn Storage, path: &Path) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        self.serialize(&mut Serializer::new(&mut buf))?;
+        if self.encoding.encrypted {
+            let signature =
+                crypto::sign(self.encoding.key(), path, &buf).map_err(|e| e.at(path))?;
+            buf.extend(signature);
+        }
+        save_atomically(storage, path, &buf)?;
+
+        Ok(())
+    }
+
+    /// Read the meta file at `path`, with `key` if the tree is encrypted, which is checked
+    /// against the file's signature.
+    fn load(storage: &dyn Storage, path: &Path, key: Option<Key>) -> Result<Self, Error> {
+        let buf = storage.read(path)?;
+        // Other versions may lay out the rest of the file differently, so the version is checked
+        // before trying to read anything else.
+        let FormatVersion(version) = rmp_serde::from_slice(&buf)?;
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: version,
+                expected: FORMAT_VERSION,
+            });
+        }
+
+        let mut signature = &buf[..];
+        let mut meta: Self = rmp_serde::from_read(&mut signature)?;
+        if meta.encoding.compressed && !cfg!(feature = "compression") {
+            return Err(Error::CompressionDisabled);
+        }
+        if meta.encoding.encrypted && !cfg!(feature = "encryption") {
+            return Err(Error::EncryptionDisabled);
+        }
+        meta.encoding = meta.encoding.with_key(key)?;
+        if meta.encoding.encrypted {
+            let signed = &buf[..buf.len() - signature.len()];
+            crypto::verify(meta.encoding.key(), path, signed, signature).map_err(|e| e.at(path))?;
+        }
+
+        Ok(meta)
+    }
+}
+
+impl Naming {
+    /// Node files named `{prefix}{name}.{namespace}.{extension}`, leaving out the namespace or
+    /// the extension if there is none. No part may have a `.` in it, which would confuse the
+    /// extension of a node's file with that of its values file, or a path separator. Neither a
+    /// namespace nor an extension can be empty.
+    ///
+    /// Nodes are named in sequence if `deterministic_names` is set, and by UUID otherwise.
+    fn new(
+        prefix: String,
+        namespace: Option<String>,
+        extension: Option<String>,
+        deterministic_names: bool,
+    ) -> Result<Self, Error> {
+        let invalid = |part: &str| part.contains(['.', '/', std::path::MAIN_SEPARATOR]);
+        if invalid(&prefix) {
+            return Err(Error::InvalidFileName(prefix));
+        }
+        for part in namespace.iter().chain(&extension) {
+            if part.is_empty() || invalid(part) {
+                return Err(Error::InvalidFileName(part.clone()));
+            }
+        }
+
+        Ok(Self {
+            prefix,
+            namespace,
+            extension,
+            next_node: deterministic_names.then_some(1),
+        })
+    }
+
+    /// The name of a new node: the next in sequence, such as `node-0000001`, for a tree that
+    /// names its nodes that way, and a random UUID otherwise.
+    fn new_name(&mut self) -> String {
+        match self.next_node.as_mut() {
+            Some(next) => {
+                let name = format!("node-{next:07}");
+                *next += 1;
+                name
+            }
+            None => Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// The path in `backing_dir` of the file of the node called `name`.
+    fn path(&self, backing_dir: &Path, name: &str) -> PathBuf {
+        let (inner, last) = self.suffixes();
+
+        backing_dir.join(format!("{}{name}{inner}{last}", self.prefix))
+    }
+
+    /// What follows the name of a node in its file name, split at the extension that `.values`
+    /// goes in front of for the node's values file.
+    fn suffixes(&self) -> (String, String) {
+        let suffix = |part: &Option<String>| match part {
+            Some(part) => format!(".{part}"),
+            None => String::new(),
+        };
+        match self.extension {
+            Some(_) => (suffix(&self.namespace), suffix(&self.extension)),
+            None => (String::new(), suffix(&self.namespace)),
+        }
+    }
+
+    /// Whether the file called `file_name` belongs to a tree named this way rather than to a
+    /// tree in another namespace: a node's file, a node's values file, a file a value was moved
+    /// out to, one of the files of the tree as a whole, or a temporary file left behind while
+    /// any of those was being written.
+    fn owns(&self, file_name: &str) -> bool {
+        let file_name = file_name
+            .strip_suffix(&format!(".{TEMP_EXTENSION}"))
+            .unwrap_or(file_name);
+        let is_name = |name: Option<&str>| name.is_some_and(|name| !name.contains('.'));
+
+        let namespace = match &self.namespace {
+            Some(namespace) => format!(".{namespace}"),
+            None => String::new(),
+        };
+        if is_name(file_name.strip_suffix(&namespace)) {
+            return true;
+        }
+
+        let (inner, last) = self.suffixes();
+        let node = file_name
+            .strip_prefix(&self.prefix)
+            .and_then(|rest| rest.strip_suffix(&last))
+            .map(|rest| match rest.rsplit_once('.') {
+                Some((node, marker))
+                    if marker == VALUES_EXTENSION || marker.starts_with(EXTERNAL_VALUE_MARKER) =>
+                {
+                    node
+                }
+                _ => rest,
+            })
+            .and_then(|rest| rest.strip_suffix(&inner));
+
+        is_name(node)
+    }
+}
+
+impl Encoding {
+    /// Serialize `value` for writing to the node file at `path`.
+    fn encode<T>(self, path: &Path, value: &T) -> Result<Vec<u8>, NodeError>
+    where
+        T: Serialize,
+    {
+        let mut buf = self.codec.encode(value)?;
+        if self.compressed {
+            buf = compress(&buf)?;
+        }
+        if self.encrypted {
+            buf = crypto::seal(self.key(), path, &buf)?;
+        }
+
+        Ok(buf)
+    }
+
+    /// Deserialize what `encode` wrote to the node file at `path`.
+    fn decode<T>(self, path: &Path, buf: &[u8]) -> Result<T, NodeError>
+    where
+        T: DeserializeOwned,
+    {
+        let decrypted;
+        let buf = if self.encrypted {
+            decrypted = crypto::open(self.key(), path, buf)?;
+            &decrypted[..]
+        } else {
+            buf
+        };
+        if self.compressed {
+            return self.codec.decode(&decompress(buf)?);
+        }
+
+        self.codec.decode(buf)
+    }
+
+    /// This encoding, read from a meta file, along with the key it is opened with, which an
+    /// encrypted tree needs and any other tree mustn't be given.
+    fn with_key(self, key: Option<Key>) -> Result<Self, Error> {
+        match (self.encrypted, key) {
+            (true, None) => Err(Error::KeyRequired),
+            (false, Some(_)) => Err(Error::NotEncrypted),
+            _ => Ok(Self { key, ..self }),
+        }
+    }
+
+    fn key(self) -> Key {
+        self.key
+            .expect("an encrypted tree is only ever opened with its key")
+    }
+}
+
+#[cfg(feature = "compression")]
+fn compress(buf: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::encode_all(buf, zstd::DEFAULT_COMPRESSION_LEVEL)
+}
+
+#[cfg(feature = "compression")]
+fn decompress(buf: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(buf)
+}
+
+// Without the feature, no tree can be created compressed, and `Meta::load` refuses to open one
+// that was.
+#[cfg(not(feature = "compression"))]
+fn compress(_: &[u8]) -> io::Result<Vec<u8>> {
+    unreachable!("compression is disabled")
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_: &[u8]) -> io::Result<Vec<u8>> {
+    unreachable!("compression is disabled")
+}
+
+/// The version a meta file starts with, read on its own.
+struct FormatVersion(u32);
+
+impl<'de> Deserialize<'de> for FormatVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = FormatVersion;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a meta file")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<FormatVersion, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let version = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+
+                Ok(FormatVersion(version))
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor)
+    }
+}
+
+impl<K, V> Node<K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    fn new(path: PathBuf, capacity: usize) -> Self {
+        Self::create(path, NodeData::new(capacity))
+    }
+
+    /// A node that has not been written yet. Its file is created the first time it is saved.
+    fn create(path: PathBuf, data: NodeData<K, V>) -> Self {
+        Node {
+            path,
+            data,
+            dirty: true,
+            values_loaded: true,
+            fresh: true,
+        }
+    }
+
+    fn save(&mut self, storage: &dyn Storage, encoding: Encoding) -> Result<(), Error> {
+        // Only a changed node is saved, and every change goes through a node with its values.
+        debug_assert!(self.values_loaded);
+        save_node(storage, encoding, &self.path, &mut self.data)?;
+        self.dirty = false;
+        self.fresh = false;
+
+        Ok(())
+    }
+
+    /// Save the node if it has changed since it was last saved.
+    fn flush(&mut self, storage: &dyn Storage, encoding: Encoding) -> Result<(), Error> {
+        if self.dirty {
+            self.save(storage, encoding)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.data.children.is_none()
+    }
+
+    /// Load the node's keys and children. Its values are left to `load_values`.
+    fn load(storage: &dyn Storage, encoding: Encoding, path: &NodeRef) -> Result<Self, Error> {
+        let mut data: NodeData<K, V> = read_node_file(storage, encoding, path)?;
+        // Children are named by their paths, as they were when the node was written. A tree read
+        // from anywhere else, such as a snapshot, finds them next to the node instead.
+        if let (Some(children), Some(dir)) = (data.children.as_mut(), path.parent()) {
+            for child in children
+                .iter_mut()
+                .filter(|child| child.parent() != Some(dir))
+            {
+                *child = dir.join(child.file_name().unwrap());
+            }
+        }
+        let path = path.clone();
+
+        Ok(Self {
+            path,
+            data,
+            dirty: false,
+            values_loaded: false,
+            fresh: false,
+        })
+    }
+
+    /// Read the node's values from its values file, unless they have been already.
+    fn load_values(&mut self, storage: &dyn Storage, encoding: Encoding) -> Result<(), Error> {
+        if !self.values_loaded {
+            (self.data.values, self.data.external) =
+                external::load_values(storage, encoding, &self.path)?;
+            self.values_loaded = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl NodeError {
+    /// This error, as it happened to the node file at `path`.
+    fn at(self, path: &Path) -> Error {
+        Error::Node {
+            path: path.to_path_buf(),
+            source: self,
+        }
+    }
+}
+
+impl<K, V> NodeData<K, V> {
+    fn new(capacity: usize) -> Self {
+        NodeData {
+            capacity,
+            keys: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
+            children: None,
+            external: Vec::new(),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.keys.len() == self.capacity
+    }
+
+    /// Release the room set aside for keys, values and children the node doesn't hold.
+    fn shrink_to_fit(&mut self) {
+        self.keys.shrink_to_fit();
+        self.values.shrink_to_fit();
+        if let Some(children) = self.children.as_mut() {
+            children.shrink_to_fit();
+        }
+    }
+
+    /// The fewest keys a node other than the root may hold. Splitting a full node leaves at
+    /// least this many keys on either side of the median, whether the capacity is odd or even.
+    fn min_keys(&self) -> usize {
+        (self.capacity - 1) / 2
+    }
+
+    fn children(&self) -> &[NodeRef] {
+        self.children
+            .as_ref()
+            .expect("leaf nodes have no children.")
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<NodeRef> {
+        self.children
+            .as_mut()
+            .expect("leaf nodes have no children.")
+    }
+
+    fn find<Q, C>(&self, key: &Q, cmp: &C) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        self.keys
+            .binary_search_by(|probe| cmp.compare(probe.borrow(), key))
+    }
+
+    fn lookup<Q, C>(&self, key: &Q, cmp: &C) -> Lookup
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        match self.find(key, cmp) {
+            Ok(idx) => Lookup::Found(idx),
+            Err(idx) if self.children.is_some() => Lookup::Child(idx),
+            Err(_) => Lookup::Missing,
+        }
+    }
+
+    /// If the key was already present, return the old value. If the key was not present, return
+    /// None.
+    fn insert<C>(&mut self, key: K, value: V, cmp: &C) -> Result<Option<V>, NodeError>
+    where
+        C: Compare<K>,
+    {
+        match self.find(&key, cmp) {
+            Ok(idx) => Ok(Some(mem::replace(&mut self.values[idx], value))),
+            Err(idx) => {
+                if self.is_full() {
+                    return Err(NodeError::Full);
+                }
+                self.keys.insert(idx, key);
+                self.values.insert(idx, value);
+                Ok(None)
+            }
+        }
+    }
+
+    fn remove(&mut self, idx: usize) -> (K, V) {
+        (self.keys.remove(idx), self.values.remove(idx))
+    }
+
+    /// Replace the key and value at `idx`, returning the old ones.
+    fn replace(&mut self, idx: usize, (key, value): (K, V)) -> (K, V) {
+        (
+            mem::replace(&mut self.keys[idx], key),
+            mem::replace(&mut self.values[idx], value),
+        )
+    }
+
+    /// Remove the first key and value, along with the first child if this is an internal node.
+    fn pop_first(&mut self) -> (K, V, Option<NodeRef>) {
+        let (key, value) = self.remove(0);
+        let child = self.children.as_mut().map(|children| children.remove(0));
+
+        (key, value, child)
+    }
+
+    /// Remove the last key and value, along with the last child if this is an internal node.
+    fn pop_last(&mut self) -> (K, V, Option<NodeRef>) {
+        let key = self.keys.pop().unwrap();
+        let value = self.values.pop().unwrap();
+        let child = self
+            .children
+            .as_mut()
+            .map(|children| children.pop().unwrap());
+
+        (key, value, child)
+    }
+
+    fn push_first(&mut self, key: K, value: V, child: Option<NodeRef>) {
+        self.keys.insert(0, key);
+        self.values.insert(0, value);
+        if let Some(child) = child {
+            self.children_mut().insert(0, child);
+        }
+    }
+
+    fn push_last(&mut self, key: K, value: V, child: Option<NodeRef>) {
+        self.keys.push(key);
+        self.values.push(value);
+        if let Some(child) = child {
+            self.children_mut().push(child);
+        }
+    }
+
+    /// Append a separator key and value followed by the contents of `other`, the inverse of
+    /// `split`.
+    fn append(&mut self, key: K, value: V, other: NodeData<K, V>) {
+        self.keys.push(key);
+        self.values.push(value);
+        self.keys.extend(other.keys);
+        self.values.extend(other.values);
+        if let Some(children) = other.children {
+            self.children_mut().extend(children);
+        }
+    }
+
+    /// Split a node around its median. `self` keeps the lower half, and the median key and value
+    /// are returned along with a new node holding the upper half.
+    fn split(&mut self) -> (K, V, NodeData<K, V>) {
+        let split_idx = self.keys.len() / 2;
+        let mut other = NodeData::new(self.capacity);
+
+        other.keys.extend(self.keys.drain(split_idx + 1..));
+        other.values.extend(self.values.drain(split_idx + 1..));
+        if let Some(children) = self.children.as_mut() {
+            other.children = Some(children.split_off(split_idx + 1));
+        }
+
+        let key = self.keys.pop().unwrap();
+        let value = self.values.pop().unwrap();
 
-        Ok(Self { file, data })
+        (key, value, other)
     }
 }
@@ -0,0 +1,139 @@
+use std::borrow::Borrow;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Error};
+
+/// An ordered map that keeps any number of values under each key, stored as a `BTree` whose
+/// values are the groups of values for each key, in the order they were inserted.
+///
+/// Every change goes through `BTree::insert` or `BTree::remove` with the whole group, so it is
+/// logged like any other when the write-ahead log is enabled.
+pub struct BTreeMultiMap<K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    tree: BTree<K, Vec<V>>,
+}
+
+impl<K, V> BTreeMultiMap<K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    /// Create a multimap in `backing_dir`, as `BTree::new` does.
+    pub fn new(backing_dir: PathBuf, capacity: usize) -> Result<Self, Error> {
+        let tree = BTree::new(backing_dir, capacity)?;
+
+        Ok(Self { tree })
+    }
+
+    /// Create a multimap that keeps its nodes in memory, as `BTree::new_in_memory` does.
+    pub fn new_in_memory(capacity: usize) -> Result<Self, Error> {
+        let tree = BTree::new_in_memory(capacity)?;
+
+        Ok(Self { tree })
+    }
+
+    /// Reopen a multimap previously created with `new` in `backing_dir`.
+    pub fn open(backing_dir: PathBuf) -> Result<Self, Error> {
+        let tree = BTree::open(backing_dir)?;
+
+        Ok(Self { tree })
+    }
+
+    /// The number of distinct keys in the multimap, however many values each has.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Add `value` after any values already under `key`.
+    pub fn insert(&mut self, key: K, value: V) -> Result<(), Error>
+    where
+        V: Clone,
+    {
+        let mut values = self.tree.get(&key)?.unwrap_or_default();
+        values.push(value);
+        self.tree.insert(key, values)?;
+
+        Ok(())
+    }
+
+    /// Every value under `key`, in the order they were inserted. A missing key has none.
+    pub fn get_all<Q>(&mut self, key: &Q) -> Result<Vec<V>, Error>
+    where
+        K: Borrow<Q>,
+        Q: Ord + Serialize + ?Sized,
+        V: Clone,
+    {
+        Ok(self.tree.get(key)?.unwrap_or_default())
+    }
+
+    /// Whether any value is under `key`.
+    pub fn contains_key<Q>(&mut self, key: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q>,
+        Q: Ord + Serialize + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+
+    /// Remove `key` along with every value under it, returning them in the order they were
+    /// inserted.
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<Vec<V>, Error>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(self.tree.remove(key)?.unwrap_or_default())
+    }
+
+    /// Remove the first value under `key` that equals `value`, returning whether there was one.
+    /// The key goes too once its last value does.
+    pub fn remove_value(&mut self, key: &K, value: &V) -> Result<bool, Error>
+    where
+        K: Clone,
+        V: Clone + PartialEq,
+    {
+        let mut values = match self.tree.get(key)? {
+            Some(values) => values,
+            None => return Ok(false),
+        };
+        let idx = match values.iter().position(|v| v == value) {
+            Some(idx) => idx,
+            None => return Ok(false),
+        };
+        values.remove(idx);
+        if values.is_empty() {
+            self.tree.remove(key)?;
+        } else {
+            self.tree.insert(key.clone(), values)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Check that the underlying tree is well-formed, as `BTree::validate` does.
+    pub fn validate(&mut self) -> Result<(), Error>
+    where
+        K: Clone,
+    {
+        self.tree.validate()
+    }
+
+    /// See `BTree::flush`.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.tree.flush()
+    }
+
+    /// See `BTree::close`.
+    pub fn close(self) -> Result<(), Error> {
+        self.tree.close()
+    }
+}
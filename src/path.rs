@@ -0,0 +1,88 @@
+use crate::NodeRef;
+
+/// One node on the way down a tree, and which of its children the way goes on into.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// The node itself, where `None` stands for the root. Left out of anything public, since
+    /// nodes are only ever named inside the tree.
+    pub(crate) node: Option<NodeRef>,
+    /// The index of the child the way goes on into.
+    pub child: usize,
+    /// How many children the node has.
+    pub children: usize,
+}
+
+impl Step {
+    pub fn new(child: usize, children: usize) -> Self {
+        debug_assert!(child < children);
+        Self {
+            node: None,
+            child,
+            children,
+        }
+    }
+
+    /// The index of the sibling just left of the child the way goes on into, if it has one.
+    /// The key separating the two is at the same index in the node.
+    pub fn left_sibling(&self) -> Option<usize> {
+        self.child.checked_sub(1)
+    }
+
+    /// The index of the sibling just right of the child the way goes on into, if it has one.
+    /// The key separating the two is at the index of the child in the node.
+    pub fn right_sibling(&self) -> Option<usize> {
+        Some(self.child + 1).filter(|&idx| idx < self.children)
+    }
+}
+
+/// The way down from the root of a tree to one of its nodes, as a step for each node above it,
+/// from the root down. Each step says which of the node's children the way goes on into, and
+/// so where the siblings of the next node down are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathStack {
+    steps: Vec<Step>,
+}
+
+impl PathStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Go on down from the node the way leads to.
+    pub fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    /// Go back up a level, returning the step that led down from it.
+    pub fn pop(&mut self) -> Option<Step> {
+        self.steps.pop()
+    }
+
+    /// The step into the node the way leads to, from its parent. There is none if the way ends
+    /// at the root.
+    pub fn last(&self) -> Option<&Step> {
+        self.steps.last()
+    }
+
+    /// How many levels below the root the way ends.
+    pub fn depth(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The indexes of the siblings either side of the node the way leads to, within its parent.
+    pub fn siblings(&self) -> (Option<usize>, Option<usize>) {
+        match self.last() {
+            Some(step) => (step.left_sibling(), step.right_sibling()),
+            None => (None, None),
+        }
+    }
+
+    /// The nodes on the way down, not counting the root or the node the way leads to.
+    pub(crate) fn ancestors(&self) -> impl Iterator<Item = &NodeRef> {
+        self.steps.iter().filter_map(|step| step.node.as_ref())
+    }
+}
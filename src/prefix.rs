@@ -0,0 +1,68 @@
+use std::ops::Bound;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Range};
+
+impl<V> BTree<Vec<u8>, V>
+where
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+{
+    /// Iterate over the entries whose keys start with `prefix`, in ascending key order. This is
+    /// the range from `prefix` up to the first key past every key that starts with it, which
+    /// a prefix made only of `0xff` bytes doesn't have, so it runs to the end of the tree.
+    pub fn scan_prefix(&mut self, prefix: &[u8]) -> Range<'_, Vec<u8>, V> {
+        let end = match bytes_successor(prefix) {
+            Some(end) => Bound::Excluded(end),
+            None => Bound::Unbounded,
+        };
+
+        self.range((Bound::Included(prefix.to_vec()), end))
+    }
+}
+
+impl<V> BTree<String, V>
+where
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+{
+    /// Like `scan_prefix` for byte keys, for keys that are strings.
+    pub fn scan_prefix(&mut self, prefix: &str) -> Range<'_, String, V> {
+        let end = match str_successor(prefix) {
+            Some(end) => Bound::Excluded(end),
+            None => Bound::Unbounded,
+        };
+
+        self.range((Bound::Included(prefix.to_owned()), end))
+    }
+}
+
+/// The least byte string greater than every one starting with `prefix`, which is `prefix` with
+/// any trailing `0xff` bytes dropped and the last byte left incremented.
+fn bytes_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+
+    None
+}
+
+/// Like `bytes_successor`, but incrementing characters rather than bytes, so that the bound is
+/// still a string. UTF-8 sorts the same way as the characters it encodes, so this is still
+/// past every string starting with `prefix`.
+fn str_successor(prefix: &str) -> Option<String> {
+    let mut end = prefix.to_owned();
+    while let Some(last) = end.pop() {
+        // Skipping over the surrogates, which aren't characters.
+        let next = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+        if let Some(next) = next {
+            end.push(next);
+            return Some(end);
+        }
+    }
+
+    None
+}
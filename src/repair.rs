@@ -0,0 +1,132 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{FileStorage, MemoryStorage, Storage};
+use crate::{
+    bulk, external, lock, remove_if_exists, save_atomically, tree_file, values_path, wal, BTree,
+    Error, Meta, Natural, Node, BLOOM_FILE, LOCK_FILE, META_FILE, TEMP_EXTENSION,
+};
+
+/// What `BTree::repair` managed to salvage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// The entries the rebuilt tree holds.
+    pub entries_recovered: usize,
+    /// The node files that were read, whether or not everything in them survived.
+    pub nodes_read: usize,
+    /// The node files that couldn't be read, or whose values file couldn't be, and whose
+    /// entries are lost.
+    pub unreadable_nodes: usize,
+}
+
+/// Salvage what can be read of the tree in `namespace` of `backing_dir`, and replace it with a
+/// tree built from that.
+pub(crate) fn repair<K, V>(
+    backing_dir: PathBuf,
+    namespace: Option<&str>,
+) -> Result<RepairReport, Error>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    let storage = FileStorage;
+    let _lock = lock(&storage, &backing_dir, namespace, false)?;
+    let meta_path = tree_file(&backing_dir, META_FILE, namespace);
+    // Nothing of an encrypted tree can be read without its key, which isn't given here, so the
+    // tree is left alone, log and all.
+    if let Err(Error::KeyRequired) = Meta::load(&storage, &meta_path, None) {
+        return Err(Error::KeyRequired);
+    }
+    // A committed change the log still holds is worth having, but a log too damaged to replay
+    // is no reason not to salvage the rest.
+    let _ = wal::recover::<K, V>(&storage, &backing_dir, namespace, None);
+    let meta = Meta::load(&storage, &meta_path, None)?;
+
+    let mut report = RepairReport::default();
+    let mut entries = BTreeMap::new();
+    for path in node_files(&storage, &backing_dir, &meta)? {
+        let node = Node::<K, V>::load(&storage, meta.encoding, &path).and_then(|mut node| {
+            node.load_values(&storage, meta.encoding)?;
+            Ok(node)
+        });
+        match node {
+            Ok(node) => {
+                report.nodes_read += 1;
+                entries.extend(node.data.keys.into_iter().zip(node.data.values));
+            }
+            Err(_) => report.unreadable_nodes += 1,
+        }
+    }
+    report.entries_recovered = entries.len();
+
+    // The new tree is built in memory under the paths it will have, and only written out once
+    // it is complete. The directory may hold other trees in namespaces of their own, so only
+    // the files this tree owns are touched: each of the new tree's files replaces whatever is
+    // at its path in one step, the meta file last, and the old files the new tree doesn't use
+    // are removed once it names none of them.
+    let repaired = Arc::new(MemoryStorage::default());
+    let naming = meta.naming.clone();
+    let mut tree = BTree::create(
+        repaired.clone(),
+        backing_dir.clone(),
+        meta.capacity,
+        meta.merge_threshold,
+        Natural,
+        meta.encoding,
+        meta.naming,
+    )?;
+    // The filter is built again from the keys that were salvaged, which is every key it has to
+    // know of.
+    if meta.bloom_filter.is_some() {
+        tree.start_bloom_filter()?;
+        tree.reset_bloom_filter(entries.len());
+    }
+    bulk::load(&mut tree, entries)?;
+    tree.close()?;
+    let mut files = repaired.list(&backing_dir)?;
+    files.sort_by_key(|path| *path == meta_path);
+    for path in &files {
+        save_atomically(&storage, path, &repaired.read(path)?)?;
+    }
+    let files: HashSet<PathBuf> = files.into_iter().collect();
+    let lock_path = tree_file(&backing_dir, LOCK_FILE, namespace);
+    for path in storage.list(&backing_dir)? {
+        let name = path.file_name().unwrap().to_string_lossy();
+        if naming.owns(&name) && path != lock_path && !files.contains(&path) {
+            remove_if_exists(&storage, &path)?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Every file in `backing_dir` that holds a node's keys, reachable from the root or not, since
+/// a damaged node may have been the only way to reach the ones below it.
+fn node_files(
+    storage: &dyn Storage,
+    backing_dir: &Path,
+    meta: &Meta,
+) -> Result<Vec<PathBuf>, Error> {
+    let files = storage.list(backing_dir)?;
+    let values: HashSet<PathBuf> = files.iter().map(|path| values_path(path)).collect();
+    let namespace = meta.naming.namespace.as_deref();
+    let tree_files: HashSet<PathBuf> = [META_FILE, LOCK_FILE, wal::WAL_FILE, BLOOM_FILE]
+        .into_iter()
+        .map(|name| tree_file(backing_dir, name, namespace))
+        .collect();
+
+    Ok(files
+        .into_iter()
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            meta.naming.owns(&name)
+                && !tree_files.contains(path)
+                && !name.ends_with(&format!(".{}", TEMP_EXTENSION))
+                && !values.contains(path)
+                && !external::is_external(path)
+        })
+        .collect())
+}
@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{external, read_node_file, BTree, Compare, Error, NodeData, NodeRef};
+
+/// What `BTree::scrub` found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScrubReport {
+    /// The nodes whose files were read, whether or not they could be made sense of.
+    pub nodes_checked: usize,
+    /// The node files and values files that failed their checksum or couldn't be decoded. The
+    /// nodes below a node whose own file is damaged can't be found, so aren't checked.
+    pub damaged: Vec<NodeRef>,
+    /// The first problem `BTree::validate` found, which is only looked for once every node has
+    /// been read back without damage.
+    pub invalid: Option<String>,
+}
+
+impl ScrubReport {
+    /// Whether nothing was found wrong with the tree.
+    pub fn is_clean(&self) -> bool {
+        self.damaged.is_empty() && self.invalid.is_none()
+    }
+}
+
+/// Read back every file of every node reachable from the root of `tree`, straight from storage
+/// rather than the cache, and note the ones that can't be, before checking the shape of the
+/// tree.
+pub(crate) fn scrub<K, V, C>(tree: &mut BTree<K, V, C>) -> Result<ScrubReport, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Clone,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    // What is on disk only matches the tree once it is flushed.
+    tree.flush()?;
+
+    let mut report = ScrubReport::default();
+    let mut stack = vec![tree.root_node.path.clone()];
+    // A damaged tree could lead back to a node it has been through already.
+    let mut seen = HashSet::new();
+    while let Some(path) = stack.pop() {
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        report.nodes_checked += 1;
+        let storage = &*tree.storage;
+        match read_node_file::<NodeData<K, V>>(storage, tree.encoding, &path) {
+            Ok(data) => stack.extend(data.children.into_iter().flatten()),
+            Err(Error::Node { path, .. }) => report.damaged.push(path),
+            Err(e) => return Err(e),
+        }
+        match external::load_values::<V>(storage, tree.encoding, &path) {
+            Ok(_) => {}
+            Err(Error::Node { path, .. }) => report.damaged.push(path),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if report.damaged.is_empty() {
+        match tree.validate() {
+            Ok(()) => {}
+            Err(Error::Invalid(problem)) => report.invalid = Some(problem),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,152 @@
+use std::borrow::Borrow;
+use std::ops::RangeBounds;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Error, Keys, Range};
+
+/// An ordered set of keys, stored as a `BTree` whose values are all `()`.
+pub struct BTreeSet<K>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+{
+    tree: BTree<K, ()>,
+}
+
+/// An iterator over the keys of a `BTreeSet` in ascending order, created by `BTreeSet::iter`.
+pub struct SetIter<'a, K>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Ord,
+{
+    keys: Keys<'a, K, ()>,
+}
+
+/// An iterator over a range of the keys of a `BTreeSet` in ascending order, created by
+/// `BTreeSet::range`.
+pub struct SetRange<'a, K>
+where
+    K: for<'de> Deserialize<'de> + Serialize + Ord,
+{
+    range: Range<'a, K, ()>,
+}
+
+impl<K> BTreeSet<K>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+{
+    /// Create a set in `backing_dir`, as `BTree::new` does.
+    pub fn new(backing_dir: PathBuf, capacity: usize) -> Result<Self, Error> {
+        let tree = BTree::new(backing_dir, capacity)?;
+
+        Ok(Self { tree })
+    }
+
+    /// Create a set that keeps its nodes in memory, as `BTree::new_in_memory` does.
+    pub fn new_in_memory(capacity: usize) -> Result<Self, Error> {
+        let tree = BTree::new_in_memory(capacity)?;
+
+        Ok(Self { tree })
+    }
+
+    /// Reopen a set previously created with `new` in `backing_dir`.
+    pub fn open(backing_dir: PathBuf) -> Result<Self, Error> {
+        let tree = BTree::open(backing_dir)?;
+
+        Ok(Self { tree })
+    }
+
+    /// The number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Add `key` to the set, returning whether it wasn't there already. A key that was keeps
+    /// the one first inserted.
+    pub fn insert(&mut self, key: K) -> Result<bool, Error> {
+        Ok(self.tree.insert(key, ())?.is_none())
+    }
+
+    /// Whether `key`, which may be any borrowed form of the set's key type, is in the set.
+    pub fn contains<Q>(&mut self, key: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q>,
+        Q: Ord + Serialize + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+
+    /// Remove `key` from the set, returning whether it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(self.tree.remove(key)?.is_some())
+    }
+
+    /// Iterate over the keys of the set in ascending order.
+    pub fn iter(&mut self) -> SetIter<'_, K>
+    where
+        K: Clone,
+    {
+        SetIter {
+            keys: self.tree.keys(),
+        }
+    }
+
+    /// Iterate over the keys of the set that fall within `range` in ascending order.
+    pub fn range<R>(&mut self, range: R) -> SetRange<'_, K>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+    {
+        SetRange {
+            range: self.tree.range(range),
+        }
+    }
+
+    /// Check that the underlying tree is well-formed, as `BTree::validate` does.
+    pub fn validate(&mut self) -> Result<(), Error>
+    where
+        K: Clone,
+    {
+        self.tree.validate()
+    }
+
+    /// See `BTree::flush`.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.tree.flush()
+    }
+
+    /// See `BTree::close`.
+    pub fn close(self) -> Result<(), Error> {
+        self.tree.close()
+    }
+}
+
+impl<K> Iterator for SetIter<'_, K>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord + Clone,
+{
+    type Item = Result<K, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.next()
+    }
+}
+
+impl<K> Iterator for SetRange<'_, K>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord + Clone,
+{
+    type Item = Result<K, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|entry| entry.map(|(key, ())| key))
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{wal, BTree, Compare, Error, BLOOM_FILE, LOCK_FILE, META_FILE};
+
+/// The shape of a tree, as returned by `BTree::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    /// The number of levels, counting the root and the leaves.
+    pub height: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub entry_count: usize,
+    /// The mean over all nodes of how full each one is, as a fraction of the node capacity.
+    pub average_fill: f64,
+}
+
+/// How full the nodes on one level of a tree are, as returned for each level by
+/// `BTree::fill_histogram`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LevelStats {
+    pub node_count: usize,
+    pub key_count: usize,
+    /// The mean over the level's nodes of how full each one is, as a fraction of the node
+    /// capacity.
+    pub average_fill: f64,
+}
+
+/// How much storage a tree takes up, as returned by `BTree::disk_usage`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// The size of the files of the nodes the tree still needs, which are those reachable from
+    /// the root and those of earlier versions that readers or `BTree::snapshot_at` may read.
+    pub node_bytes: u64,
+    /// The size of the meta file, and with a Bloom filter, the filter's file.
+    pub meta_bytes: u64,
+    /// The size of the write-ahead log, which is only there while a change is being made.
+    pub wal_bytes: u64,
+    /// The size of the files the tree no longer needs, which `BTree::gc` would remove.
+    pub orphaned_bytes: u64,
+}
+
+impl DiskUsage {
+    /// The size of every file the tree still needs, leaving out those `gc` would remove.
+    pub fn total_bytes(&self) -> u64 {
+        self.node_bytes + self.meta_bytes + self.wal_bytes
+    }
+}
+
+/// Work out the stats of `tree` by loading every node, without their values.
+pub(crate) fn collect<K, V, C>(tree: &mut BTree<K, V, C>) -> Result<TreeStats, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    let mut stats = TreeStats::default();
+    let mut fill = 0.0;
+    // `(node, depth)` for each node still to visit, where `None` stands for the root.
+    let mut stack = vec![(None, 1)];
+    while let Some((path, depth)) = stack.pop() {
+        let node = tree.node(path.as_ref())?;
+        let data = &node.data;
+        stats.node_count += 1;
+        stats.entry_count += data.keys.len();
+        fill += data.keys.len() as f64 / data.capacity as f64;
+        match data.children.as_ref() {
+            Some(children) => stack.extend(children.iter().map(|c| (Some(c.clone()), depth + 1))),
+            None => {
+                stats.leaf_count += 1;
+                stats.height = stats.height.max(depth);
+            }
+        }
+    }
+    stats.average_fill = fill / stats.node_count as f64;
+
+    Ok(stats)
+}
+
+/// Work out how full each level of `tree` is, from the root down, by loading the nodes of each
+/// level in turn, without their values.
+pub(crate) fn levels<K, V, C>(tree: &mut BTree<K, V, C>) -> Result<Vec<LevelStats>, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    let mut levels = Vec::new();
+    // `None` stands for the root.
+    let mut level = vec![None];
+    while !level.is_empty() {
+        let mut stats = LevelStats::default();
+        let mut fill = 0.0;
+        let mut below = Vec::new();
+        for path in level {
+            let data = &tree.node(path.as_ref())?.data;
+            stats.node_count += 1;
+            stats.key_count += data.keys.len();
+            fill += data.keys.len() as f64 / data.capacity as f64;
+            below.extend(data.children.iter().flatten().cloned().map(Some));
+        }
+        stats.average_fill = fill / stats.node_count as f64;
+        levels.push(stats);
+        level = below;
+    }
+
+    Ok(levels)
+}
+
+/// Add up the sizes of the files of `tree`, loading every node to find the ones it still needs.
+pub(crate) fn disk_usage<K, V, C>(tree: &mut BTree<K, V, C>) -> Result<DiskUsage, Error>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    let live = tree.live_files()?;
+    let meta = tree.tree_file(META_FILE);
+    let wal = tree.tree_file(wal::WAL_FILE);
+    let lock = tree.tree_file(LOCK_FILE);
+    let bloom = tree.tree_file(BLOOM_FILE);
+
+    let mut usage = DiskUsage::default();
+    for path in tree.storage.list(&tree.backing_dir)? {
+        let name = path.file_name().unwrap().to_string_lossy();
+        // Anything that isn't named as this tree names its files belongs to another tree.
+        if !tree.naming.owns(&name) || path == lock {
+            continue;
+        }
+        let size = tree.storage.size(&path)?;
+        if path == meta || path == bloom {
+            usage.meta_bytes += size;
+        } else if path == wal {
+            usage.wal_bytes += size;
+        } else if live.contains(&path) {
+            usage.node_bytes += size;
+        } else {
+            usage.orphaned_bytes += size;
+        }
+    }
+
+    Ok(usage)
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs::{self, DirBuilder, File};
+
+use fs2::FileExt;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Where a tree keeps its files. Every read and write of a node, the meta file and the
+/// write-ahead log goes through here, addressed by the same paths the tree would use on disk.
+///
+/// A tree uses `FileStorage` unless `BTreeBuilder::storage` gives it something else, such as a
+/// wrapper around `FileStorage` that watches what the tree does with its files.
+pub trait Storage: Send + Sync {
+    /// Create the directory a new tree lives in, failing if it already exists.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Read the file at `path` as `read` does, into whatever holds its bytes most cheaply. Node
+    /// files are read this way, and only looked at until they have been decoded. Storage that
+    /// can't do better than `read` reads the file.
+    fn read_data(&self, path: &Path) -> io::Result<FileData> {
+        self.read(path).map(FileData::from)
+    }
+
+    /// Replace the contents of the file at `path`, creating it if it doesn't exist.
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    /// Move the file at `from` to `to`, replacing anything already there in one step.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// The paths of the files directly in the directory at `path`.
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Make sure what was last written to `path` would survive a crash. `path` may also be a
+    /// directory, to make the files created in it, moved into it or removed from it survive a
+    /// crash as well, which storage without directories of its own has nothing to do for.
+    fn sync(&self, path: &Path) -> io::Result<()>;
+
+    /// Lock the file at `path`, creating it first for an exclusive lock, for as long as the
+    /// returned file stays open. A shared lock only keeps out exclusive ones. Fails with
+    /// `io::ErrorKind::WouldBlock` if the lock is held elsewhere. `None` means there is nothing
+    /// to lock, since nothing else can reach the tree's files.
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>>;
+
+    /// Make the file at `from` available at `to` as well, without copying it if the storage
+    /// can share one file between two paths. Either path can be replaced later without changing
+    /// what is at the other, since files are only ever replaced whole. Storage that can't share
+    /// files copies them.
+    fn link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let data = self.read(from)?;
+        self.write(to, &data)
+    }
+
+    /// The size in bytes of the file at `path`. Storage that can't tell without reading the
+    /// file reads it.
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        self.read(path).map(|data| data.len() as u64)
+    }
+
+    /// Make sure everything written since the last call would survive a crash, for storage that
+    /// holds back its syncs until then. Called at the end of each flush.
+    fn barrier(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The bytes of a file, as `Storage::read_data` returns them.
+pub struct FileData(Bytes);
+
+enum Bytes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl From<Vec<u8>> for FileData {
+    fn from(data: Vec<u8>) -> Self {
+        Self(Bytes::Owned(data))
+    }
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            Bytes::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            Bytes::Mapped(map) => map,
+        }
+    }
+}
+
+/// Keeps each file of a tree in a real file.
+pub struct FileStorage;
+
+impl Storage for FileStorage {
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        DirBuilder::new().create(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+
+        Ok(paths)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        // Only Unix lets a directory be opened, and so synced, like a file.
+        if cfg!(not(unix)) && path.is_dir() {
+            return Ok(());
+        }
+
+        File::open(path)?.sync_all()
+    }
+
+    fn link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::hard_link(from, to)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>> {
+        // A shared lock is taken by a reader, which mustn't create anything.
+        let file = if shared {
+            let file = File::open(path)?;
+            file.try_lock_shared()?;
+            file
+        } else {
+            let file = File::options()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            file.try_lock_exclusive()?;
+            file
+        };
+
+        Ok(Some(file))
+    }
+}
+
+/// Keeps each file of a tree in a real file, as `FileStorage` does, but reads node files by
+/// mapping them into memory rather than copying them out, which saves a copy of every node
+/// read. Chosen with `BTreeBuilder::mmap`.
+///
+/// A mapped file must not be cut short while it is mapped, which would crash the process the
+/// next time the missing part is touched. The tree itself never changes a file while it is
+/// reading it, and holds its lock for as long as it is open to keep other trees from doing so,
+/// but nothing stops anything else writing to the backing directory. Only use this where
+/// nothing outside the tree writes to its files.
+#[cfg(feature = "mmap")]
+pub struct MmapStorage;
+
+#[cfg(feature = "mmap")]
+impl Storage for MmapStorage {
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        FileStorage.create_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        FileStorage.read(path)
+    }
+
+    fn read_data(&self, path: &Path) -> io::Result<FileData> {
+        let file = File::open(path)?;
+        // Mapping an empty file fails on some platforms, and would save nothing anyway.
+        if file.metadata()?.len() == 0 {
+            return Ok(FileData::from(Vec::new()));
+        }
+        // Safety: the tree doesn't write to a file while it is mapped, and nothing else is
+        // meant to write to the tree's files, as documented above.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        Ok(FileData(Bytes::Mapped(map)))
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        FileStorage.write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        FileStorage.rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        FileStorage.remove(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        FileStorage.list(path)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        FileStorage.sync(path)
+    }
+
+    fn link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        FileStorage.link(from, to)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        FileStorage.size(path)
+    }
+
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>> {
+        FileStorage.lock(path, shared)
+    }
+}
+
+/// Keeps each file of a tree in memory, so nothing touches the disk and everything is gone once
+/// the tree is dropped.
+#[derive(Default)]
+pub(crate) struct MemoryStorage {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    fn files(&self) -> MutexGuard<'_, HashMap<PathBuf, Vec<u8>>> {
+        self.files.lock().unwrap()
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::from(io::ErrorKind::NotFound)
+}
+
+impl Storage for MemoryStorage {
+    fn create_dir(&self, _path: &Path) -> io::Result<()> {
+        // Directories only exist as part of the paths of the files in them.
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files().get(path).cloned().ok_or_else(not_found)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.files().insert(path.to_path_buf(), data.to_vec());
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.files();
+        let data = files.remove(from).ok_or_else(not_found)?;
+        files.insert(to.to_path_buf(), data);
+
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.files().remove(path).map(drop).ok_or_else(not_found)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let files = self.files();
+        let paths = files.keys().filter(|file| file.parent() == Some(path));
+
+        Ok(paths.cloned().collect())
+    }
+
+    fn sync(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        let files = self.files();
+        let data = files.get(path).ok_or_else(not_found)?;
+
+        Ok(data.len() as u64)
+    }
+
+    fn lock(&self, _path: &Path, _shared: bool) -> io::Result<Option<File>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,271 @@
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+use std::ops::{Bound, Deref, RangeBounds};
+use std::path::PathBuf;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{external, BTree, Compare, Error, Lookup, Natural, Node, NodeRef};
+
+/// A `BTree` that can be shared between threads, behind an `RwLock`. Any number of threads can
+/// look keys up at once, while a change waits for every lookup to finish and holds off new ones
+/// until it is done.
+///
+/// Lookups go through a shared reference to the tree, so they can't load nodes into the cache.
+/// They use whatever is cached, and read anything else from storage without caching it, which
+/// is always up to date, since a node is written back before it leaves the cache.
+///
+/// For the tree to be shared, its keys, values and comparator have to be `Send` and `Sync`, as
+/// they do for any value behind an `RwLock`.
+pub struct SyncBTree<K, V, C = Natural>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    tree: RwLock<BTree<K, V, C>>,
+}
+
+impl<K, V> SyncBTree<K, V>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Ord,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    /// Create a tree in `backing_dir`, as `BTree::new` does.
+    pub fn new(backing_dir: PathBuf, capacity: usize) -> Result<Self, Error> {
+        Ok(Self::from(BTree::new(backing_dir, capacity)?))
+    }
+
+    /// Reopen the tree in `backing_dir`, as `BTree::open` does.
+    pub fn open(backing_dir: PathBuf) -> Result<Self, Error> {
+        Ok(Self::from(BTree::open(backing_dir)?))
+    }
+}
+
+impl<K, V, C> SyncBTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// The value under `key`, if there is one.
+    pub fn get<Q>(&self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+        V: Clone,
+    {
+        let tree = self.read();
+        let mut node = Shared::Cached(&tree.root_node);
+        loop {
+            match node.data.lookup(key, &tree.cmp) {
+                Lookup::Found(idx) => return Ok(Some(values(&tree, &node)?[idx].clone())),
+                Lookup::Child(idx) => {
+                    let path = node.data.children()[idx].clone();
+                    node = shared_node(&tree, &path)?;
+                }
+                Lookup::Missing => return Ok(None),
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        let tree = self.read();
+        let mut node = Shared::Cached(&tree.root_node);
+        loop {
+            match node.data.lookup(key, &tree.cmp) {
+                Lookup::Found(_) => return Ok(true),
+                Lookup::Child(idx) => {
+                    let path = node.data.children()[idx].clone();
+                    node = shared_node(&tree, &path)?;
+                }
+                Lookup::Missing => return Ok(false),
+            }
+        }
+    }
+
+    /// The entries whose keys fall within `range`, in ascending key order. They are collected
+    /// while the tree is locked for reading, rather than handed out as they are found, so that
+    /// no change has to wait on a caller that is slow to consume them.
+    pub fn range<R>(&self, range: R) -> Result<Vec<(K, V)>, Error>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+        V: Clone,
+    {
+        let tree = self.read();
+        let mut entries = Vec::new();
+        collect_range(&tree, Shared::Cached(&tree.root_node), &range, &mut entries)?;
+
+        Ok(entries)
+    }
+
+    /// See `BTree::insert`.
+    pub fn insert(&self, key: K, value: V) -> Result<Option<V>, Error> {
+        self.write().insert(key, value)
+    }
+
+    /// See `BTree::remove`.
+    pub fn remove<Q>(&self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        self.write().remove(key)
+    }
+
+    /// See `BTree::flush`.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.write().flush()
+    }
+
+    /// Lock the tree for as long as the guard is held, for anything else `BTree` can do.
+    pub fn lock(&self) -> RwLockWriteGuard<'_, BTree<K, V, C>> {
+        self.write()
+    }
+
+    /// Take the tree back out of the lock.
+    pub fn into_inner(self) -> BTree<K, V, C> {
+        self.tree.into_inner().unwrap()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTree<K, V, C>> {
+        self.tree.read().unwrap()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTree<K, V, C>> {
+        self.tree.write().unwrap()
+    }
+}
+
+impl<K, V, C> From<BTree<K, V, C>> for SyncBTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    fn from(tree: BTree<K, V, C>) -> Self {
+        Self {
+            tree: RwLock::new(tree),
+        }
+    }
+}
+
+/// A node looked up through a shared reference to its tree, which is either the cached node or
+/// a copy read from storage.
+enum Shared<'a, K, V> {
+    Cached(&'a Node<K, V>),
+    Read(Node<K, V>),
+}
+
+impl<K, V> Deref for Shared<'_, K, V> {
+    type Target = Node<K, V>;
+
+    fn deref(&self) -> &Node<K, V> {
+        match self {
+            Shared::Cached(node) => node,
+            Shared::Read(node) => node,
+        }
+    }
+}
+
+fn shared_node<'a, K, V, C>(
+    tree: &'a BTree<K, V, C>,
+    path: &NodeRef,
+) -> Result<Shared<'a, K, V>, Error>
+where
+    K: for<'b> Deserialize<'b> + Serialize,
+    V: for<'b> Deserialize<'b> + Serialize,
+    C: Compare<K>,
+{
+    if let Some(node) = tree.node_cache.peek(path) {
+        return Ok(Shared::Cached(node));
+    }
+    let node = Node::load(&*tree.storage, tree.encoding, path)?;
+
+    Ok(Shared::Read(node))
+}
+
+/// The values of `node`, read from its values file if they haven't been loaded.
+fn values<'a, K, V, C>(tree: &BTree<K, V, C>, node: &'a Node<K, V>) -> Result<Cow<'a, [V]>, Error>
+where
+    K: for<'b> Deserialize<'b> + Serialize,
+    V: for<'b> Deserialize<'b> + Serialize + Clone,
+    C: Compare<K>,
+{
+    if node.values_loaded {
+        return Ok(Cow::Borrowed(&node.data.values));
+    }
+    let (values, _) = external::load_values(&*tree.storage, tree.encoding, &node.path)?;
+
+    Ok(Cow::Owned(values))
+}
+
+/// Add the entries of the subtree under `node` that fall within `range` to `entries`, in order.
+fn collect_range<K, V, C, R>(
+    tree: &BTree<K, V, C>,
+    node: Shared<'_, K, V>,
+    range: &R,
+    entries: &mut Vec<(K, V)>,
+) -> Result<(), Error>
+where
+    K: for<'a> Deserialize<'a> + Serialize + Clone,
+    V: for<'a> Deserialize<'a> + Serialize + Clone,
+    C: Compare<K>,
+    R: RangeBounds<K>,
+{
+    let keys = &node.data.keys;
+    let cmp = &tree.cmp;
+    let lo = match range.start_bound() {
+        Bound::Included(start) => {
+            keys.partition_point(|key| cmp.compare(key, start) == Ordering::Less)
+        }
+        Bound::Excluded(start) => {
+            keys.partition_point(|key| cmp.compare(key, start) != Ordering::Greater)
+        }
+        Bound::Unbounded => 0,
+    };
+    let hi = match range.end_bound() {
+        Bound::Included(end) => {
+            keys.partition_point(|key| cmp.compare(key, end) != Ordering::Greater)
+        }
+        Bound::Excluded(end) => keys.partition_point(|key| cmp.compare(key, end) == Ordering::Less),
+        Bound::Unbounded => keys.len(),
+    };
+    // A key both past the end and before the start means the range is empty.
+    if hi < lo {
+        return Ok(());
+    }
+
+    let values = if hi > lo {
+        values(tree, &node)?
+    } else {
+        Cow::Borrowed(&[][..])
+    };
+    for idx in lo..=hi {
+        if let Some(children) = node.data.children.as_ref() {
+            collect_range(tree, shared_node(tree, &children[idx])?, range, entries)?;
+        }
+        if idx < hi {
+            entries.push((keys[idx].clone(), values[idx].clone()));
+        }
+    }
+
+    Ok(())
+}
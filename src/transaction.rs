@@ -0,0 +1,138 @@
+use std::borrow::Borrow;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BTree, Compare, Error, Natural, Node, NodeRef};
+
+impl<K, V, C> BTree<K, V, C>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+    C: Compare<K>,
+{
+    /// Make every change `f` makes through the `Txn` it is given as one, which happens only if
+    /// `f` returns `Ok`. If it returns an error, or a change fails along the way, every change
+    /// is thrown away and the tree is left as it was, and the error is returned.
+    ///
+    /// The tree is flushed first. No node is written while `f` runs, so every node it changes
+    /// stays in the cache until the end, however many that is. With the write-ahead log, the
+    /// changes are then logged and written together, as any single change is, and with
+    /// copy-on-write they are flushed as one version. Otherwise they are flushed, which a crash
+    /// can interrupt part way through.
+    pub fn transaction<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Txn<K, V, C>) -> Result<(), Error>,
+    {
+        self.check_writable()?;
+        // What is on disk is then what a rollback goes back to.
+        self.flush()?;
+        self.node_cache.defer_evictions()?;
+        let len = self.len;
+        let free_nodes = self.free_nodes.clone();
+        let next_node = self.naming.next_node;
+
+        self.transaction = true;
+        let result = f(&mut Txn { tree: self });
+        self.transaction = false;
+        if let Err(e) = result {
+            self.roll_back(len, free_nodes, next_node)?;
+            return Err(e);
+        }
+
+        if self.write_ahead_log {
+            self.commit()
+        } else {
+            self.flush()?;
+            self.node_cache.resume_evictions()
+        }
+    }
+
+    /// Throw away every change since the flush at the start of a transaction, going back to the
+    /// tree on disk, which had `len` entries, `free_nodes` to hand out and `next_node` to name
+    /// the next new node after, if it names them in sequence.
+    fn roll_back(
+        &mut self,
+        len: usize,
+        free_nodes: Vec<NodeRef>,
+        next_node: Option<u64>,
+    ) -> Result<(), Error> {
+        // The nodes deleted since are still on disk, and are left there.
+        self.node_cache.clear();
+        self.node_cache.resume_evictions()?;
+        self.len = len;
+        self.free_nodes = free_nodes;
+        self.naming.next_node = next_node;
+
+        let path = mem::take(&mut self.root_node.path);
+        let mut root = Node::load(&*self.storage, self.encoding, &path)?;
+        root.load_values(&*self.storage, self.encoding)?;
+        self.root_node = root;
+
+        // The filter may have been rebuilt without the keys the transaction removed.
+        self.rebuild_bloom_filter()
+    }
+}
+
+/// The tree as `BTree::transaction` hands it to its closure, through which every change is
+/// held back until the transaction is committed.
+pub struct Txn<'a, K, V, C = Natural>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    tree: &'a mut BTree<K, V, C>,
+}
+
+impl<K, V, C> Txn<'_, K, V, C>
+where
+    K: for<'de> Deserialize<'de> + Serialize,
+    V: for<'de> Deserialize<'de> + Serialize,
+    C: Compare<K>,
+{
+    /// The number of entries in the tree, counting the changes made so far.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// As `BTree::insert`.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
+        self.tree.insert(key, value)
+    }
+
+    /// As `BTree::remove`.
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: ?Sized,
+    {
+        self.tree.remove(key)
+    }
+
+    /// As `BTree::get`, which sees the changes made so far.
+    pub fn get<Q>(&mut self, key: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: Serialize + ?Sized,
+        V: Clone,
+    {
+        self.tree.get(key)
+    }
+
+    /// As `BTree::contains_key`.
+    pub fn contains_key<Q>(&mut self, key: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        Q: Serialize + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+}
@@ -0,0 +1,177 @@
+use std::io;
+use std::path::Path;
+
+use rmp_serde::Serializer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{self, Key};
+use crate::storage::Storage;
+use crate::{
+    remove_if_exists, remove_node, save_node, tree_file, Error, Meta, Node, NodeData, NodeRef,
+    META_FILE,
+};
+
+pub(crate) const WAL_FILE: &str = "wal";
+
+/// An entry in the write-ahead log, as written. It borrows what it records so that logging a
+/// batch doesn't copy any nodes.
+///
+/// A batch is every write one change to the tree makes, in no particular order, followed by
+/// `Commit`. Its entries are only applied once the commit marker is on disk.
+#[derive(Serialize)]
+enum Record<'a, K, V> {
+    /// A node, with its values alongside since `NodeData` leaves them out.
+    Write(&'a NodeRef, &'a NodeData<K, V>, &'a [V]),
+    Remove(&'a NodeRef),
+    Meta(&'a Meta),
+    Commit,
+}
+
+/// An entry in the write-ahead log, as read back. This must match `Record` variant for variant.
+#[derive(Deserialize)]
+enum Entry<K, V> {
+    Write(NodeRef, NodeData<K, V>, Vec<V>),
+    Remove(NodeRef),
+    Meta(Meta),
+    Commit,
+}
+
+/// Write a committed batch to the log of `namespace` in `backing_dir`, replacing whatever it
+/// held, and make sure it has reached the disk before returning. The log of an encrypted tree
+/// is encrypted as a whole, with the key of `meta`.
+pub(crate) fn log<'a, K, V>(
+    storage: &dyn Storage,
+    backing_dir: &Path,
+    namespace: Option<&str>,
+    nodes: impl Iterator<Item = &'a Node<K, V>>,
+    deleted: &[NodeRef],
+    meta: &Meta,
+) -> Result<(), Error>
+where
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+{
+    let mut buf = Vec::new();
+    let mut serializer = Serializer::new(&mut buf);
+    for node in nodes {
+        Record::Write(&node.path, &node.data, &node.data.values).serialize(&mut serializer)?;
+    }
+    for path in deleted {
+        Record::<K, V>::Remove(path).serialize(&mut serializer)?;
+    }
+    Record::<K, V>::Meta(meta).serialize(&mut serializer)?;
+    Record::<K, V>::Commit.serialize(&mut serializer)?;
+
+    let path = tree_file(backing_dir, WAL_FILE, namespace);
+    if meta.encoding.encrypted {
+        buf = crypto::seal(meta.encoding.key(), &path, &buf).map_err(|e| e.at(&path))?;
+    }
+    storage.write(&path, &buf)?;
+    storage.sync(&path)?;
+    // The log is a new file, which is only sure to be found after a crash once its directory is
+    // synced too.
+    storage.sync(backing_dir)?;
+
+    Ok(())
+}
+
+/// Remove the log of `namespace` from `backing_dir` once every write in it has been applied.
+pub(crate) fn clear(
+    storage: &dyn Storage,
+    backing_dir: &Path,
+    namespace: Option<&str>,
+) -> Result<(), Error> {
+    remove_if_exists(storage, &tree_file(backing_dir, WAL_FILE, namespace))?;
+
+    Ok(())
+}
+
+/// Deal with a log left in `namespace` of `backing_dir` by a tree that didn't get to clear it.
+/// A batch that reached its commit marker may have been partly applied, so it is applied again
+/// in full. A batch cut short before its commit marker was never applied at all, so it is
+/// dropped, leaving the tree as it was before the change it recorded. The log of an encrypted
+/// tree is read with `key`.
+pub(crate) fn recover<K, V>(
+    storage: &dyn Storage,
+    backing_dir: &Path,
+    namespace: Option<&str>,
+    key: Option<Key>,
+) -> Result<(), Error>
+where
+    K: for<'a> Deserialize<'a> + Serialize,
+    V: for<'a> Deserialize<'a> + Serialize,
+{
+    let path = tree_file(backing_dir, WAL_FILE, namespace);
+    let buf = match storage.read(&path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    // An encrypted log cut short fails to decrypt as a whole, and so is dropped as any batch
+    // cut short is. The key has been checked by now, so it can't be the key that is wrong.
+    let buf = match key.map(|key| crypto::open(key, &path, &buf)) {
+        Some(Ok(buf)) => buf,
+        Some(Err(_)) => return clear(storage, backing_dir, namespace),
+        None => buf,
+    };
+
+    let mut reader = &buf[..];
+    let mut entries = Vec::new();
+    // A batch that ends early, whether cleanly or partway through an entry, fails to decode.
+    while let Ok(entry) = rmp_serde::from_read::<_, Entry<K, V>>(&mut reader) {
+        if let Entry::Commit = entry {
+            apply(
+                storage,
+                &tree_file(backing_dir, META_FILE, namespace),
+                entries,
+                key,
+            )?;
+            storage.barrier()?;
+            break;
+        }
+        entries.push(entry);
+    }
+
+    clear(storage, backing_dir, namespace)
+}
+
+/// Apply a committed batch, saving the meta file it holds at `meta_path`, and encrypting the
+/// nodes with `key` if the tree is encrypted.
+fn apply<K, V>(
+    storage: &dyn Storage,
+    meta_path: &Path,
+    entries: Vec<Entry<K, V>>,
+    key: Option<Key>,
+) -> Result<(), Error>
+where
+    K: Serialize,
+    V: Serialize + DeserializeOwned,
+{
+    // The meta file is logged with every batch, and says how the nodes in it are encoded.
+    let encoding = entries.iter().find_map(|entry| match entry {
+        Entry::Meta(meta) => Some(meta.encoding),
+        _ => None,
+    });
+    let encoding = encoding
+        .ok_or_else(|| Error::Invalid(String::from("the write-ahead log has no meta entry")))?
+        .with_key(key)?;
+
+    for entry in entries {
+        match entry {
+            Entry::Write(path, mut data, values) => {
+                data.values = values;
+                save_node(storage, encoding, &path, &mut data)?;
+            }
+            Entry::Remove(path) => remove_node::<V>(storage, encoding, &path)?,
+            Entry::Meta(mut meta) => {
+                // The key is never logged, but is needed to sign the meta file.
+                meta.encoding = encoding;
+                meta.save(storage, meta_path)?
+            }
+            Entry::Commit => unreachable!("a batch ends at its commit marker"),
+        }
+    }
+
+    Ok(())
+}
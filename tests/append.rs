@@ -0,0 +1,58 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn append_disjoint_trees() {
+    let dir = common::temp_dir();
+    let other_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    let mut other = BTree::new(other_dir.clone(), 3).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+        other.insert(key + 100, key + 100).unwrap();
+    }
+
+    tree.append(&mut other).unwrap();
+    tree.validate().unwrap();
+    other.validate().unwrap();
+    assert!(other.is_empty());
+    assert_eq!(tree.len(), 200);
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(entries, (0..200).map(|key| (key, key)).collect::<Vec<_>>());
+    drop(tree);
+    drop(other);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(other_dir).unwrap();
+}
+
+#[test]
+fn append_overlapping_trees_takes_the_other_value() {
+    let dir = common::temp_dir();
+    let other_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    let mut other = BTree::new(other_dir.clone(), 5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, String::from("self")).unwrap();
+    }
+    for key in 50..150u64 {
+        other.insert(key, String::from("other")).unwrap();
+    }
+
+    tree.append(&mut other).unwrap();
+    tree.validate().unwrap();
+    assert!(other.is_empty());
+    assert_eq!(tree.len(), 150);
+    assert_eq!(tree.get(&49).unwrap().as_deref(), Some("self"));
+    assert_eq!(tree.get(&50).unwrap().as_deref(), Some("other"));
+    assert_eq!(tree.get(&99).unwrap().as_deref(), Some("other"));
+    assert_eq!(tree.get(&149).unwrap().as_deref(), Some("other"));
+    drop(tree);
+    drop(other);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(other_dir).unwrap();
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use btree::{BTree, BTreeBuilder, Durability, FileStorage, Storage};
+
+#[test]
+fn background_flush_is_durable_on_close() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, String> = BTreeBuilder::new(dir.clone())
+        .capacity(5)
+        .cache_capacity(4)
+        .durability(Durability::Fsync)
+        .background_flush(true)
+        .build()
+        .unwrap();
+    for key in 0..1000 {
+        tree.insert(key, key.to_string()).unwrap();
+    }
+    for key in (0..1000).step_by(3) {
+        tree.remove(&key).unwrap();
+    }
+    tree.close().unwrap();
+
+    let mut tree = BTree::<u64, String>::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    for key in 0..1000 {
+        let expected = (key % 3 != 0).then(|| key.to_string());
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Files on disk, whose writes fail once `failing` is set.
+#[derive(Default)]
+struct Failing {
+    failing: AtomicBool,
+}
+
+impl Storage for Failing {
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        FileStorage.create_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        FileStorage.read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(io::Error::other("disk full"));
+        }
+        FileStorage.write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        FileStorage.rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        FileStorage.remove(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        FileStorage.list(path)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        FileStorage.sync(path)
+    }
+
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>> {
+        FileStorage.lock(path, shared)
+    }
+}
+
+#[test]
+fn background_flush_stops_at_a_failed_write() {
+    let dir = common::temp_dir();
+    let storage = Arc::new(Failing::default());
+    let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+        .capacity(5)
+        .background_flush(true)
+        .storage(storage.clone())
+        .build()
+        .unwrap();
+    tree.insert(1, 1).unwrap();
+
+    storage.failing.store(true, Ordering::SeqCst);
+    assert!(tree.flush().is_err());
+
+    // The failure is reported from then on, and nothing written after it is written out.
+    storage.failing.store(false, Ordering::SeqCst);
+    assert!(tree.flush().is_err());
+    let _ = tree.insert(2, 2);
+    assert!(tree.close().is_err());
+
+    // What was last flushed is left as it was.
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.get(&2).unwrap(), None);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,190 @@
+#![cfg(feature = "bloom")]
+
+mod common;
+
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+const KEYS: u64 = 1000;
+
+/// A tree of the even keys below `2 * KEYS`, which caches nothing, so that every node a lookup
+/// needs below the root is read from storage.
+fn even_tree(dir: &Path, bloom_filter: bool) -> BTree<u64, u64> {
+    let mut tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(5)
+        .bloom_filter(bloom_filter)
+        .build()
+        .unwrap();
+    tree.extend((0..KEYS).map(|i| (i * 2, i))).unwrap();
+    tree.set_cache_capacity(0).unwrap();
+
+    tree
+}
+
+/// The mean number of nodes read from storage to look up each odd key, none of which are in the
+/// tree.
+fn loads_per_miss(tree: &mut BTree<u64, u64>) -> f64 {
+    tree.reset_cache_stats();
+    for i in 0..KEYS {
+        assert_eq!(tree.get(&(i * 2 + 1)).unwrap(), None);
+    }
+
+    tree.cache_stats().misses as f64 / KEYS as f64
+}
+
+#[test]
+fn misses_read_fewer_nodes_with_a_filter() {
+    let dir = common::temp_dir();
+    let mut tree = even_tree(&dir, false);
+    let without = loads_per_miss(&mut tree);
+    drop(tree);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut tree = even_tree(&dir, true);
+    let with = loads_per_miss(&mut tree);
+    assert!(without > 3.0, "{without} loads per miss without a filter");
+    assert!(with < 0.5, "{with} loads per miss with a filter");
+
+    // Every key that is there is still found.
+    for i in 0..KEYS {
+        assert_eq!(tree.get(&(i * 2)).unwrap(), Some(i));
+        assert!(tree.contains_key(&(i * 2)).unwrap());
+    }
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn filter_is_kept_with_the_tree() {
+    let dir = common::temp_dir();
+    let mut tree = even_tree(&dir, true);
+    for i in (0..KEYS).step_by(2) {
+        tree.remove(&(i * 2)).unwrap();
+    }
+    tree.insert(KEYS * 10 + 1, 1).unwrap();
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    tree.set_cache_capacity(0).unwrap();
+    // Reading the filter back reads no nodes, unlike building it again would.
+    assert_eq!(tree.cache_stats().misses, 0);
+    assert!(loads_per_miss(&mut tree) < 0.5);
+    assert_eq!(tree.get(&(KEYS * 10 + 1)).unwrap(), Some(1));
+    for i in 0..KEYS {
+        let expected = (i % 2 == 1).then_some(i);
+        assert_eq!(tree.get(&(i * 2)).unwrap(), expected);
+    }
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lost_filter_is_built_again() {
+    let dir = common::temp_dir();
+    let mut tree = even_tree(&dir, true);
+    tree.flush().unwrap();
+    // An older filter, as if the tree crashed after its meta file was replaced but before the
+    // filter that goes with it was written.
+    let old = fs::read(dir.join("bloom")).unwrap();
+    tree.insert(KEYS * 10 + 1, 1).unwrap();
+    tree.close().unwrap();
+    fs::write(dir.join("bloom"), old).unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.get(&(KEYS * 10 + 1)).unwrap(), Some(1));
+    assert!(loads_per_miss(&mut tree) < 0.5);
+    tree.clear().unwrap();
+    assert_eq!(tree.get(&2).unwrap(), None);
+    tree.insert(2, 2).unwrap();
+    assert_eq!(tree.get(&2).unwrap(), Some(2));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn filter_is_saved_by_flush_rather_than_each_commit() {
+    for (write_ahead_log, copy_on_write) in [(true, false), (false, true)] {
+        let dir = common::temp_dir();
+        let mut tree = even_tree(&dir, true);
+        tree.set_write_ahead_log(write_ahead_log).unwrap();
+        tree.set_copy_on_write(copy_on_write).unwrap();
+        tree.flush().unwrap();
+        let saved = fs::read(dir.join("bloom")).unwrap();
+
+        // Each of these is committed on its own, and none of them writes the filter.
+        for i in 0..100 {
+            tree.insert(KEYS * 10 + i, i).unwrap();
+        }
+        assert_eq!(fs::read(dir.join("bloom")).unwrap(), saved);
+        tree.flush().unwrap();
+        assert_ne!(fs::read(dir.join("bloom")).unwrap(), saved);
+
+        // A crash before the next flush leaves a filter behind that is built again. A crash would
+        // release the lock too, which the forgotten tree still holds, so the lock file goes
+        // instead.
+        tree.insert(KEYS * 20, 1).unwrap();
+        mem::forget(tree);
+        fs::remove_file(dir.join("LOCK")).unwrap();
+        let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+        assert!(tree.cache_stats().misses > 0);
+        assert_eq!(tree.get(&(KEYS * 20)).unwrap(), Some(1));
+        assert_eq!(tree.get(&(KEYS * 10 + 99)).unwrap(), Some(99));
+        tree.set_cache_capacity(0).unwrap();
+        assert!(loads_per_miss(&mut tree) < 0.5);
+        drop(tree);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn repair_keeps_the_filter() {
+    let dir = common::temp_dir();
+    let tree = even_tree(&dir, true);
+    tree.close().unwrap();
+
+    let report = BTree::<u64, u64>::repair(dir.clone()).unwrap();
+    // The filter's file is no node, and isn't counted as an unreadable one.
+    assert_eq!(report.unreadable_nodes, 0);
+    assert_eq!(report.entries_recovered, KEYS as usize);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    tree.set_cache_capacity(0).unwrap();
+    // The repaired tree has a filter of its own, which is read back rather than built again.
+    assert_eq!(tree.cache_stats().misses, 0);
+    assert!(loads_per_miss(&mut tree) < 0.5);
+    for i in 0..KEYS {
+        assert_eq!(tree.get(&(i * 2)).unwrap(), Some(i));
+    }
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reserve_more_than_a_filter_holds() {
+    let dir = common::temp_dir();
+    let mut tree = even_tree(&dir, true);
+    let result = tree.reserve(usize::MAX);
+    assert!(matches!(result, Err(Error::TooManyEntries(usize::MAX))));
+
+    // The filter only grows so far, however many entries are on the way.
+    tree.reserve(usize::MAX - KEYS as usize).unwrap();
+    tree.insert(1, 1).unwrap();
+    tree.close().unwrap();
+    assert!(fs::metadata(dir.join("bloom")).unwrap().len() <= 100 << 20);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.get(&1).unwrap(), Some(1));
+    assert_eq!(tree.get(&2).unwrap(), Some(1));
+    assert_eq!(tree.get(&3).unwrap(), None);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,51 @@
+use btree::BTree;
+
+fn word_tree() -> BTree<String, u32> {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    for (idx, word) in [
+        "apple",
+        "banana",
+        "cherry",
+        "date",
+        "elderberry",
+        "fig",
+        "grape",
+    ]
+    .iter()
+    .enumerate()
+    {
+        tree.insert(word.to_string(), idx as u32).unwrap();
+    }
+
+    tree
+}
+
+#[test]
+fn get_by_str() {
+    let mut tree = word_tree();
+    assert_eq!(tree.get("apple").unwrap(), Some(0));
+    assert_eq!(tree.get("grape").unwrap(), Some(6));
+    assert_eq!(tree.get("kiwi").unwrap(), None);
+    assert!(tree.contains_key("date").unwrap());
+    assert!(!tree.contains_key("dates").unwrap());
+}
+
+#[test]
+fn get_mut_by_str() {
+    let mut tree = word_tree();
+    *tree.get_mut("fig").unwrap().unwrap() += 10;
+    assert_eq!(tree.get("fig").unwrap(), Some(15));
+}
+
+#[test]
+fn remove_by_str() {
+    let mut tree = word_tree();
+    // Removing from the root of a tree that has split exercises the separator path.
+    for word in ["cherry", "apple", "kiwi", "grape"] {
+        tree.remove(word).unwrap();
+    }
+    assert_eq!(tree.len(), 4);
+    tree.validate().unwrap();
+    let keys: Vec<String> = tree.keys().collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, ["banana", "date", "elderberry", "fig"]);
+}
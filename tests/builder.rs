@@ -0,0 +1,188 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+#[test]
+fn build_creates_missing_tree() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+        .capacity(5)
+        .cache_capacity(2)
+        .build()
+        .unwrap();
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in 0..100 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+    assert!(tree.cache_stats().evictions > 0);
+    tree.close().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn build_opens_existing_tree() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+
+    for create_if_missing in [true, false] {
+        let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+            .capacity(7)
+            .create_if_missing(create_if_missing)
+            .build()
+            .unwrap();
+        assert_eq!(tree.len(), 100);
+        for key in 0..100 {
+            assert_eq!(tree.get(&key).unwrap(), Some(key));
+        }
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn build_without_creating_missing_tree() {
+    let dir = common::temp_dir();
+    let result: Result<BTree<u64, u64>, _> = BTreeBuilder::new(dir.clone())
+        .create_if_missing(false)
+        .build();
+
+    assert!(matches!(result, Err(Error::Missing)));
+    assert!(!dir.exists());
+}
+
+/// The names of the files in `dir` other than the meta and lock files.
+fn node_file_names(dir: &std::path::Path) -> Vec<String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != "meta" && name != "LOCK")
+        .collect()
+}
+
+#[test]
+fn build_with_file_naming() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+        .capacity(3)
+        .file_prefix("node-")
+        .file_extension("bt")
+        .build()
+        .unwrap();
+    for key in 0..50 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+
+    // New nodes made after reopening are named the same way.
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in 50..100 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.validate().unwrap();
+    tree.close().unwrap();
+
+    let names = node_file_names(&dir);
+    assert!(names.len() > 20);
+    assert!(names.contains(&String::from("node-root.bt")));
+    assert!(names.contains(&String::from("node-root.values.bt")));
+    for name in &names {
+        assert!(name.starts_with("node-") && name.ends_with(".bt"), "{name}");
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn build_with_invalid_file_naming() {
+    let dir = common::temp_dir();
+    let result: Result<BTree<u64, u64>, _> = BTreeBuilder::new(dir.clone())
+        .file_extension("tar.gz")
+        .build();
+    assert!(matches!(result, Err(Error::InvalidFileName(name)) if name == "tar.gz"));
+
+    let result: Result<BTree<u64, u64>, _> =
+        BTreeBuilder::new(dir.clone()).file_prefix("a/b").build();
+    assert!(matches!(result, Err(Error::InvalidFileName(name)) if name == "a/b"));
+    assert!(!dir.exists());
+}
+
+/// `contents` with every mention of `dir` left out.
+fn without_dir(contents: &[u8], dir: &std::path::Path) -> Vec<u8> {
+    let dir = dir.to_str().unwrap().as_bytes();
+    let mut rest = contents;
+    let mut left = Vec::with_capacity(contents.len());
+    while let Some((&byte, after)) = rest.split_first() {
+        match rest.strip_prefix(dir) {
+            Some(after_dir) => rest = after_dir,
+            None => {
+                left.push(byte);
+                rest = after;
+            }
+        }
+    }
+
+    left
+}
+
+#[test]
+fn build_with_deterministic_names() {
+    let build = |dir: &std::path::Path| {
+        // Every other value is large enough to be moved out to a file of its own.
+        let value = |i: u64| vec![i as u8; if i.is_multiple_of(2) { 4 } else { 64 }];
+        let mut tree: BTree<u64, Vec<u8>> = BTreeBuilder::new(dir.to_path_buf())
+            .capacity(3)
+            .deterministic_names(true)
+            .external_values(32)
+            .build()
+            .unwrap();
+        for i in 0..100 {
+            tree.insert((i * 37) % 100, value(i)).unwrap();
+        }
+        tree.close().unwrap();
+
+        // The numbering goes on from where it was after reopening.
+        let mut tree: BTree<u64, Vec<u8>> = BTree::open(dir.to_path_buf()).unwrap();
+        for key in (0..100).step_by(3) {
+            tree.remove(&key).unwrap();
+        }
+        for key in 100..150 {
+            tree.insert(key, value(key)).unwrap();
+        }
+        tree.close().unwrap();
+
+        // Nodes name their children by path, so the directory is left out of what is compared,
+        // along with the checksum in front of each file that covers it.
+        let files: std::collections::BTreeMap<String, Vec<u8>> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let contents = fs::read(entry.path()).unwrap();
+                let contents = match contents.get(4..) {
+                    Some(rest) if entry.file_name() != "meta" => without_dir(rest, dir),
+                    _ => contents,
+                };
+                (entry.file_name().into_string().unwrap(), contents)
+            })
+            .collect();
+        fs::remove_dir_all(dir).unwrap();
+
+        files
+    };
+
+    let first = build(&common::temp_dir());
+    let second = build(&common::temp_dir());
+    assert!(first.contains_key("node-0000001"));
+    assert!(first.keys().any(|name| name.contains(".value-")));
+    assert!(first.len() > 20);
+    assert!(first.keys().eq(second.keys()));
+    assert!(first == second);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, Error};
+
+#[test]
+fn build_sorted_tree() {
+    let dir = common::temp_dir();
+    let mut tree =
+        BTree::build_sorted(dir.clone(), 16, (0..100_000u64).map(|key| (key, key * 2))).unwrap();
+    assert_eq!(tree.len(), 100_000);
+    tree.validate().unwrap();
+
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        entries,
+        (0..100_000).map(|key| (key, key * 2)).collect::<Vec<_>>()
+    );
+    tree.close().unwrap();
+
+    // The tree behaves like any other once reopened.
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 100_000);
+    for key in (0..100_000).step_by(997) {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 2));
+    }
+    for key in (0..100_000).step_by(3) {
+        tree.remove(&key).unwrap();
+    }
+    tree.insert(100_000, 0).unwrap();
+    assert_eq!(tree.len(), 66_667);
+    tree.validate().unwrap();
+    assert_eq!(tree.keys().count(), 66_667);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn build_small_sorted_trees() {
+    // Sizes either side of where the last node on a level has to borrow from its sibling.
+    for capacity in [3, 4, 5] {
+        for len in 0..60u64 {
+            let dir = common::temp_dir();
+            let mut tree =
+                BTree::build_sorted(dir.clone(), capacity, (0..len).map(|key| (key, key))).unwrap();
+            assert_eq!(tree.len(), len as usize);
+            tree.validate().unwrap();
+            let keys: Vec<u64> = tree.keys().collect::<Result<_, _>>().unwrap();
+            assert_eq!(keys, (0..len).collect::<Vec<_>>());
+            drop(tree);
+
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
+
+#[test]
+fn build_sorted_rejects_unsorted_entries() {
+    for keys in [vec![0, 1, 3, 2], vec![0, 1, 1, 2]] {
+        let dir = common::temp_dir();
+        let result =
+            BTree::build_sorted(dir.clone(), 5, keys.into_iter().map(|key: u64| (key, key)));
+        assert!(matches!(result, Err(Error::Unsorted)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
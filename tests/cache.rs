@@ -0,0 +1,230 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, BTreeBuilder, CacheStats};
+
+#[test]
+fn repeated_reads_are_served_from_the_cache() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.flush().unwrap();
+    drop(tree);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.get(&42).unwrap(), Some(42));
+
+    // With every node below the root deleted from disk, the only way to find the key again is
+    // through the nodes the first lookup cached.
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if ![Some("root"), Some("meta"), Some("LOCK")].contains(&path.file_name().unwrap().to_str())
+        {
+            fs::remove_file(path).unwrap();
+        }
+    }
+    assert_eq!(tree.get(&42).unwrap(), Some(42));
+    assert!(tree.get(&0).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn evicted_nodes_are_written_back() {
+    // Far more nodes than the cache holds, so most dirty nodes are written when they are evicted
+    // rather than by the final flush.
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..3000 {
+        let key = (i * 7919) % 3000;
+        tree.insert(key, key + 1).unwrap();
+    }
+    tree.flush().unwrap();
+    drop(tree);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 3000);
+    for key in 0..3000 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key + 1));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cache_stats_count_hits_misses_and_evictions() {
+    // Seven keys in a tree of capacity 5 split the root once, leaving `2` in the root with `0, 1`
+    // and `3..=6` in its two leaves.
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..7 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.flush().unwrap();
+    drop(tree);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    tree.get(&0).unwrap();
+    tree.get(&1).unwrap();
+    tree.get(&6).unwrap();
+    tree.get(&2).unwrap();
+    tree.get(&6).unwrap();
+    assert_eq!(
+        tree.cache_stats(),
+        CacheStats {
+            hits: 2,
+            misses: 2,
+            evictions: 0,
+        }
+    );
+
+    tree.set_cache_capacity(1).unwrap();
+    tree.get(&0).unwrap();
+    assert_eq!(
+        tree.cache_stats(),
+        CacheStats {
+            hits: 2,
+            misses: 3,
+            evictions: 2,
+        }
+    );
+
+    tree.reset_cache_stats();
+    assert_eq!(tree.cache_stats(), CacheStats::default());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn exercise_with_cache_capacity(capacity: usize) {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    tree.set_cache_capacity(capacity).unwrap();
+
+    for i in 0..500 {
+        let key = (i * 7919) % 500;
+        tree.insert(key, key).unwrap();
+    }
+    for key in (0..500).step_by(2) {
+        assert_eq!(tree.remove(&key).unwrap(), Some(key));
+    }
+    for key in 0..500 {
+        let expected = if key % 2 == 1 { Some(key) } else { None };
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+    let keys: Vec<u64> = tree.iter().map(|entry| entry.unwrap().0).collect();
+    assert_eq!(keys, (1..500).step_by(2).collect::<Vec<_>>());
+
+    tree.flush().unwrap();
+    drop(tree);
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in 0..500 {
+        let expected = if key % 2 == 1 { Some(key) } else { None };
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn tiny_cache_stays_correct() {
+    exercise_with_cache_capacity(1);
+}
+
+#[test]
+fn disabled_cache_stays_correct() {
+    exercise_with_cache_capacity(0);
+}
+
+#[test]
+fn shrinking_the_cache_writes_back_evicted_nodes() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..200 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.set_cache_capacity(0).unwrap();
+    tree.flush().unwrap();
+    drop(tree);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in 0..200 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cache_stays_within_byte_budget() {
+    const BUDGET: usize = 64 * 1024;
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, String> = BTreeBuilder::new(dir.clone())
+        .capacity(4)
+        .cache_capacity(100_000)
+        .cache_budget(BUDGET)
+        .build()
+        .unwrap();
+    // Every tenth value is big enough that a few nodes holding them fill the budget, while a
+    // node of small values takes a tiny part of it.
+    let value = |key: u64| match key % 10 {
+        0 => "x".repeat(4096),
+        _ => key.to_string(),
+    };
+    for i in 0..1000 {
+        let key = (i * 7919) % 1000;
+        tree.insert(key, value(key)).unwrap();
+        assert!(tree.cache_bytes().unwrap().unwrap() <= BUDGET);
+    }
+    for key in (0..1000).step_by(3) {
+        assert_eq!(tree.get(&key).unwrap(), Some(value(key)));
+        assert!(tree.cache_bytes().unwrap().unwrap() <= BUDGET);
+    }
+    assert!(tree.cache_stats().evictions > 0);
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, String> = BTree::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.cache_bytes().unwrap(), None);
+    for key in 0..1000 {
+        assert_eq!(tree.get(&key).unwrap(), Some(value(key)));
+    }
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn shrink_to_fit_drops_leaves_from_the_cache() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..500 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in 0..500 {
+        tree.get(&key).unwrap();
+    }
+    let cached = tree.cached_nodes();
+    let stats = tree.stats().unwrap();
+    // Every internal node but the root, which isn't cached.
+    let internal = stats.node_count - stats.leaf_count - 1;
+
+    tree.shrink_to_fit().unwrap();
+    assert!(tree.cached_nodes() < cached);
+    assert!(tree.cached_nodes() <= internal);
+    tree.validate().unwrap();
+    for key in 0..500 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+    tree.insert(500, 500).unwrap();
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 501);
+    assert_eq!(tree.get(&500).unwrap(), Some(500));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, Error};
+
+/// The number of node files in `dir` once `tree` is flushed.
+fn node_count(tree: &mut BTree<u64, u64>, dir: &Path) -> usize {
+    tree.flush().unwrap();
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_none() && !path.ends_with("meta") && !path.ends_with("LOCK")
+        })
+        .count()
+}
+
+fn exercise_capacity(capacity: usize) {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), capacity).unwrap();
+    for i in 0..300 {
+        let key = (i * 7919) % 300;
+        tree.insert(key, key * 2).unwrap();
+    }
+    // Splits at every level, since 300 keys can't fit in fewer than `300 / capacity` nodes.
+    assert!(node_count(&mut tree, &dir) >= 300 / capacity);
+    for key in 0..300 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 2));
+    }
+    tree.validate().unwrap();
+
+    // Removing everything exercises borrowing and merging at the minimum occupancy.
+    for i in 0..300 {
+        let key = (i * 4177) % 300;
+        assert_eq!(tree.remove(&key).unwrap(), Some(key * 2));
+        if i % 50 == 0 {
+            tree.validate().unwrap();
+        }
+    }
+    assert!(tree.is_empty());
+    assert_eq!(node_count(&mut tree, &dir), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn capacity_three() {
+    exercise_capacity(3);
+}
+
+#[test]
+fn capacity_four() {
+    exercise_capacity(4);
+}
+
+#[test]
+fn capacity_five() {
+    exercise_capacity(5);
+}
+
+#[test]
+fn capacity_too_small() {
+    for capacity in [0, 1, 2] {
+        let dir = common::temp_dir();
+        assert!(matches!(
+            BTree::<u64, u64>::new(dir.clone(), capacity),
+            Err(Error::InvalidCapacity(c)) if c == capacity
+        ));
+        assert!(!dir.exists());
+    }
+}
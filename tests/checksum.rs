@@ -0,0 +1,82 @@
+mod common;
+
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use btree::{BTree, Error, NodeError};
+
+/// Overwrite a byte in the middle of the file at `path`.
+fn corrupt(path: &Path) {
+    let mut file = fs::read(path).unwrap();
+    let idx = file.len() / 2;
+    file[idx] ^= 0xff;
+    fs::write(path, file).unwrap();
+}
+
+fn small_tree(dir: &Path) {
+    let mut tree = BTree::new(dir.to_path_buf(), 5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+}
+
+fn mismatched_path<T: Debug>(result: Result<T, Error>) -> PathBuf {
+    match result {
+        Err(Error::Node {
+            source: NodeError::ChecksumMismatch { path },
+            ..
+        }) => path,
+        result => panic!("expected a checksum mismatch, got {:?}", result),
+    }
+}
+
+#[test]
+fn corrupt_root_fails_to_open() {
+    let dir = common::temp_dir();
+    small_tree(&dir);
+    corrupt(&dir.join("root"));
+
+    let path = mismatched_path(BTree::<u64, u64>::open(dir.clone()).map(|_| ()));
+    assert_eq!(path, dir.join("root"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn corrupt_node_is_reported_on_load() {
+    let dir = common::temp_dir();
+    small_tree(&dir);
+    let leaf = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.extension().is_none()
+                && !path.ends_with("root")
+                && !path.ends_with("meta")
+                && !path.ends_with("LOCK")
+        })
+        .unwrap();
+    corrupt(&leaf);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    let path = mismatched_path(tree.keys().collect::<Result<Vec<_>, _>>());
+    assert_eq!(path, leaf);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn corrupt_values_are_reported_on_load() {
+    let dir = common::temp_dir();
+    small_tree(&dir);
+    let values = dir.join("root.values");
+    corrupt(&values);
+
+    let path = mismatched_path(BTree::<u64, u64>::open(dir.clone()).map(|_| ()));
+    assert_eq!(path, values);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn clear_empties_the_tree() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..500u64 {
+        tree.insert(key, key).unwrap();
+    }
+    // Leave some changes unflushed, including deleted nodes, for `clear` to throw away.
+    tree.flush().unwrap();
+    for key in 0..100 {
+        tree.remove(&key).unwrap();
+    }
+
+    tree.clear().unwrap();
+    assert_eq!(tree.len(), 0);
+    assert_eq!(tree.iter().count(), 0);
+    assert_eq!(tree.stats().unwrap().node_count, 1);
+    tree.validate().unwrap();
+
+    for key in 0..100 {
+        tree.insert(key, key * 2).unwrap();
+    }
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 100);
+    tree.validate().unwrap();
+    for key in 0..100 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 2));
+    }
+    assert_eq!(tree.get(&300).unwrap(), None);
+    assert_eq!(tree.gc().unwrap(), 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
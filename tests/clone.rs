@@ -0,0 +1,49 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn clone_is_independent() {
+    let dir = common::temp_dir();
+    let clone_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..500 {
+        let key = (i * 7919) % 500;
+        tree.insert(key, key * 2).unwrap();
+    }
+    // Leave some deleted nodes behind, which the clone shouldn't copy.
+    for key in 0..100 {
+        tree.remove(&key).unwrap();
+    }
+
+    let mut clone = tree.clone_to(clone_dir.clone()).unwrap();
+    clone.validate().unwrap();
+    assert_eq!(clone.len(), 400);
+    for key in 100..300 {
+        clone.remove(&key).unwrap();
+    }
+    clone.insert(1000, 1).unwrap();
+    clone.validate().unwrap();
+    clone.close().unwrap();
+
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 400);
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        entries,
+        (100..500).map(|key| (key, key * 2)).collect::<Vec<_>>()
+    );
+    drop(tree);
+
+    // The clone lives entirely in its own directory.
+    fs::remove_dir_all(&dir).unwrap();
+    let mut clone = BTree::<u64, u64>::open(clone_dir.clone()).unwrap();
+    clone.validate().unwrap();
+    let keys: Vec<u64> = clone.keys().collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, (300..500).chain([1000]).collect::<Vec<_>>());
+    drop(clone);
+
+    fs::remove_dir_all(clone_dir).unwrap();
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, CodecKind, Error};
+
+fn build(dir: &Path, codec: CodecKind) {
+    let mut tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(5)
+        .codec(codec)
+        .build()
+        .unwrap();
+    for key in 0..500u64 {
+        tree.insert(key, key.to_string()).unwrap();
+    }
+    tree.close().unwrap();
+}
+
+#[test]
+fn bincode_tree_round_trips() {
+    let dir = common::temp_dir();
+    build(&dir, CodecKind::Bincode);
+
+    // The meta file records the codec, so a plain `open` reads the nodes back.
+    let mut tree: BTree<u64, String> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 500);
+    for key in 0..500 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key.to_string()));
+    }
+    tree.validate().unwrap();
+    drop(tree);
+
+    let mut tree: BTree<u64, String> = BTreeBuilder::new(dir.clone())
+        .codec(CodecKind::Bincode)
+        .build()
+        .unwrap();
+    assert_eq!(tree.keys().count(), 500);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn open_with_mismatched_codec() {
+    for (written, asked) in [
+        (CodecKind::Bincode, CodecKind::Msgpack),
+        (CodecKind::Msgpack, CodecKind::Bincode),
+    ] {
+        let dir = common::temp_dir();
+        build(&dir, written);
+
+        let result = BTreeBuilder::<u64, String>::new(dir.clone())
+            .codec(asked)
+            .build();
+        assert!(matches!(
+            result,
+            Err(Error::CodecMismatch { found, expected }) if found == written && expected == asked
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
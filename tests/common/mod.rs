@@ -0,0 +1,9 @@
+use std::env;
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+/// A fresh path under the system temp directory. The directory itself is not created.
+pub fn temp_dir() -> PathBuf {
+    env::temp_dir().join(format!("btree-test-{}", Uuid::new_v4()))
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder};
+
+fn file_count(dir: &Path) -> usize {
+    fs::read_dir(dir).unwrap().count()
+}
+
+#[test]
+fn compact_fills_nodes_back_up() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 9).unwrap();
+    for key in 0..2000 {
+        tree.insert(key, key * 2).unwrap();
+    }
+    for key in (1..2000).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    let before = tree.stats().unwrap();
+    tree.flush().unwrap();
+    let files = file_count(&dir);
+
+    tree.compact().unwrap();
+    let after = tree.stats().unwrap();
+    assert!(after.average_fill > before.average_fill);
+    assert!(after.node_count < before.node_count);
+    // The old nodes' files are gone.
+    assert!(file_count(&dir) < files);
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 1000);
+    let expected: Vec<_> = (0..2000).step_by(2).map(|key| (key, key * 2)).collect();
+    let entries: Vec<_> = tree.iter().map(Result::unwrap).collect();
+    assert_eq!(entries, expected);
+
+    // The compacted tree is the one that is reopened, and it takes changes as any other would.
+    tree.close().unwrap();
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    let entries: Vec<_> = tree.iter().map(Result::unwrap).collect();
+    assert_eq!(entries, expected);
+    tree.insert(1, 1).unwrap();
+    tree.remove(&0).unwrap();
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compact_with_copy_on_write_keeps_readers() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+        .capacity(5)
+        .copy_on_write(true)
+        .build()
+        .unwrap();
+    for key in 0..300 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in (0..300).filter(|key| key % 3 != 0) {
+        tree.remove(&key).unwrap();
+    }
+    let reader = tree.reader().unwrap();
+
+    tree.compact().unwrap();
+    tree.validate().unwrap();
+    let old: Vec<_> = reader.iter().map(Result::unwrap).collect();
+    let new: Vec<_> = tree.iter().map(Result::unwrap).collect();
+    assert_eq!(old, new);
+    assert_eq!(new.len(), 100);
+    drop(reader);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compact_empty_tree() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    tree.compact().unwrap();
+    assert!(tree.is_empty());
+    tree.insert(1, 1).unwrap();
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::cmp::Ordering;
+use std::fs;
+
+use btree::BTree;
+
+fn case_insensitive() -> impl Fn(&String, &String) -> Ordering {
+    |a, b| a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+#[test]
+fn case_insensitive_keys() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new_by(dir.clone(), 3, case_insensitive()).unwrap();
+    let words = [
+        "banana",
+        "Apple",
+        "cherry",
+        "Date",
+        "elderberry",
+        "Fig",
+        "grape",
+    ];
+    for (idx, word) in words.iter().enumerate() {
+        tree.insert(word.to_string(), idx).unwrap();
+    }
+
+    // A key that differs only in case is the same key, so its value is replaced and the key
+    // first inserted is kept.
+    assert_eq!(tree.insert(String::from("APPLE"), 10).unwrap(), Some(1));
+    assert_eq!(tree.len(), 7);
+    assert_eq!(tree.get(&String::from("apple")).unwrap(), Some(10));
+    assert_eq!(tree.get(&String::from("FIG")).unwrap(), Some(5));
+    assert!(tree.contains_key(&String::from("GRAPE")).unwrap());
+    tree.validate().unwrap();
+
+    let keys: Vec<String> = tree.keys().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        keys,
+        [
+            "Apple",
+            "banana",
+            "cherry",
+            "Date",
+            "elderberry",
+            "Fig",
+            "grape"
+        ]
+    );
+    let range: Vec<String> = tree
+        .range(String::from("B")..String::from("e"))
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(range, ["banana", "cherry", "Date"]);
+    tree.close().unwrap();
+
+    // Reopened with the same comparator, the tree finds its keys as before.
+    let mut tree = BTree::<String, usize, _>::open_by(dir.clone(), case_insensitive()).unwrap();
+    assert_eq!(tree.remove(&String::from("BANANA")).unwrap(), Some(0));
+    assert_eq!(tree.get(&String::from("date")).unwrap(), Some(3));
+    assert_eq!(tree.len(), 6);
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_entry_returns_key_as_stored() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new_by(dir.clone(), 3, case_insensitive()).unwrap();
+    tree.insert(String::from("Apple"), 1).unwrap();
+    tree.insert(String::from("banana"), 2).unwrap();
+
+    assert_eq!(
+        tree.remove_entry(&String::from("APPLE")).unwrap(),
+        Some((String::from("Apple"), 1))
+    );
+    assert_eq!(tree.len(), 1);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "compression")]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder};
+
+fn value(key: u64) -> String {
+    format!("{}{}", "compressible ".repeat(100), key)
+}
+
+/// Build a closed tree of highly compressible values in `dir` and return the total size of its
+/// files.
+fn build(dir: &Path, compression: bool) -> u64 {
+    let mut tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(5)
+        .compression(compression)
+        .build()
+        .unwrap();
+    for key in 0..200u64 {
+        tree.insert(key, value(key)).unwrap();
+    }
+    tree.close().unwrap();
+
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum()
+}
+
+#[test]
+fn compressed_nodes_are_smaller() {
+    let plain_dir = common::temp_dir();
+    let compressed_dir = common::temp_dir();
+    let plain = build(&plain_dir, false);
+    let compressed = build(&compressed_dir, true);
+    assert!(compressed * 10 < plain);
+
+    fs::remove_dir_all(plain_dir).unwrap();
+    fs::remove_dir_all(compressed_dir).unwrap();
+}
+
+#[test]
+fn compressed_tree_round_trips() {
+    let dir = common::temp_dir();
+    build(&dir, true);
+
+    // The meta file records that the nodes are compressed, so a plain `open` reads them back.
+    let mut tree: BTree<u64, String> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 200);
+    for key in 0..200 {
+        assert_eq!(tree.get(&key).unwrap(), Some(value(key)));
+    }
+    tree.remove(&0).unwrap();
+    tree.validate().unwrap();
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, String> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.get(&0).unwrap(), None);
+    assert_eq!(tree.keys().count(), 199);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
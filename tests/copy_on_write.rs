@@ -0,0 +1,129 @@
+mod common;
+
+use std::fs;
+use std::thread;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+const KEYS: u64 = 200;
+
+fn cow_tree(dir: &std::path::Path) -> BTree<u64, u64> {
+    let mut tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(3)
+        .cache_capacity(8)
+        .copy_on_write(true)
+        .build()
+        .unwrap();
+    for key in 0..KEYS {
+        tree.insert(key, key * 10).unwrap();
+    }
+
+    tree
+}
+
+#[test]
+fn reader_sees_version_from_before_writes() {
+    let dir = common::temp_dir();
+    let mut tree = cow_tree(&dir);
+    let reader = tree.reader().unwrap();
+    let mut iter = reader.iter();
+    let mut seen: Vec<(u64, u64)> = iter.by_ref().take(50).map(Result::unwrap).collect();
+
+    thread::scope(|scope| {
+        let tree = &mut tree;
+        scope.spawn(move || {
+            for key in (0..KEYS).step_by(2) {
+                tree.remove(&key).unwrap();
+            }
+            for key in KEYS..2 * KEYS {
+                tree.insert(key, key).unwrap();
+            }
+            for key in (1..KEYS).step_by(2) {
+                *tree.get_mut(&key).unwrap().unwrap() += 1;
+            }
+            tree.clear().unwrap();
+            tree.insert(1, 1).unwrap();
+        });
+        seen.extend(iter.map(Result::unwrap));
+    });
+
+    let expected: Vec<_> = (0..KEYS).map(|key| (key, key * 10)).collect();
+    assert_eq!(seen, expected);
+    assert_eq!(reader.len(), KEYS as usize);
+    assert_eq!(reader.get(&2).unwrap(), Some(20));
+    assert_eq!(reader.get(&KEYS).unwrap(), None);
+    assert_eq!(tree.len(), 1);
+    assert_eq!(tree.get(&2).unwrap(), None);
+    tree.validate().unwrap();
+    drop(reader);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn writes_are_seen_after_reopening() {
+    let dir = common::temp_dir();
+    let mut tree = cow_tree(&dir);
+    for key in (0..KEYS).step_by(3) {
+        tree.remove(&key).unwrap();
+    }
+    *tree.get_mut(&1).unwrap().unwrap() = 7;
+    match tree.entry(KEYS).unwrap() {
+        btree::Entry::Vacant(entry) => *entry.insert(0).unwrap() += 5,
+        btree::Entry::Occupied(_) => panic!("{KEYS} shouldn't be in the tree"),
+    }
+    let len = tree.len();
+    tree.extend((KEYS + 1..2 * KEYS).map(|key| (key, key)))
+        .unwrap();
+    tree.close().unwrap();
+
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), len + KEYS as usize - 1);
+    assert_eq!(tree.get(&1).unwrap(), Some(7));
+    assert_eq!(tree.get(&3).unwrap(), None);
+    assert_eq!(tree.get(&KEYS).unwrap(), Some(5));
+    assert_eq!(tree.get(&(2 * KEYS - 1)).unwrap(), Some(2 * KEYS - 1));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn gc_keeps_files_readers_need() {
+    let dir = common::temp_dir();
+    let mut tree = cow_tree(&dir);
+    let reader = tree.reader().unwrap();
+    for key in 0..KEYS {
+        tree.insert(key, key).unwrap();
+    }
+
+    // Only the reader keeps the old version now.
+    tree.release_versions_before(tree.current_version())
+        .unwrap();
+    tree.gc().unwrap();
+    let old: Vec<_> = reader.iter().map(Result::unwrap).collect();
+    assert_eq!(old.len(), KEYS as usize);
+    assert!(old.iter().all(|(key, value)| *value == key * 10));
+
+    drop(reader);
+    assert!(tree.gc().unwrap() > 0);
+    tree.validate().unwrap();
+    for key in 0..KEYS {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reader_needs_copy_on_write() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 3).unwrap();
+    assert!(matches!(tree.reader(), Err(Error::NotCopyOnWrite)));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
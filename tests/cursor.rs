@@ -0,0 +1,89 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+/// Even keys from 0 to 998, in nodes small enough that stepping a few keys crosses a node.
+fn even_tree() -> (std::path::PathBuf, BTree<u64, u64>) {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..500 {
+        let key = (i * 7919) % 500 * 2;
+        tree.insert(key, key * 3).unwrap();
+    }
+
+    (dir, tree)
+}
+
+#[test]
+fn seek_and_step_both_ways() {
+    let (dir, mut tree) = even_tree();
+    let expected: Vec<(u64, u64)> = (0..500).map(|i| (i * 2, i * 6)).collect();
+
+    let mut cursor = tree.cursor().unwrap();
+    cursor.seek(&501).unwrap();
+    assert_eq!(cursor.current().unwrap(), Some((502, 1506)));
+    cursor.seek(&500).unwrap();
+    assert_eq!(cursor.current().unwrap(), Some((500, 1500)));
+
+    let mut pos = 250;
+    for _ in 0..40 {
+        pos += 1;
+        assert_eq!(cursor.next().unwrap(), Some(expected[pos]));
+    }
+    for _ in 0..80 {
+        pos -= 1;
+        assert_eq!(cursor.prev().unwrap(), Some(expected[pos]));
+    }
+    drop(cursor);
+
+    // The cursor saw the same values a plain lookup does.
+    for (key, value) in &expected[pos..pos + 80] {
+        assert_eq!(tree.get(key).unwrap(), Some(*value));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn step_off_either_end() {
+    let (dir, mut tree) = even_tree();
+    let forward: Vec<(u64, u64)> = tree.iter().map(Result::unwrap).collect();
+
+    let mut cursor = tree.cursor().unwrap();
+    let mut entries = vec![cursor.current().unwrap().unwrap()];
+    while let Some(entry) = cursor.next().unwrap() {
+        entries.push(entry);
+    }
+    assert_eq!(entries, forward);
+    assert_eq!(cursor.current().unwrap(), None);
+    assert_eq!(cursor.prev().unwrap(), None);
+
+    cursor.seek(&998).unwrap();
+    let mut entries = vec![cursor.current().unwrap().unwrap()];
+    while let Some(entry) = cursor.prev().unwrap() {
+        entries.push(entry);
+    }
+    entries.reverse();
+    assert_eq!(entries, forward);
+
+    cursor.seek(&999).unwrap();
+    assert_eq!(cursor.current().unwrap(), None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cursor_on_empty_tree() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+
+    let mut cursor = tree.cursor().unwrap();
+    assert_eq!(cursor.current().unwrap(), None);
+    cursor.seek(&0).unwrap();
+    assert_eq!(cursor.current().unwrap(), None);
+    assert_eq!(cursor.next().unwrap(), None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn debug_summarizes_the_tree() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    // Six keys split the root once, leaving one key in it over two leaves.
+    for key in 0..6u64 {
+        tree.insert(key, vec![0u8; 1000]).unwrap();
+    }
+
+    let debug = format!("{tree:?}");
+    assert!(debug.starts_with("BTree {"));
+    assert!(debug.contains(&format!("backing_dir: {:?}", dir)));
+    assert!(debug.contains("capacity: 5,"));
+    assert!(debug.contains("len: 6,"));
+    assert!(debug.contains("cached: 2,"));
+    assert!(debug.contains("cache_capacity: 256,"));
+    assert!(debug.contains("NodeData { keys: 1, leaf: false, children: 2, .. }"));
+    // Neither the keys nor the values are printed.
+    assert!(debug.len() < 500, "{debug}");
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
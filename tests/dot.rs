@@ -0,0 +1,32 @@
+use btree::BTree;
+
+#[test]
+fn dot_has_a_graph_node_per_tree_node() {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    for i in 0..200u64 {
+        tree.insert((i * 7919) % 200, i).unwrap();
+    }
+
+    let dot = tree.to_dot().unwrap();
+    assert!(dot.starts_with("digraph btree {"));
+    assert!(dot.trim_end().ends_with('}'));
+    let stats = tree.stats().unwrap();
+    let nodes = dot
+        .lines()
+        .filter(|line| line.contains("[label=") && !line.contains("->"))
+        .count();
+    let edges = dot.lines().filter(|line| line.contains("->")).count();
+    assert_eq!(nodes, stats.node_count);
+    // Every node but the root hangs off exactly one edge.
+    assert_eq!(edges, stats.node_count - 1);
+    assert!(dot.contains("\"root\" [label="));
+}
+
+#[test]
+fn dot_escapes_record_labels() {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    tree.insert(String::from("a|b"), 0).unwrap();
+
+    let dot = tree.to_dot().unwrap();
+    assert!(dot.contains(r#""root" [label="\"a\|b\""];"#), "{}", dot);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use btree::{BTree, BTreeBuilder, Durability, FileStorage, Storage};
+
+/// Files on disk, counting how many times they are written and synced.
+#[derive(Default)]
+struct Counting {
+    writes: AtomicUsize,
+    syncs: AtomicUsize,
+    dir_syncs: AtomicUsize,
+}
+
+impl Counting {
+    fn writes(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+
+    fn syncs(&self) -> usize {
+        self.syncs.load(Ordering::SeqCst)
+    }
+
+    fn dir_syncs(&self) -> usize {
+        self.dir_syncs.load(Ordering::SeqCst)
+    }
+}
+
+impl Storage for Counting {
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        FileStorage.create_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        FileStorage.read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        FileStorage.write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        FileStorage.rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        FileStorage.remove(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        FileStorage.list(path)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            self.dir_syncs.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.syncs.fetch_add(1, Ordering::SeqCst);
+        }
+        FileStorage.sync(path)
+    }
+
+    fn lock(&self, path: &Path, shared: bool) -> io::Result<Option<File>> {
+        FileStorage.lock(path, shared)
+    }
+}
+
+/// A tree in `dir` with a cache small enough that inserting evicts, and so writes, nodes.
+fn tree(dir: &Path, durability: Durability) -> (Arc<Counting>, BTree<u64, u64>) {
+    let storage = Arc::new(Counting::default());
+    let tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(5)
+        .cache_capacity(2)
+        .durability(durability)
+        .storage(storage.clone())
+        .build()
+        .unwrap();
+
+    (storage, tree)
+}
+
+#[test]
+fn no_durability_never_syncs() {
+    let dir = common::temp_dir();
+    let (storage, mut tree) = tree(&dir, Durability::None);
+    for key in 0..200 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+
+    assert!(storage.writes() > 0);
+    assert_eq!(storage.syncs(), 0);
+    assert_eq!(storage.dir_syncs(), 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fsync_syncs_every_write() {
+    let dir = common::temp_dir();
+    let (storage, mut tree) = tree(&dir, Durability::Fsync);
+    for key in 0..200 {
+        tree.insert(key, key).unwrap();
+    }
+    assert!(storage.syncs() > 0);
+    tree.close().unwrap();
+
+    assert_eq!(storage.syncs(), storage.writes());
+    // Files are moved into place once written, which is only durable once the directory is synced.
+    assert!(storage.dir_syncs() > 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fsync_barrier_syncs_only_on_flush() {
+    let dir = common::temp_dir();
+    let (storage, mut tree) = tree(&dir, Durability::FsyncBarrier);
+    // Building the tree flushes it once already.
+    let (writes, syncs, dir_syncs) = (storage.writes(), storage.syncs(), storage.dir_syncs());
+    for key in 0..200 {
+        tree.insert(key, key).unwrap();
+    }
+    assert!(storage.writes() > writes);
+    assert_eq!(storage.syncs(), syncs);
+    assert_eq!(storage.dir_syncs(), dir_syncs);
+
+    tree.flush().unwrap();
+    let synced = storage.syncs() - syncs;
+    assert!(synced > 0);
+    // Each file written more than once is only synced once.
+    assert!(synced < storage.writes() - writes);
+
+    // Every file is in the one directory, which is synced once after them.
+    assert_eq!(storage.dir_syncs(), dir_syncs + 1);
+
+    // Flushing again with nothing changed only rewrites the meta file, so only it is synced.
+    tree.flush().unwrap();
+    assert_eq!(storage.syncs(), syncs + synced + 1);
+    assert_eq!(storage.dir_syncs(), dir_syncs + 2);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
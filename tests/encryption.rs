@@ -0,0 +1,174 @@
+#![cfg(feature = "encryption")]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, CodecKind, Error, NodeError};
+
+const KEY: [u8; 32] = [7; 32];
+const SECRET: &str = "a secret worth keeping";
+
+fn open(dir: &Path, key: [u8; 32]) -> Result<BTree<u64, String>, Error> {
+    BTreeBuilder::new(dir.to_path_buf())
+        .capacity(4)
+        .encryption_key(key)
+        .build()
+}
+
+#[test]
+fn encrypted_tree_needs_its_key() {
+    let dir = common::temp_dir();
+    let mut tree = open(&dir, KEY).unwrap();
+    for key in 0..200 {
+        tree.insert(key, format!("{SECRET} {key}")).unwrap();
+    }
+    tree.close().unwrap();
+
+    // None of the files holds any of the values as written.
+    for entry in fs::read_dir(&dir).unwrap() {
+        let data = fs::read(entry.unwrap().path()).unwrap();
+        assert!(!data.windows(SECRET.len()).any(|w| w == SECRET.as_bytes()));
+    }
+
+    let mut tree = open(&dir, KEY).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 200);
+    for key in 0..200 {
+        assert_eq!(tree.get(&key).unwrap(), Some(format!("{SECRET} {key}")));
+    }
+    drop(tree);
+
+    let mut wrong = KEY;
+    wrong[0] ^= 1;
+    assert!(matches!(
+        open(&dir, wrong),
+        Err(Error::Node {
+            source: NodeError::Authentication,
+            ..
+        })
+    ));
+    assert!(matches!(
+        BTree::<u64, String>::open(dir.clone()),
+        Err(Error::KeyRequired)
+    ));
+    assert!(matches!(
+        BTree::<u64, String>::repair(dir.clone()),
+        Err(Error::KeyRequired)
+    ));
+    // Nothing that failed changed the tree.
+    let mut tree = open(&dir, KEY).unwrap();
+    assert_eq!(tree.get(&0).unwrap(), Some(format!("{SECRET} 0")));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn encrypted_tree_with_write_ahead_log() {
+    let dir = common::temp_dir();
+    let mut tree = BTreeBuilder::<u64, String>::new(dir.clone())
+        .capacity(4)
+        .write_ahead_log(true)
+        .encryption_key(KEY)
+        .build()
+        .unwrap();
+    for key in 0..50 {
+        tree.insert(key, SECRET.to_string()).unwrap();
+    }
+    for key in (0..50).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    drop(tree);
+
+    let mut tree = open(&dir, KEY).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 25);
+    assert_eq!(tree.get(&1).unwrap().as_deref(), Some(SECRET));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn key_for_a_plain_tree() {
+    let dir = common::temp_dir();
+    let tree: BTree<u64, String> = BTree::new(dir.clone(), 4).unwrap();
+    drop(tree);
+    assert!(matches!(open(&dir, KEY), Err(Error::NotEncrypted)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn encrypted_bincode_tree_reopens() {
+    let dir = common::temp_dir();
+    let mut tree = BTreeBuilder::new(dir.clone())
+        .codec(CodecKind::Bincode)
+        .encryption_key(KEY)
+        .build()
+        .unwrap();
+    tree.insert(1u64, String::from(SECRET)).unwrap();
+    tree.close().unwrap();
+
+    // The key is checked against the meta file, which is read the same way whatever the codec.
+    let mut tree = open(&dir, KEY).unwrap();
+    assert_eq!(tree.get(&1).unwrap(), Some(String::from(SECRET)));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn files_cant_stand_in_for_one_another() {
+    let dir = common::temp_dir();
+    let mut tree = open(&dir, KEY).unwrap();
+    for key in 0..200 {
+        tree.insert(key, format!("{SECRET} {key}")).unwrap();
+    }
+    tree.close().unwrap();
+
+    // Another node's values, sealed under the same key, in place of the root's.
+    let root_values = dir.join("root.values");
+    let other = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "values") && *path != root_values)
+        .unwrap();
+    fs::copy(other, &root_values).unwrap();
+    assert!(matches!(
+        open(&dir, KEY),
+        Err(Error::Node {
+            path,
+            source: NodeError::Authentication,
+        }) if path == root_values
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn meta_file_cant_be_changed() {
+    let dir = common::temp_dir();
+    let mut tree = open(&dir, KEY).unwrap();
+    tree.insert(1, String::from(SECRET)).unwrap();
+    tree.close().unwrap();
+
+    // The capacity, which follows the header of the array the meta file is and the format
+    // version.
+    let meta = dir.join("meta");
+    let mut buf = fs::read(&meta).unwrap();
+    assert_eq!(buf[2], 4);
+    buf[2] = 5;
+    fs::write(&meta, buf).unwrap();
+    assert!(matches!(
+        open(&dir, KEY),
+        Err(Error::Node {
+            path,
+            source: NodeError::Authentication,
+        }) if path == meta
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
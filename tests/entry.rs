@@ -0,0 +1,197 @@
+mod common;
+
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use btree::{BTree, Entry};
+
+#[test]
+fn or_insert_with_only_calls_default_when_vacant() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in (0..100).step_by(2) {
+        tree.insert(key, key).unwrap();
+    }
+
+    let mut calls = 0;
+    for key in 0..100 {
+        let value = tree
+            .entry(key)
+            .unwrap()
+            .or_insert_with(|| {
+                calls += 1;
+                key + 1000
+            })
+            .unwrap();
+        assert_eq!(*value, if key % 2 == 0 { key } else { key + 1000 });
+    }
+    assert_eq!(calls, 50);
+    assert_eq!(tree.len(), 100);
+    for key in 0..100 {
+        let expected = if key % 2 == 0 { key } else { key + 1000 };
+        assert_eq!(tree.get(&key).unwrap(), Some(expected));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn and_modify_persists() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+
+    for key in 0..110 {
+        tree.entry(key)
+            .unwrap()
+            .and_modify(|value| *value *= 2)
+            .unwrap()
+            .or_insert(0)
+            .unwrap();
+    }
+    assert!(matches!(tree.entry(200).unwrap(), Entry::Vacant(_)));
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 110);
+    for key in 0..110 {
+        let expected = if key < 100 { key * 2 } else { 0 };
+        assert_eq!(tree.get(&key).unwrap(), Some(expected));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn insert_through_entries_only() {
+    // Inserting in ascending order keeps filling the rightmost leaf, so entries regularly land
+    // in full leaves and have to split their way in from the root.
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..300 {
+        match tree.entry(key).unwrap() {
+            Entry::Vacant(entry) => *entry.insert(key).unwrap() += 1,
+            Entry::Occupied(_) => panic!("{key} was already present"),
+        }
+    }
+
+    assert_eq!(tree.len(), 300);
+    let entries: Vec<(u64, u64)> = tree.iter().map(Result::unwrap).collect();
+    assert_eq!(
+        entries,
+        (0..300).map(|key| (key, key + 1)).collect::<Vec<_>>()
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn get_or_insert_with_calls_f_once_per_missing_key() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, String> = BTree::new(dir.clone(), 4).unwrap();
+    for key in (0..200).step_by(4) {
+        tree.insert(key, format!("old {key}")).unwrap();
+    }
+
+    let calls = AtomicUsize::new(0);
+    for _ in 0..2 {
+        for key in 0..200 {
+            let value = tree
+                .get_or_insert_with(key, || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    format!("new {key}")
+                })
+                .unwrap();
+            let expected = if key % 4 == 0 { "old" } else { "new" };
+            assert_eq!(*value, format!("{expected} {key}"));
+        }
+    }
+    // Only the first pass finds keys missing.
+    assert_eq!(calls.load(Ordering::SeqCst), 150);
+    assert_eq!(tree.len(), 200);
+    tree.validate().unwrap();
+    tree.close().unwrap();
+
+    let mut tree = BTree::<u64, String>::open(dir.clone()).unwrap();
+    assert_eq!(tree.get(&1).unwrap(), Some(String::from("new 1")));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn first_entry_as_a_min_heap() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    assert!(tree.first_entry().unwrap().is_none());
+    assert!(tree.last_entry().unwrap().is_none());
+    for i in 0..200u64 {
+        let key = (i * 7919) % 200;
+        tree.insert(key, key * 10).unwrap();
+    }
+
+    let mut popped = Vec::new();
+    while let Some(mut entry) = tree.first_entry().unwrap() {
+        let key = *entry.key();
+        assert_eq!(*entry.get().unwrap(), key * 10);
+        popped.push((key, entry.remove().unwrap()));
+    }
+    let expected: Vec<_> = (0..200).map(|key| (key, key * 10)).collect();
+    assert_eq!(popped, expected);
+    assert!(tree.is_empty());
+    tree.validate().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn last_entry_changes_in_place() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+
+    let mut entry = tree.last_entry().unwrap().unwrap();
+    assert_eq!(*entry.key(), 99);
+    *entry.get_mut().unwrap() += 1000;
+    assert_eq!(
+        tree.last_entry().unwrap().unwrap().remove_entry().unwrap(),
+        (99, 1099)
+    );
+    *tree.last_entry().unwrap().unwrap().into_mut().unwrap() = 0;
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 99);
+    assert_eq!(tree.last_key_value().unwrap(), Some((98, 0)));
+    assert_eq!(tree.first_key_value().unwrap(), Some((0, 0)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn or_insert_after_reopening() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in (0..100).step_by(2) {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+
+    // None of the leaves has its values loaded yet, and the one the entry goes into needs them.
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(*tree.entry(51).unwrap().or_insert(7).unwrap(), 7);
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 51);
+    assert_eq!(tree.get(&50).unwrap(), Some(50));
+    assert_eq!(tree.get(&51).unwrap(), Some(7));
+    assert_eq!(tree.get(&52).unwrap(), Some(52));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use btree::{BTree, Error, NodeError};
+
+fn small_tree(dir: &Path) {
+    let mut tree = BTree::new(dir.to_path_buf(), 3).unwrap();
+    for key in 0..50u64 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+}
+
+#[test]
+fn missing_node_file_is_io_error_with_path() {
+    let dir = common::temp_dir();
+    small_tree(&dir);
+    let root = dir.join("root");
+    fs::remove_file(&root).unwrap();
+
+    match BTree::<u64, u64>::open(dir.clone()) {
+        Err(Error::Node {
+            path,
+            source: NodeError::Io(e),
+        }) => {
+            assert_eq!(path, root);
+            assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        }
+        result => panic!("expected a missing node file, got {:?}", result.map(|_| ())),
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn undecodable_node_file_is_deserialization_error_with_path() {
+    let dir = common::temp_dir();
+    small_tree(&dir);
+    // Garbage under a checksum that matches it, so only decoding can catch it.
+    let garbage = [0xffu8; 7];
+    let mut file = crc32fast::hash(&garbage).to_le_bytes().to_vec();
+    file.extend_from_slice(&garbage);
+    let root = dir.join("root");
+    fs::write(&root, file).unwrap();
+
+    match BTree::<u64, u64>::open(dir.clone()) {
+        Err(Error::Node {
+            path,
+            source: NodeError::Deserialization(_),
+        }) => assert_eq!(path, root),
+        result => panic!("expected an undecodable node, got {:?}", result.map(|_| ())),
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn node_error_names_the_file() {
+    let dir = common::temp_dir();
+    small_tree(&dir);
+    fs::remove_file(dir.join("root")).unwrap();
+
+    let message = BTree::<u64, u64>::open(dir.clone())
+        .err()
+        .unwrap()
+        .to_string();
+    let root = dir.join("root");
+    assert!(message.contains(&root.display().to_string()));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, Error};
+
+#[test]
+fn export_and_import_round_trip() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, String> = BTree::new(dir.clone(), 4).unwrap();
+    for key in (0..500).rev() {
+        tree.insert(key, format!("value {key}")).unwrap();
+    }
+    for key in (0..500).step_by(7) {
+        tree.remove(&key).unwrap();
+    }
+    let mut buf = Vec::new();
+    tree.export(&mut buf).unwrap();
+
+    let new_dir = common::temp_dir();
+    let mut imported = BTree::<u64, String>::import(new_dir.clone(), buf.as_slice()).unwrap();
+    imported.validate().unwrap();
+    assert_eq!(imported.len(), tree.len());
+    let expected: Vec<_> = tree.iter().map(Result::unwrap).collect();
+    let found: Vec<_> = imported.iter().map(Result::unwrap).collect();
+    assert_eq!(found, expected);
+    imported.close().unwrap();
+
+    // The imported tree carries on like any other once reopened.
+    let mut imported = BTree::<u64, String>::open(new_dir.clone()).unwrap();
+    imported.insert(1000, String::from("new")).unwrap();
+    imported.validate().unwrap();
+    drop(imported);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(new_dir).unwrap();
+}
+
+#[test]
+fn import_of_truncated_export_fails() {
+    let mut tree: BTree<u64, u64> = BTree::new_in_memory(4).unwrap();
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+    let mut buf = Vec::new();
+    tree.export(&mut buf).unwrap();
+    buf.truncate(buf.len() - 3);
+
+    let dir = common::temp_dir();
+    let result = BTree::<u64, u64>::import(dir.clone(), buf.as_slice());
+    assert!(matches!(result, Err(Error::Io(_))));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn extend_from_vec() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    tree.insert(500, String::from("old")).unwrap();
+    let entries: Vec<(u64, String)> = (0..1000).map(|key| (key, key.to_string())).collect();
+    tree.extend(entries).unwrap();
+
+    assert_eq!(tree.len(), 1000);
+    for key in 0..1000 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key.to_string()));
+    }
+    let keys: Vec<u64> = tree.keys().collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn extend_from_hash_map() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    let entries: HashMap<u64, u64> = (0..1000).map(|key| (key * 7919 % 1000, key)).collect();
+    tree.extend(entries.clone()).unwrap();
+
+    assert_eq!(tree.len(), 1000);
+    tree.validate().unwrap();
+    for (key, value) in entries {
+        assert_eq!(tree.get(&key).unwrap(), Some(value));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sorted_extend_skips_searches() {
+    let entries = || (0..1000u64).map(|key| (key, key));
+
+    let mut one_at_a_time = BTree::new_in_memory(5).unwrap();
+    for (key, value) in entries() {
+        one_at_a_time.insert(key, value).unwrap();
+    }
+    let mut extended = BTree::new_in_memory(5).unwrap();
+    extended.extend(entries()).unwrap();
+
+    // Every node access below the root goes through the cache, so fewer accesses mean fewer
+    // nodes visited.
+    let accesses = |tree: &BTree<u64, u64>| {
+        let stats = tree.cache_stats();
+        stats.hits + stats.misses
+    };
+    assert!(accesses(&extended) * 3 < accesses(&one_at_a_time) * 2);
+    assert_eq!(
+        extended.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        entries().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn extend_over_keys_in_internal_nodes() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    tree.insert(11u64, 0u64).unwrap();
+    tree.extend([10, 13, 16, 19, 22, 25].map(|key| (key, 1)))
+        .unwrap();
+    // Some of these replace separators, after which the keys that follow mustn't go into the
+    // internal node holding them.
+    tree.extend([16, 19, 22, 25, 28, 31].map(|key| (key, 2)))
+        .unwrap();
+    assert_eq!(tree.len(), 9);
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        entries,
+        [
+            (10, 1),
+            (11, 0),
+            (13, 1),
+            (16, 2),
+            (19, 2),
+            (22, 2),
+            (25, 2),
+            (28, 2),
+            (31, 2)
+        ]
+    );
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn try_from_iter() {
+    let mut tree = BTree::try_from_iter((0..100u64).rev().map(|key| (key, key * 2))).unwrap();
+    assert_eq!(tree.len(), 100);
+    for key in 0..100 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 2));
+    }
+}
+
+#[test]
+fn extend_after_reserve() {
+    const KEYS: u64 = 1000;
+    let entries = || (0..KEYS).map(|i| ((i * 7919) % KEYS, i));
+    let mut evictions = Vec::new();
+    let mut contents = Vec::new();
+    for reserve in [false, true] {
+        let dir = common::temp_dir();
+        let mut tree = BTree::new(dir.clone(), 5).unwrap();
+        tree.set_cache_capacity(16).unwrap();
+        if reserve {
+            tree.reserve(KEYS as usize).unwrap();
+        }
+        tree.extend(entries()).unwrap();
+        tree.validate().unwrap();
+        evictions.push(tree.cache_stats().evictions);
+        contents.push(tree.iter().collect::<Result<Vec<_>, _>>().unwrap());
+        drop(tree);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    assert_eq!(contents[0], contents[1]);
+    assert_eq!(contents[1].len(), KEYS as usize);
+    assert!(evictions[1] < evictions[0]);
+}
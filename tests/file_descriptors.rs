@@ -0,0 +1,40 @@
+// Open files are counted through `/proc`.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::fs;
+
+use btree::BTreeBuilder;
+
+/// The number of file descriptors the process has open. This is the only test in its binary,
+/// so nothing else opens files while it runs.
+fn open_files() -> usize {
+    fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+#[test]
+fn cached_nodes_hold_no_files_open() {
+    let dir = common::temp_dir();
+    let mut tree = BTreeBuilder::new(dir.clone())
+        .capacity(3)
+        .cache_capacity(100_000)
+        .build()
+        .unwrap();
+    // The tree's lock file is open already, and no other file stays open however many nodes
+    // the tree caches.
+    let before = open_files();
+    for key in 0..20_000u64 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in 0..20_000u64 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+    assert!(tree.stats().unwrap().node_count > 5000);
+    assert!(open_files() <= before);
+    tree.flush().unwrap();
+    assert!(open_files() <= before);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use btree::BTree;
+
+/// The names of the node files in `dir` once `tree` is flushed.
+fn node_names(tree: &mut BTree<u64, u64>, dir: &Path) -> HashSet<String> {
+    tree.flush().unwrap();
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_none() && !path.ends_with("meta") && !path.ends_with("LOCK")
+        })
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn freed_names_are_reused() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..300u64 {
+        tree.insert(key, key).unwrap();
+    }
+    let full = node_names(&mut tree, &dir);
+
+    for key in 0..200 {
+        tree.remove(&key).unwrap();
+    }
+    let shrunk = node_names(&mut tree, &dir);
+    let freed: HashSet<String> = full.difference(&shrunk).cloned().collect();
+    assert!(freed.len() >= 5, "only {} nodes were freed", freed.len());
+
+    // The free list outlives the tree.
+    tree.close().unwrap();
+    let mut tree = BTree::open(dir.clone()).unwrap();
+    for key in 0..200 {
+        tree.insert(key, key).unwrap();
+    }
+    let regrown = node_names(&mut tree, &dir);
+    let added: HashSet<String> = regrown.difference(&shrunk).cloned().collect();
+    // Fresh names are only made up once every freed one has been taken.
+    let reused = added.intersection(&freed).count();
+    assert!(reused > 0);
+    assert_eq!(reused, added.len().min(freed.len()));
+    tree.validate().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
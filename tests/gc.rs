@@ -0,0 +1,77 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::BTree;
+use uuid::Uuid;
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+
+    names
+}
+
+#[test]
+fn gc_removes_orphaned_files() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.flush().unwrap();
+    let before = file_names(&dir);
+
+    // A copy of the root under a node name of its own, which nothing refers to.
+    let orphan = dir.join(Uuid::new_v4().to_string());
+    fs::copy(dir.join("root"), &orphan).unwrap();
+    assert_eq!(tree.gc().unwrap(), 1);
+    assert!(!orphan.exists());
+    assert_eq!(file_names(&dir), before);
+
+    assert_eq!(tree.gc().unwrap(), 0);
+    tree.validate().unwrap();
+    for key in 0..100 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn gc_keeps_unflushed_nodes() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in 0..50 {
+        tree.remove(&key).unwrap();
+    }
+
+    assert_eq!(tree.gc().unwrap(), 0);
+    tree.close().unwrap();
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 50);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn gc_in_memory() {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in 0..100 {
+        tree.remove(&key).unwrap();
+    }
+
+    assert_eq!(tree.gc().unwrap(), 0);
+    assert_eq!(tree.stats().unwrap().node_count, 1);
+}
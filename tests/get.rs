@@ -0,0 +1,250 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, Error};
+
+/// Six keys into a tree of capacity five splits the root once, leaving `2` in the root and the
+/// rest in two leaves.
+fn small_tree() -> (std::path::PathBuf, BTree<u64, String>) {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..6 {
+        tree.insert(key, key.to_string()).unwrap();
+    }
+
+    (dir, tree)
+}
+
+#[test]
+fn get_key_in_root() {
+    let (dir, mut tree) = small_tree();
+    assert_eq!(tree.get(&2).unwrap(), Some(String::from("2")));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn get_key_in_leaf() {
+    let (dir, mut tree) = small_tree();
+    assert_eq!(tree.get(&0).unwrap(), Some(String::from("0")));
+    assert_eq!(tree.get(&5).unwrap(), Some(String::from("5")));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn get_absent_key() {
+    let (dir, mut tree) = small_tree();
+    assert_eq!(tree.get(&100).unwrap(), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn index_present_and_absent() {
+    let (dir, mut tree) = small_tree();
+    assert_eq!(tree.index(&4).unwrap(), "4");
+    assert!(matches!(tree.index(&100), Err(Error::KeyNotFound)));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn get_after_many_inserts() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in (0..2000).step_by(2) {
+        tree.insert(key, key + 1).unwrap();
+    }
+
+    for key in 0..2000 {
+        let expected = if key % 2 == 0 { Some(key + 1) } else { None };
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn contains_key_present_and_absent() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in (0..500).step_by(5) {
+        tree.insert(key, key).unwrap();
+    }
+
+    for key in 0..500 {
+        assert_eq!(tree.contains_key(&key).unwrap(), key % 5 == 0);
+    }
+    assert!(!tree.contains_key(&1000).unwrap());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn get_mut_persists_changes() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, Vec<u64>> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100 {
+        tree.insert(key, vec![key]).unwrap();
+    }
+
+    for key in (0..100).step_by(3) {
+        tree.get_mut(&key).unwrap().unwrap().push(key + 1);
+    }
+    // Reading through the guard without changing anything is fine too.
+    assert_eq!(*tree.get_mut(&1).unwrap().unwrap(), vec![1]);
+    assert!(tree.get_mut(&100).unwrap().is_none());
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, Vec<u64>> = BTree::open(dir.clone()).unwrap();
+    for key in 0..100 {
+        let expected = if key % 3 == 0 {
+            vec![key, key + 1]
+        } else {
+            vec![key]
+        };
+        assert_eq!(tree.get(&key).unwrap(), Some(expected));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn update_values_persists_changes() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..1000 {
+        tree.insert(key, key).unwrap();
+    }
+
+    let mut seen = Vec::new();
+    tree.update_values(|key, value| {
+        seen.push(*key);
+        *value *= 2;
+    })
+    .unwrap();
+    assert_eq!(seen, (0..1000).collect::<Vec<_>>());
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    tree.validate().unwrap();
+    for key in 0..1000 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 2));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn first_and_last_key_value() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    assert_eq!(tree.first_key_value().unwrap(), None);
+    assert_eq!(tree.last_key_value().unwrap(), None);
+
+    for i in 0..500 {
+        let key = (i * 7919) % 500;
+        tree.insert(key, key * 10).unwrap();
+    }
+    tree.close().unwrap();
+
+    // Each lookup only reads the nodes on one path from the root, which for 500 keys in nodes of
+    // at least two keys is no more than eight.
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.first_key_value().unwrap(), Some((0, 0)));
+    assert!(tree.cache_stats().misses <= 8);
+    tree.reset_cache_stats();
+    assert_eq!(tree.last_key_value().unwrap(), Some((499, 4990)));
+    assert!(tree.cache_stats().misses <= 8);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn floor_and_ceiling() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    assert_eq!(tree.floor(&10).unwrap(), None);
+    assert_eq!(tree.ceiling(&10).unwrap(), None);
+
+    // Multiples of ten from 10 to 1000.
+    for i in 1..=100 {
+        let key = ((i * 37) % 100 + 1) * 10;
+        tree.insert(key, key + 1).unwrap();
+    }
+
+    // Below every key.
+    assert_eq!(tree.floor(&5).unwrap(), None);
+    assert_eq!(tree.ceiling(&5).unwrap(), Some((10, 11)));
+    // Above every key.
+    assert_eq!(tree.floor(&1005).unwrap(), Some((1000, 1001)));
+    assert_eq!(tree.ceiling(&1005).unwrap(), None);
+    // Exactly matching, and in the gaps between keys, whichever nodes they fall between.
+    for key in (10..=1000).step_by(10) {
+        assert_eq!(tree.floor(&key).unwrap(), Some((key, key + 1)));
+        assert_eq!(tree.ceiling(&key).unwrap(), Some((key, key + 1)));
+    }
+    for key in (15..1000).step_by(10) {
+        assert_eq!(tree.floor(&key).unwrap(), Some((key - 5, key - 4)));
+        assert_eq!(tree.ceiling(&key).unwrap(), Some((key + 5, key + 6)));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn predecessor_and_successor() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 3).unwrap();
+    assert_eq!(tree.predecessor(&10).unwrap(), None);
+    assert_eq!(tree.successor(&10).unwrap(), None);
+
+    // Multiples of ten from 10 to 1000, in enough nodes that neighbours are often in different
+    // ones.
+    for i in 1..=100 {
+        let key = ((i * 37) % 100 + 1) * 10;
+        tree.insert(key, key + 1).unwrap();
+    }
+
+    // Keys in the tree have neighbours a step either side.
+    for key in (20..1000).step_by(10) {
+        assert_eq!(tree.predecessor(&key).unwrap(), Some((key - 10, key - 9)));
+        assert_eq!(tree.successor(&key).unwrap(), Some((key + 10, key + 11)));
+    }
+    // Keys missing from the tree have neighbours half a step either side.
+    for key in (15..1000).step_by(10) {
+        assert_eq!(tree.predecessor(&key).unwrap(), Some((key - 5, key - 4)));
+        assert_eq!(tree.successor(&key).unwrap(), Some((key + 5, key + 6)));
+    }
+    // The first and last keys, and keys beyond them.
+    assert_eq!(tree.predecessor(&10).unwrap(), None);
+    assert_eq!(tree.successor(&10).unwrap(), Some((20, 21)));
+    assert_eq!(tree.predecessor(&1000).unwrap(), Some((990, 991)));
+    assert_eq!(tree.successor(&1000).unwrap(), None);
+    assert_eq!(tree.predecessor(&0).unwrap(), None);
+    assert_eq!(tree.successor(&0).unwrap(), Some((10, 11)));
+    assert_eq!(tree.predecessor(&5000).unwrap(), Some((1000, 1001)));
+    assert_eq!(tree.successor(&5000).unwrap(), None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn get_many_keeps_the_order_asked_for() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in (0..200u64).step_by(2) {
+        tree.insert(key, key.to_string()).unwrap();
+    }
+
+    // Keys spread over many leaves, some missing, one asked for twice.
+    let keys = [150, 3, 0, 198, 64, 150, 1000, 2];
+    let refs: Vec<&u64> = keys.iter().collect();
+    let found = tree.get_many(&refs).unwrap();
+    let expected: Vec<Option<String>> = keys
+        .iter()
+        .map(|key| (key % 2 == 0 && *key < 200).then(|| key.to_string()))
+        .collect();
+    assert_eq!(found, expected);
+    assert_eq!(tree.get_many(&[]).unwrap(), Vec::<Option<String>>::new());
+
+    fs::remove_dir_all(dir).unwrap();
+}
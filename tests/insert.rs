@@ -0,0 +1,60 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn insert_many_and_read_back() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+
+    // Insert in an order that isn't sorted so splits happen all over the tree.
+    for i in 0..1000 {
+        let key = (i * 7919) % 1000;
+        assert_eq!(tree.insert(key, key * 2).unwrap(), None);
+    }
+
+    // Overwriting returns the previously stored value, which proves every key is reachable.
+    for key in 0..1000 {
+        assert_eq!(tree.insert(key, key).unwrap(), Some(key * 2));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn insert_existing_key_returns_old_value() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, String> = BTree::new(dir.clone(), 5).unwrap();
+
+    assert_eq!(tree.insert(1, String::from("first")).unwrap(), None);
+    assert_eq!(
+        tree.insert(1, String::from("second")).unwrap(),
+        Some(String::from("first"))
+    );
+    assert_eq!(tree.get(&1).unwrap(), Some(String::from("second")));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn insert_into_a_deep_tree() {
+    // The smallest capacity makes for the tallest tree, which the descent on each insert has to
+    // get through without recursing.
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 3).unwrap();
+    for i in 0..10000 {
+        let key = (i * 7919) % 10000;
+        assert_eq!(tree.insert(key, key).unwrap(), None);
+    }
+
+    assert!(tree.stats().unwrap().height >= 8);
+    assert_eq!(tree.len(), 10000);
+    tree.validate().unwrap();
+    for key in (0..10000).step_by(97) {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn iter_empty_tree() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+
+    assert!(tree.iter().next().is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn iter_yields_sorted_entries() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..1000 {
+        let key = (i * 7919) % 1000;
+        tree.insert(key, key * 2).unwrap();
+    }
+
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    let expected: Vec<(u64, u64)> = (0..1000).map(|key| (key, key * 2)).collect();
+    assert_eq!(entries, expected);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn iter_rev_yields_descending_entries() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    assert!(tree.iter_rev().next().is_none());
+
+    for i in 0..1000 {
+        let key = (i * 7919) % 1000;
+        tree.insert(key, key * 2).unwrap();
+    }
+
+    let entries: Vec<(u64, u64)> = tree.iter_rev().collect::<Result<_, _>>().unwrap();
+    assert!(entries.windows(2).all(|pair| pair[0].0 > pair[1].0));
+    let mut forward: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    forward.reverse();
+    assert_eq!(entries, forward);
+    assert_eq!(entries.len(), 1000);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn keys_and_values() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, String> = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..500 {
+        let key = (i * 7919) % 500;
+        tree.insert(key, format!("value {key}")).unwrap();
+    }
+
+    let keys: Vec<u64> = tree.keys().collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, (0..500).collect::<Vec<_>>());
+    let values: Vec<String> = tree.values().collect::<Result<_, _>>().unwrap();
+    let expected: Vec<String> = (0..500).map(|key| format!("value {key}")).collect();
+    assert_eq!(values, expected);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn into_iter_consumes_tree() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in (0..100u64).rev() {
+        tree.insert(key, key * 10).unwrap();
+    }
+
+    let mut entries = Vec::new();
+    for entry in tree {
+        entries.push(entry.unwrap());
+    }
+    let expected: Vec<(u64, u64)> = (0..100).map(|key| (key, key * 10)).collect();
+    assert_eq!(entries, expected);
+
+    // The backing directory is left for the caller to deal with.
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 100);
+    assert_eq!(tree.get(&42).unwrap(), Some(420));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn peek_then_next_agree() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..50u64 {
+        tree.insert(key, key + 100).unwrap();
+    }
+    tree.close().unwrap();
+
+    // Reopened, so every node is read from disk the first time the iterator reaches it.
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    let mut iter = tree.iter();
+    for key in 0..50 {
+        assert_eq!(iter.peek().unwrap().as_ref().unwrap(), &(key, key + 100));
+        assert_eq!(iter.peek().unwrap().as_ref().unwrap(), &(key, key + 100));
+        assert_eq!(iter.next().unwrap().unwrap(), (key, key + 100));
+    }
+    assert!(iter.peek().is_none());
+    assert!(iter.next().is_none());
+    drop(iter);
+
+    // Peeking reads no node that plain iteration wouldn't, and none twice.
+    let peeked = tree.cache_stats().misses;
+    drop(tree);
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    assert_eq!(tree.iter().count(), 50);
+    assert_eq!(tree.cache_stats().misses, peeked);
+
+    fs::remove_dir_all(dir).unwrap();
+}
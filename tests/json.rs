@@ -0,0 +1,55 @@
+#![cfg(feature = "json")]
+
+mod common;
+
+use std::fs;
+
+use btree::{BTree, Error};
+
+#[test]
+fn json_round_trip() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<String, Vec<u32>> = BTree::new(dir.clone(), 4).unwrap();
+    for key in 0..200u32 {
+        tree.insert(format!("key {key:03}"), vec![key, key * 2])
+            .unwrap();
+    }
+    let mut buf = Vec::new();
+    tree.to_json_writer(&mut buf).unwrap();
+    let json = String::from_utf8(buf.clone()).unwrap();
+    assert!(json.starts_with(r#"[{"key":"key 000","value":[0,0]},{"key":"key 001""#));
+
+    let new_dir = common::temp_dir();
+    let mut imported =
+        BTree::<String, Vec<u32>>::from_json_reader(new_dir.clone(), 5, buf.as_slice()).unwrap();
+    imported.validate().unwrap();
+    let expected: Vec<_> = tree.iter().map(Result::unwrap).collect();
+    let found: Vec<_> = imported.iter().map(Result::unwrap).collect();
+    assert_eq!(found, expected);
+    drop(imported);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(new_dir).unwrap();
+}
+
+#[test]
+fn json_entries_in_any_order() {
+    let json = r#"[{"key":3,"value":"c"},{"key":1,"value":"a"},{"key":2,"value":"b"}]"#;
+    let dir = common::temp_dir();
+    let mut tree = BTree::<u64, String>::from_json_reader(dir.clone(), 3, json.as_bytes()).unwrap();
+    let keys: Vec<_> = tree.keys().map(Result::unwrap).collect();
+    assert_eq!(keys, [1, 2, 3]);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn malformed_json_fails() {
+    let dir = common::temp_dir();
+    let result = BTree::<u64, String>::from_json_reader(dir.clone(), 3, &b"[{\"key\":1}]"[..]);
+    assert!(matches!(result, Err(Error::Json(_))));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn len_tracks_inserts_overwrites_and_removes() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    assert_eq!(tree.len(), 0);
+    assert!(tree.is_empty());
+
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+        assert_eq!(tree.len(), key as usize + 1);
+    }
+
+    // Overwriting doesn't add entries.
+    for key in 0..50 {
+        tree.insert(key, key + 1).unwrap();
+    }
+    assert_eq!(tree.len(), 100);
+
+    // Removing absent keys doesn't remove entries.
+    for key in 100..150 {
+        tree.remove(&key).unwrap();
+    }
+    assert_eq!(tree.len(), 100);
+
+    for key in (0..100).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    assert_eq!(tree.len(), 50);
+    assert!(!tree.is_empty());
+
+    for key in (1..100).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    assert_eq!(tree.len(), 0);
+    assert!(tree.is_empty());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn len_survives_reopen() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..30 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.remove(&3).unwrap();
+    tree.flush().unwrap();
+    drop(tree);
+
+    let tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 29);
+
+    fs::remove_dir_all(dir).unwrap();
+}
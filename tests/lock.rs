@@ -0,0 +1,54 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, Error};
+
+#[test]
+fn second_writer_is_locked_out() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    tree.insert(1u64, 1u64).unwrap();
+    tree.flush().unwrap();
+
+    assert!(matches!(
+        BTree::<u64, u64>::open(dir.clone()),
+        Err(Error::Locked)
+    ));
+    assert!(matches!(
+        BTree::<u64, u64>::open_read_only(dir.clone()),
+        Err(Error::Locked)
+    ));
+    // The tree that holds the lock carries on as before.
+    tree.insert(2, 2).unwrap();
+    drop(tree);
+
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    assert_eq!(tree.get(&2).unwrap(), Some(2));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn readers_share_the_lock() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    tree.insert(1u64, 1u64).unwrap();
+    tree.close().unwrap();
+
+    let mut first = BTree::<u64, u64>::open_read_only(dir.clone()).unwrap();
+    let mut second = BTree::<u64, u64>::open_read_only(dir.clone()).unwrap();
+    assert_eq!(first.get(&1).unwrap(), Some(1));
+    assert_eq!(second.get(&1).unwrap(), Some(1));
+    assert!(matches!(
+        BTree::<u64, u64>::open(dir.clone()),
+        Err(Error::Locked)
+    ));
+    drop(first);
+    drop(second);
+
+    BTree::<u64, u64>::open(dir.clone()).unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "mmap")]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder};
+
+const KEYS: u64 = 1000;
+
+/// The tree in `dir`, reading its node files by mapping them if `mmap` is set, and caching
+/// nothing, so that every node below the root is read from storage each time.
+fn open(dir: &Path, mmap: bool) -> BTree<u64, String> {
+    let mut tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(8)
+        .mmap(mmap)
+        .build()
+        .unwrap();
+    tree.set_cache_capacity(0).unwrap();
+
+    tree
+}
+
+fn contents(tree: &mut BTree<u64, String>) -> Vec<(u64, String)> {
+    tree.iter().map(Result::unwrap).collect()
+}
+
+#[test]
+fn mapped_reads_match_normal_reads() {
+    let dir = common::temp_dir();
+    let mut tree = open(&dir, false);
+    for key in 0..KEYS {
+        tree.insert(key, key.to_string().repeat(20)).unwrap();
+    }
+    let expected = contents(&mut tree);
+    tree.close().unwrap();
+
+    let mut tree = open(&dir, true);
+    tree.validate().unwrap();
+    assert_eq!(contents(&mut tree), expected);
+    for key in 0..KEYS {
+        assert_eq!(
+            tree.get(&key).unwrap().as_ref(),
+            Some(&expected[key as usize].1)
+        );
+    }
+
+    // Nodes written while the tree reads through mappings read back the same way either way.
+    for key in (0..KEYS).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    for key in KEYS..2 * KEYS {
+        tree.insert(key, String::new()).unwrap();
+    }
+    let expected = contents(&mut tree);
+    tree.close().unwrap();
+
+    let mut tree = open(&dir, false);
+    tree.validate().unwrap();
+    assert_eq!(contents(&mut tree), expected);
+    drop(tree);
+    let mut tree = open(&dir, true);
+    assert_eq!(contents(&mut tree), expected);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
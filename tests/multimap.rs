@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+
+use btree::BTreeMultiMap;
+
+#[test]
+fn values_under_one_key_keep_their_order() {
+    let dir = common::temp_dir();
+    let mut map = BTreeMultiMap::new(dir.clone(), 3).unwrap();
+    for (key, value) in [
+        (1u64, "a"),
+        (2, "x"),
+        (1, "b"),
+        (3, "y"),
+        (1, "c"),
+        (2, "z"),
+    ] {
+        map.insert(key, value.to_string()).unwrap();
+    }
+    for key in 10..60u64 {
+        map.insert(key, key.to_string()).unwrap();
+    }
+
+    assert_eq!(map.len(), 53);
+    assert_eq!(map.get_all(&1).unwrap(), ["a", "b", "c"]);
+    assert_eq!(map.get_all(&2).unwrap(), ["x", "z"]);
+    assert_eq!(map.get_all(&4).unwrap(), Vec::<String>::new());
+    map.validate().unwrap();
+    map.close().unwrap();
+
+    let mut map = BTreeMultiMap::<u64, String>::open(dir.clone()).unwrap();
+    assert_eq!(map.get_all(&1).unwrap(), ["a", "b", "c"]);
+    drop(map);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_takes_the_group_and_remove_value_one() {
+    let dir = common::temp_dir();
+    let mut map = BTreeMultiMap::new(dir.clone(), 3).unwrap();
+    for value in ["a", "b", "a", "c"] {
+        map.insert(1u64, value.to_string()).unwrap();
+    }
+    map.insert(2, String::from("d")).unwrap();
+
+    assert!(map.remove_value(&1, &String::from("a")).unwrap());
+    assert_eq!(map.get_all(&1).unwrap(), ["b", "a", "c"]);
+    assert!(!map.remove_value(&1, &String::from("e")).unwrap());
+    assert!(!map.remove_value(&3, &String::from("a")).unwrap());
+
+    // Removing the last value under a key removes the key.
+    assert!(map.remove_value(&2, &String::from("d")).unwrap());
+    assert!(!map.contains_key(&2).unwrap());
+
+    assert_eq!(map.remove(&1).unwrap(), ["b", "a", "c"]);
+    assert!(map.is_empty());
+    assert_eq!(map.remove(&1).unwrap(), Vec::<String>::new());
+    drop(map);
+
+    fs::remove_dir_all(dir).unwrap();
+}
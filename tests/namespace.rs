@@ -0,0 +1,91 @@
+mod common;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+fn file_names(dir: &Path) -> HashSet<String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect()
+}
+
+fn tree(dir: &Path, namespace: &str) -> BTree<u64, u64> {
+    BTreeBuilder::new(dir.to_path_buf())
+        .capacity(3)
+        .namespace(namespace)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn trees_share_a_directory() {
+    let dir = common::temp_dir();
+    let mut a = tree(&dir, "a");
+    let mut b = tree(&dir, "b");
+    for key in 0..100 {
+        a.insert(key, key).unwrap();
+        b.insert(key, key * 2).unwrap();
+    }
+    for key in 0..50 {
+        a.remove(&key).unwrap();
+    }
+    a.flush().unwrap();
+    b.close().unwrap();
+
+    let b_files: HashSet<String> = file_names(&dir)
+        .into_iter()
+        .filter(|name| name.ends_with(".b"))
+        .collect();
+    assert!(b_files.contains("meta.b"));
+    assert!(b_files.contains("root.b"));
+    assert!(b_files.len() > 20);
+
+    // Whatever `a` has lying around is its to collect, but none of `b`'s files are.
+    fs::write(dir.join("stray.a"), b"").unwrap();
+    assert!(a.gc().unwrap() > 0);
+    let names = file_names(&dir);
+    assert!(!names.contains("stray.a"));
+    assert!(b_files.is_subset(&names));
+    a.close().unwrap();
+
+    let mut a = tree(&dir, "a");
+    let mut b = tree(&dir, "b");
+    a.validate().unwrap();
+    b.validate().unwrap();
+    assert_eq!(a.len(), 50);
+    assert_eq!(b.len(), 100);
+    assert_eq!(a.get(&70).unwrap(), Some(70));
+    assert_eq!(b.get(&70).unwrap(), Some(140));
+    drop(a);
+    drop(b);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn namespaced_tree_is_not_created_twice() {
+    let dir = common::temp_dir();
+    let mut tree = tree_in(&dir, "a").unwrap();
+    tree.insert(1, 1).unwrap();
+    tree.close().unwrap();
+
+    // Building it again opens the existing tree rather than making a new one.
+    let mut tree = tree_in(&dir, "a").unwrap();
+    assert_eq!(tree.get(&1).unwrap(), Some(1));
+    drop(tree);
+
+    let result = tree_in(&dir, "a.b");
+    assert!(matches!(result, Err(Error::InvalidFileName(name)) if name == "a.b"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn tree_in(dir: &Path, namespace: &str) -> Result<BTree<u64, u64>, Error> {
+    BTreeBuilder::new(dir.to_path_buf())
+        .namespace(namespace)
+        .build()
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use std::fs::{self, File};
+
+use btree::{BTree, Error};
+use rmp_serde::Serializer;
+use serde::{Deserialize, Serialize};
+
+#[test]
+fn reopen_existing_tree() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..200 {
+        tree.insert(key, key * 3).unwrap();
+    }
+    tree.flush().unwrap();
+    drop(tree);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in 0..200 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 3));
+    }
+
+    // The reopened tree keeps the original capacity, so it keeps splitting correctly.
+    for key in 200..400 {
+        tree.insert(key, key * 3).unwrap();
+    }
+    for key in 0..400 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 3));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn open_missing_tree() {
+    let dir = common::temp_dir();
+    assert!(matches!(
+        BTree::<u64, u64>::open(dir.clone()),
+        Err(Error::Missing)
+    ));
+    assert!(matches!(
+        BTree::<u64, u64>::open_read_only(dir.clone()),
+        Err(Error::Missing)
+    ));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn open_empty_directory() {
+    let dir = common::temp_dir();
+    fs::create_dir(&dir).unwrap();
+    assert!(matches!(
+        BTree::<u64, u64>::open(dir.clone()),
+        Err(Error::NotInitialized)
+    ));
+    assert!(matches!(
+        BTree::<u64, u64>::open_read_only(dir.clone()),
+        Err(Error::NotInitialized)
+    ));
+    // Nothing was left behind, not even a lock file.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Mirrors the layout of the crate's meta file.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Meta {
+    version: u32,
+    capacity: usize,
+    merge_threshold: f32,
+    root: String,
+    len: usize,
+    free: Vec<String>,
+    encoding: Encoding,
+    naming: Naming,
+    current_version: u64,
+    versions: Vec<Version>,
+    bloom_filter: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Version {
+    number: u64,
+    root: String,
+    len: usize,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Encoding {
+    codec: CodecKind,
+    compressed: bool,
+    encrypted: bool,
+    external_values: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Naming {
+    prefix: String,
+    namespace: Option<String>,
+    extension: Option<String>,
+    next_node: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+enum CodecKind {
+    Msgpack,
+    // Never written here, but it keeps the variants lined up with the crate's.
+    #[allow(dead_code)]
+    Bincode,
+}
+
+#[test]
+fn meta_file_round_trips() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 7).unwrap();
+    tree.flush().unwrap();
+    drop(tree);
+
+    let meta: Meta = rmp_serde::from_read(File::open(dir.join("meta")).unwrap()).unwrap();
+    assert_eq!(
+        meta,
+        Meta {
+            version: 15,
+            capacity: 7,
+            merge_threshold: 0.5,
+            root: String::from("root"),
+            len: 0,
+            free: Vec::new(),
+            encoding: Encoding {
+                codec: CodecKind::Msgpack,
+                compressed: false,
+                encrypted: false,
+                external_values: None,
+            },
+            naming: Naming {
+                prefix: String::new(),
+                namespace: None,
+                extension: None,
+                next_node: None,
+            },
+            current_version: 0,
+            versions: Vec::new(),
+            bloom_filter: None,
+        }
+    );
+    BTree::<u64, u64>::open(dir.clone()).unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn open_with_mismatched_version() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 7).unwrap();
+    tree.flush().unwrap();
+    drop(tree);
+
+    // A meta file as version 2 laid it out, before it held the free list.
+    let meta = (2u32, 7usize, "root", 0usize);
+    let mut file = File::create(dir.join("meta")).unwrap();
+    meta.serialize(&mut Serializer::new(&mut file)).unwrap();
+
+    assert!(matches!(
+        BTree::<u64, u64>::open(dir.clone()),
+        Err(Error::UnsupportedVersion {
+            found: 2,
+            expected: 15
+        })
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dropping_flushes_pending_writes() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..100 {
+        tree.insert(key, key * 2).unwrap();
+    }
+    drop(tree);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 100);
+    for key in 0..100 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 2));
+    }
+    tree.close().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, PathStack, Step};
+
+#[test]
+fn push_and_pop() {
+    let mut stack = PathStack::new();
+    assert_eq!(stack.depth(), 0);
+    assert_eq!(stack.siblings(), (None, None));
+
+    stack.push(Step::new(0, 3));
+    stack.push(Step::new(2, 3));
+    assert_eq!(stack.depth(), 2);
+    assert_eq!(stack.last().unwrap().child, 2);
+    assert_eq!(stack.siblings(), (Some(1), None));
+
+    assert_eq!(stack.pop().unwrap().child, 2);
+    assert_eq!(stack.siblings(), (None, Some(1)));
+    assert_eq!(stack.pop().unwrap().child, 0);
+    assert!(stack.pop().is_none());
+}
+
+#[test]
+fn siblings() {
+    let middle = Step::new(1, 3);
+    assert_eq!(middle.left_sibling(), Some(0));
+    assert_eq!(middle.right_sibling(), Some(2));
+
+    let only = Step::new(0, 1);
+    assert_eq!(only.left_sibling(), None);
+    assert_eq!(only.right_sibling(), None);
+
+    let last = Step::new(4, 5);
+    assert_eq!(last.left_sibling(), Some(3));
+    assert_eq!(last.right_sibling(), None);
+}
+
+#[test]
+fn path_to_a_key() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..500u64 {
+        tree.insert(key, key).unwrap();
+    }
+    let height = tree.stats().unwrap().height;
+
+    // Any key missing from the tree belongs in a leaf.
+    let path = tree.path_to(&1000).unwrap();
+    assert_eq!(path.depth(), height - 1);
+    assert!(path.steps().iter().all(|step| step.child < step.children));
+    assert_eq!(path.siblings().1, None);
+
+    let first = tree.path_to(&0).unwrap();
+    assert!(first.steps().iter().all(|step| step.child == 0));
+    assert_eq!(first.siblings().0, None);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+const WORDS: [&str; 9] = [
+    "ap",
+    "app",
+    "apple",
+    "apples",
+    "applet",
+    "application",
+    "apply",
+    "apq",
+    "banana",
+];
+
+fn scan(tree: &mut BTree<String, usize>, prefix: &str) -> Vec<String> {
+    tree.scan_prefix(prefix)
+        .map(|entry| entry.unwrap().0)
+        .collect()
+}
+
+#[test]
+fn scan_string_prefixes() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for (idx, word) in WORDS.iter().enumerate() {
+        tree.insert(word.to_string(), idx).unwrap();
+    }
+
+    assert_eq!(
+        scan(&mut tree, "app"),
+        ["app", "apple", "apples", "applet", "application", "apply"]
+    );
+    assert_eq!(scan(&mut tree, "apple"), ["apple", "apples", "applet"]);
+    assert_eq!(scan(&mut tree, "applet"), ["applet"]);
+    assert_eq!(scan(&mut tree, "applets"), Vec::<String>::new());
+    assert_eq!(scan(&mut tree, "").len(), WORDS.len());
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn scan_string_prefix_ending_in_last_char() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in ["a", "a\u{10ffff}", "a\u{10ffff}b", "b"] {
+        tree.insert(key.to_string(), 0).unwrap();
+    }
+
+    assert_eq!(
+        scan(&mut tree, "a\u{10ffff}"),
+        ["a\u{10ffff}", "a\u{10ffff}b"]
+    );
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn scan_byte_prefixes() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<Vec<u8>, u8> = BTree::new(dir.clone(), 3).unwrap();
+    let keys: [&[u8]; 7] = [
+        b"\x01",
+        b"\x01\xff",
+        b"\x01\xff\x00",
+        b"\x02",
+        b"\xff",
+        b"\xff\xff",
+        b"\xff\xff\x01",
+    ];
+    for key in keys {
+        tree.insert(key.to_vec(), 0).unwrap();
+    }
+    let mut scan = |prefix: &[u8]| -> Vec<Vec<u8>> {
+        tree.scan_prefix(prefix)
+            .map(|entry| entry.unwrap().0)
+            .collect()
+    };
+
+    assert_eq!(scan(b"\x01"), [&b"\x01"[..], b"\x01\xff", b"\x01\xff\x00"]);
+    assert_eq!(scan(b"\x01\xff"), [&b"\x01\xff"[..], b"\x01\xff\x00"]);
+    // A prefix of nothing but `0xff` has no keys past it, so the scan runs to the end.
+    assert_eq!(scan(b"\xff"), [&b"\xff"[..], b"\xff\xff", b"\xff\xff\x01"]);
+    assert_eq!(scan(b"\xff\xff"), [&b"\xff\xff"[..], b"\xff\xff\x01"]);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
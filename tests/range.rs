@@ -0,0 +1,114 @@
+mod common;
+
+use std::fs;
+use std::ops::{Bound, RangeBounds};
+use std::path::PathBuf;
+
+use btree::BTree;
+
+/// Every even key in `0..1000`, so range bounds can land both on and between keys.
+fn tree() -> (PathBuf, BTree<u64, u64>) {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..500 {
+        let key = ((i * 7919) % 500) * 2;
+        tree.insert(key, key + 1).unwrap();
+    }
+
+    (dir, tree)
+}
+
+fn check<R: RangeBounds<u64> + Clone>(tree: &mut BTree<u64, u64>, range: R) {
+    let entries: Vec<(u64, u64)> = tree.range(range.clone()).collect::<Result<_, _>>().unwrap();
+    let expected: Vec<(u64, u64)> = (0..1000)
+        .step_by(2)
+        .filter(|key| range.contains(key))
+        .map(|key| (key, key + 1))
+        .collect();
+    assert_eq!(entries, expected);
+    assert_eq!(tree.range_count(range).unwrap(), expected.len());
+}
+
+#[test]
+fn range_bounded() {
+    let (dir, mut tree) = tree();
+    check(&mut tree, 100..200);
+    check(&mut tree, 101..=199);
+    check(&mut tree, 100..=200);
+    check(&mut tree, (Bound::Excluded(100), Bound::Included(200)));
+    check(&mut tree, (Bound::Excluded(101), Bound::Excluded(201)));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn range_unbounded() {
+    let (dir, mut tree) = tree();
+    check(&mut tree, ..);
+    check(&mut tree, ..50);
+    check(&mut tree, ..=50);
+    check(&mut tree, 950..);
+    check(&mut tree, (Bound::Excluded(998), Bound::Unbounded));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn range_empty() {
+    let (dir, mut tree) = tree();
+    assert!(tree.range(100..100).next().is_none());
+    assert!(tree.range(101..102).next().is_none());
+    assert!(tree.range(2000..).next().is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+#[allow(clippy::reversed_empty_ranges)]
+fn range_start_after_end() {
+    let (dir, mut tree) = tree();
+    assert!(tree.range(200..100).next().is_none());
+    assert!(tree
+        .range((Bound::Excluded(100), Bound::Excluded(100)))
+        .next()
+        .is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+#[allow(clippy::reversed_empty_ranges)]
+fn range_count_of_empty_ranges() {
+    let (dir, mut tree) = tree();
+    assert_eq!(tree.range_count(100..100).unwrap(), 0);
+    assert_eq!(tree.range_count(101..102).unwrap(), 0);
+    assert_eq!(tree.range_count(2000..).unwrap(), 0);
+    assert_eq!(tree.range_count(200..100).unwrap(), 0);
+    assert_eq!(
+        tree.range_count((Bound::Excluded(100), Bound::Excluded(100)))
+            .unwrap(),
+        0
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn range_into_reuses_the_buffer() {
+    let (dir, mut tree) = tree();
+    let mut out = Vec::new();
+    assert_eq!(tree.range_into(100..110, &mut out, false).unwrap(), 5);
+    assert_eq!(
+        out,
+        [(100, 101), (102, 103), (104, 105), (106, 107), (108, 109)]
+    );
+    let capacity = out.capacity();
+
+    assert_eq!(tree.range_into(105..=112, &mut out, false).unwrap(), 4);
+    assert_eq!(out, [(106, 107), (108, 109), (110, 111), (112, 113)]);
+    assert_eq!(out.capacity(), capacity);
+
+    assert_eq!(tree.range_into(99..101, &mut out, true).unwrap(), 1);
+    assert_eq!(out.len(), 5);
+    assert_eq!(out.last(), Some(&(100, 101)));
+    assert_eq!(tree.range_into(1001.., &mut out, true).unwrap(), 0);
+    assert_eq!(out.len(), 5);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use btree::{BTree, Error};
+
+/// The contents of every file in `dir`.
+fn contents(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let contents = fs::read(&path).unwrap();
+            (path, contents)
+        })
+        .collect()
+}
+
+#[test]
+fn read_only_tree_reads_but_does_not_write() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..200u64 {
+        tree.insert(key, key * 2).unwrap();
+    }
+    tree.close().unwrap();
+    let before = contents(&dir);
+
+    let mut tree: BTree<u64, u64> = BTree::open_read_only(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 200);
+    assert_eq!(tree.get(&7).unwrap(), Some(14));
+    assert!(tree.contains_key(&199).unwrap());
+    assert_eq!(tree.iter().count(), 200);
+    let range: Vec<(u64, u64)> = tree.range(10..13).collect::<Result<_, _>>().unwrap();
+    assert_eq!(range, [(10, 20), (11, 22), (12, 24)]);
+    assert_eq!(tree.stats().unwrap().entry_count, 200);
+
+    assert!(matches!(tree.insert(1000, 0), Err(Error::ReadOnly)));
+    assert!(matches!(tree.remove(&7), Err(Error::ReadOnly)));
+    assert!(matches!(tree.entry(7), Err(Error::ReadOnly)));
+    assert!(matches!(tree.get_mut(&7), Err(Error::ReadOnly)));
+    assert!(matches!(tree.clear(), Err(Error::ReadOnly)));
+    assert!(matches!(tree.gc(), Err(Error::ReadOnly)));
+    assert_eq!(tree.get(&7).unwrap(), Some(14));
+    assert_eq!(tree.len(), 200);
+    tree.close().unwrap();
+
+    // Not even the meta file is rewritten.
+    assert_eq!(contents(&dir), before);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+/// The number of node files in `dir` once `tree` is flushed, which is the number of nodes in the
+/// tree.
+fn file_count(tree: &mut BTree<u64, u64>, dir: &Path) -> usize {
+    tree.flush().unwrap();
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_none() && !path.ends_with("meta") && !path.ends_with("LOCK")
+        })
+        .count()
+}
+
+fn tree_with(dir: &Path, keys: impl IntoIterator<Item = u64>) -> BTree<u64, u64> {
+    let mut tree = BTree::new(dir.to_path_buf(), 5).unwrap();
+    for key in keys {
+        tree.insert(key, key * 10).unwrap();
+    }
+
+    tree
+}
+
+#[test]
+fn remove_absent_key() {
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, 0..10);
+
+    assert_eq!(tree.remove(&100).unwrap(), None);
+    for key in 0..10 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 10));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_borrows_from_sibling() {
+    // Root [2] with leaves [0, 1] and [3, 4, 5]. The left leaf is at the minimum, so it borrows
+    // through the root from its right sibling before losing a key.
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, 0..6);
+    assert_eq!(file_count(&mut tree, &dir), 3);
+
+    assert_eq!(tree.remove(&0).unwrap(), Some(0));
+    assert_eq!(file_count(&mut tree, &dir), 3);
+    assert_eq!(tree.get(&0).unwrap(), None);
+    for key in 1..6 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 10));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_merges_siblings() {
+    // Root [2, 5] with leaves [0, 1], [3, 4] and [6, 7, 8]. The first two leaves are both at the
+    // minimum, so they merge around the separator `2` and one leaf file goes away.
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, 0..9);
+    assert_eq!(file_count(&mut tree, &dir), 4);
+
+    assert_eq!(tree.remove(&0).unwrap(), Some(0));
+    assert_eq!(file_count(&mut tree, &dir), 3);
+    assert_eq!(tree.get(&0).unwrap(), None);
+    for key in 1..9 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 10));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_shrinks_height() {
+    // Root [2] with leaves [0, 1] and [3, 4]. Merging the leaves empties the root, so the merged
+    // leaf becomes the new root.
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, 0..6);
+    tree.remove(&5).unwrap();
+    assert_eq!(file_count(&mut tree, &dir), 3);
+
+    assert_eq!(tree.remove(&4).unwrap(), Some(40));
+    assert_eq!(file_count(&mut tree, &dir), 1);
+    for key in 0..4 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key * 10));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_many() {
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, (0..1000).map(|i| (i * 7919) % 1000));
+
+    for key in (0..1000).filter(|key| key % 3 != 0) {
+        assert_eq!(tree.remove(&key).unwrap(), Some(key * 10));
+    }
+    for key in 0..1000 {
+        let expected = if key % 3 == 0 { Some(key * 10) } else { None };
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+    tree.validate().unwrap();
+
+    for key in (0..1000).filter(|key| key % 3 == 0) {
+        assert_eq!(tree.remove(&key).unwrap(), Some(key * 10));
+    }
+    assert_eq!(file_count(&mut tree, &dir), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_entry_returns_stored_key() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<String, u32> = BTree::new(dir.clone(), 3).unwrap();
+    for (idx, word) in ["pear", "apple", "fig", "plum", "kiwi", "lime", "date"]
+        .iter()
+        .enumerate()
+    {
+        tree.insert(word.to_string(), idx as u32).unwrap();
+    }
+
+    assert_eq!(
+        tree.remove_entry("pear").unwrap(),
+        Some((String::from("pear"), 0))
+    );
+    assert_eq!(
+        tree.remove_entry("date").unwrap(),
+        Some((String::from("date"), 6))
+    );
+    assert_eq!(tree.remove_entry("pear").unwrap(), None);
+    assert_eq!(tree.len(), 5);
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Remove three in every four keys from a tree built with `merge_threshold`, returning the
+/// number of nodes left.
+fn nodes_after_removing(merge_threshold: f32) -> usize {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+        .capacity(9)
+        .merge_threshold(merge_threshold)
+        .build()
+        .unwrap();
+    for key in 0..1000 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in (0..1000).filter(|key| key % 4 != 0) {
+        tree.remove(&key).unwrap();
+    }
+    tree.validate().unwrap();
+    let nodes = tree.stats().unwrap().node_count;
+
+    // The threshold is kept with the tree, so it still holds once the tree is reopened.
+    tree.close().unwrap();
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in (0..1000).step_by(8) {
+        tree.remove(&key).unwrap();
+    }
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 125);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+    nodes
+}
+
+#[test]
+fn lower_merge_threshold_merges_less() {
+    let eager = nodes_after_removing(0.5);
+    let lazy = nodes_after_removing(0.1);
+    assert!(lazy > eager, "{lazy} nodes should be more than {eager}");
+}
+
+#[test]
+fn merge_threshold_out_of_range() {
+    for merge_threshold in [0.0, 0.6, f32::NAN] {
+        let dir = common::temp_dir();
+        let result = BTreeBuilder::<u64, u64>::new(dir.clone())
+            .merge_threshold(merge_threshold)
+            .build();
+        assert!(matches!(result, Err(Error::InvalidMergeThreshold(_))));
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use std::fs;
+use std::ops::Bound;
+use std::path::PathBuf;
+
+use btree::BTree;
+
+fn tree() -> (PathBuf, BTree<u64, u64>) {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for i in 0..1000 {
+        let key = (i * 7919) % 1000;
+        tree.insert(key, key * 2).unwrap();
+    }
+
+    (dir, tree)
+}
+
+#[test]
+fn remove_middle_of_tree() {
+    let (dir, mut tree) = tree();
+    assert_eq!(tree.remove_range(250..750).unwrap(), 500);
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 500);
+
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    let expected: Vec<(u64, u64)> = (0..250)
+        .chain(750..1000)
+        .map(|key| (key, key * 2))
+        .collect();
+    assert_eq!(entries, expected);
+    assert_eq!(tree.remove_range(250..750).unwrap(), 0);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_whole_tree() {
+    let (dir, mut tree) = tree();
+    assert_eq!(tree.remove_range(..).unwrap(), 1000);
+    tree.validate().unwrap();
+    assert!(tree.is_empty());
+    assert_eq!(tree.iter().count(), 0);
+
+    tree.insert(1, 1).unwrap();
+    assert_eq!(tree.get(&1).unwrap(), Some(1));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn remove_range_with_write_ahead_log() {
+    let (dir, mut tree) = tree();
+    tree.set_write_ahead_log(true).unwrap();
+    assert_eq!(tree.remove_range(..=499).unwrap(), 500);
+    tree.validate().unwrap();
+    tree.close().unwrap();
+
+    let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 500);
+    assert_eq!(tree.first_key_value().unwrap(), Some((500, 1000)));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn retain_window() {
+    let (dir, mut tree) = tree();
+    assert_eq!(tree.retain_range(100..200).unwrap(), 900);
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), 100);
+
+    let entries: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    let expected: Vec<(u64, u64)> = (100..200).map(|key| (key, key * 2)).collect();
+    assert_eq!(entries, expected);
+    assert_eq!(tree.retain_range(100..200).unwrap(), 0);
+    assert_eq!(tree.retain_range(150..).unwrap(), 50);
+    assert_eq!(tree.first_key_value().unwrap(), Some((150, 300)));
+    tree.close().unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 50);
+    assert!(!tree.contains_key(&149).unwrap());
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn retain_empty_window() {
+    let (dir, mut tree) = tree();
+    assert_eq!(tree.retain_range(500..=500).unwrap(), 999);
+    assert_eq!(tree.len(), 1);
+    // A window with nothing in it, or with its ends the wrong way round, keeps nothing.
+    assert_eq!(
+        tree.retain_range((Bound::Excluded(500), Bound::Excluded(500)))
+            .unwrap(),
+        1
+    );
+    tree.validate().unwrap();
+    assert!(tree.is_empty());
+
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+    let inverted = (Bound::Included(60), Bound::Excluded(40));
+    assert_eq!(tree.retain_range(inverted).unwrap(), 100);
+    tree.validate().unwrap();
+    assert!(tree.is_empty());
+    assert_eq!(tree.retain_range(40..60).unwrap(), 0);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+const KEYS: u64 = 300;
+const CAPACITY: usize = 3;
+
+fn build(dir: &Path) {
+    let mut tree = BTree::new(dir.to_path_buf(), CAPACITY).unwrap();
+    for i in 0..KEYS {
+        let key = (i * 7919) % KEYS;
+        tree.insert(key, key * 10).unwrap();
+    }
+    tree.close().unwrap();
+}
+
+/// Flip a byte in the middle of the file at `path`, so its checksum no longer matches.
+fn corrupt(path: &Path) {
+    let mut buf = fs::read(path).unwrap();
+    let idx = buf.len() / 2;
+    buf[idx] ^= 0xff;
+    fs::write(path, buf).unwrap();
+}
+
+/// Check that the repaired tree in `dir` is valid and holds `entries_recovered` of the
+/// entries `build` inserted, each with its own value.
+fn check_repaired(dir: &Path, entries_recovered: usize) {
+    let mut tree = BTree::<u64, u64>::open(dir.to_path_buf()).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(tree.len(), entries_recovered);
+    for entry in tree.iter() {
+        let (key, value) = entry.unwrap();
+        assert!(key < KEYS);
+        assert_eq!(value, key * 10);
+    }
+}
+
+#[test]
+fn repair_salvages_nodes_below_damaged_root() {
+    let dir = common::temp_dir();
+    build(&dir);
+
+    // Without the root nothing can be reached, so walking the tree would salvage nothing.
+    corrupt(&dir.join("root"));
+    assert!(matches!(
+        BTree::<u64, u64>::open(dir.clone()),
+        Err(Error::Node { .. })
+    ));
+
+    let report = BTree::<u64, u64>::repair(dir.clone()).unwrap();
+    assert_eq!(report.unreadable_nodes, 1);
+    assert!(report.nodes_read > 50);
+    // Only the root's own keys are lost.
+    assert!(report.entries_recovered >= KEYS as usize - CAPACITY);
+    assert!(report.entries_recovered < KEYS as usize);
+    check_repaired(&dir, report.entries_recovered);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn repair_skips_damaged_values_file() {
+    let dir = common::temp_dir();
+    build(&dir);
+
+    let values = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".values") && name != "root.values"
+        })
+        .unwrap();
+    corrupt(&values);
+
+    let report = BTree::<u64, u64>::repair(dir.clone()).unwrap();
+    assert_eq!(report.unreadable_nodes, 1);
+    assert!(report.entries_recovered >= KEYS as usize - CAPACITY);
+    check_repaired(&dir, report.entries_recovered);
+
+    // Repairing a sound tree keeps everything.
+    let report = BTree::<u64, u64>::repair(dir.clone()).unwrap();
+    assert_eq!(report.unreadable_nodes, 0);
+    check_repaired(&dir, report.entries_recovered);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn repair_leaves_other_namespaces_alone() {
+    let dir = common::temp_dir();
+    let tree = |namespace: &str| {
+        BTreeBuilder::<u64, u64>::new(dir.clone())
+            .capacity(CAPACITY)
+            .namespace(namespace)
+    };
+    let mut a = tree("a").build().unwrap();
+    let mut b = tree("b").build().unwrap();
+    for key in 0..KEYS {
+        a.insert(key, key * 10).unwrap();
+        b.insert(key, key * 20).unwrap();
+    }
+    a.close().unwrap();
+    b.close().unwrap();
+    let b_files: Vec<(String, Vec<u8>)> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().unwrap_or_default() == "b")
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(path).unwrap())
+        })
+        .collect();
+
+    corrupt(&dir.join("root.a"));
+    let report = tree("a").repair().unwrap();
+    assert_eq!(report.unreadable_nodes, 1);
+    assert!(report.entries_recovered >= KEYS as usize - CAPACITY);
+
+    // Every file of `b` is still there, just as it was.
+    for (name, contents) in &b_files {
+        assert_eq!(&fs::read(dir.join(name)).unwrap(), contents);
+    }
+    let mut a = tree("a").build().unwrap();
+    a.validate().unwrap();
+    assert_eq!(a.len(), report.entries_recovered);
+    // The old tree's files that the new one didn't take over are gone.
+    assert_eq!(a.gc().unwrap(), 0);
+    let mut b = tree("b").build().unwrap();
+    b.validate().unwrap();
+    assert_eq!(b.len(), KEYS as usize);
+    assert_eq!(b.get(&7).unwrap(), Some(140));
+    drop(a);
+    drop(b);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn retain_even_keys() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..1000u64 {
+        tree.insert(key, key.to_string()).unwrap();
+    }
+
+    let mut seen = 0;
+    tree.retain(|key, value| {
+        assert_eq!(*value, key.to_string());
+        seen += 1;
+        key % 2 == 0
+    })
+    .unwrap();
+    assert_eq!(seen, 1000);
+
+    assert_eq!(tree.len(), 500);
+    tree.validate().unwrap();
+    let keys: Vec<u64> = tree.keys().collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, (0..1000).step_by(2).collect::<Vec<_>>());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn retain_nothing() {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+
+    tree.retain(|_, _| false).unwrap();
+    assert!(tree.is_empty());
+    tree.validate().unwrap();
+}
+
+#[test]
+fn extract_odd_keys() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key * 2).unwrap();
+    }
+
+    let extracted = tree
+        .extract_if(|key, value| {
+            assert_eq!(*value, key * 2);
+            key % 2 == 1
+        })
+        .unwrap();
+    let odd: Vec<_> = (1..100).step_by(2).map(|key| (key, key * 2)).collect();
+    assert_eq!(extracted, odd);
+
+    assert_eq!(tree.len(), 50);
+    tree.validate().unwrap();
+    let even: Vec<_> = (0..100).step_by(2).map(|key| (key, key * 2)).collect();
+    let found: Vec<_> = tree.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(found, even);
+    assert!(tree.extract_if(|key, _| *key > 100).unwrap().is_empty());
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn interrupted_save_leaves_node_intact() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    // Few enough keys that the root is the only node.
+    for key in 0..4 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+    let root = fs::read(dir.join("root")).unwrap();
+
+    // A save that dies partway through only ever gets as far as the temporary file.
+    fs::write(dir.join("root.tmp"), &root[..root.len() / 2]).unwrap();
+    assert_eq!(fs::read(dir.join("root")).unwrap(), root);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in 0..4 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+
+    // The next save of the root replaces the leftover temporary file.
+    tree.insert(4, 4).unwrap();
+    tree.close().unwrap();
+    let leftovers = fs::read_dir(&dir)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("tmp".as_ref()))
+        .count();
+    assert_eq!(leftovers, 0);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    for key in 0..5 {
+        assert_eq!(tree.get(&key).unwrap(), Some(key));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use btree::BTree;
+
+/// Overwrite a byte in the middle of the file at `path`.
+fn corrupt(path: &Path) {
+    let mut file = fs::read(path).unwrap();
+    let idx = file.len() / 2;
+    file[idx] ^= 0xff;
+    fs::write(path, file).unwrap();
+}
+
+/// The values files of every node but the root, which has to be readable to open the tree.
+fn values_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "values"))
+        .filter(|path| !path.ends_with("root.values"))
+        .collect();
+    paths.sort();
+
+    paths
+}
+
+#[test]
+fn scrub_clean_tree() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..200u64 {
+        tree.insert(key, key).unwrap();
+    }
+
+    let report = tree.scrub().unwrap();
+    assert!(report.is_clean());
+    assert_eq!(report.nodes_checked, tree.stats().unwrap().node_count);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn scrub_reports_every_damaged_node() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..200u64 {
+        tree.insert(key, key).unwrap();
+    }
+    let nodes = tree.stats().unwrap().node_count;
+    tree.close().unwrap();
+
+    // Values files don't lead anywhere, so damage to them never hides other nodes.
+    let files = values_files(&dir);
+    let mut damaged = vec![files[0].clone(), files[files.len() - 1].clone()];
+    for path in &damaged {
+        corrupt(path);
+    }
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    let mut report = tree.scrub().unwrap();
+    assert_eq!(report.nodes_checked, nodes);
+    report.damaged.sort();
+    damaged.sort();
+    assert_eq!(report.damaged, damaged);
+    assert_eq!(report.invalid, None);
+    assert!(!report.is_clean());
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
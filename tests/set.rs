@@ -0,0 +1,66 @@
+mod common;
+
+use std::fs;
+
+use btree::BTreeSet;
+
+#[test]
+fn insert_contains_remove() {
+    let dir = common::temp_dir();
+    let mut set = BTreeSet::new(dir.clone(), 5).unwrap();
+
+    for i in 0..1000u64 {
+        assert!(set.insert((i * 7919) % 1000).unwrap());
+    }
+    // Inserting a key that is already there leaves the set as it was.
+    assert!(!set.insert(500).unwrap());
+    assert_eq!(set.len(), 1000);
+
+    for key in 0..1000 {
+        assert!(set.contains(&key).unwrap());
+    }
+    assert!(!set.contains(&1000).unwrap());
+
+    for key in (0..1000).step_by(2) {
+        assert!(set.remove(&key).unwrap());
+    }
+    assert!(!set.remove(&0).unwrap());
+    assert_eq!(set.len(), 500);
+    set.validate().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn iter_and_range() {
+    let mut set = BTreeSet::new_in_memory(5).unwrap();
+    for i in 0..500u64 {
+        set.insert(((i * 7919) % 500) * 2).unwrap();
+    }
+
+    let keys: Vec<u64> = set.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, (0..1000).step_by(2).collect::<Vec<_>>());
+
+    let keys: Vec<u64> = set.range(101..=199).collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys, (102..200).step_by(2).collect::<Vec<_>>());
+    assert!(set.range(2000..).next().is_none());
+}
+
+#[test]
+fn reopen_set() {
+    let dir = common::temp_dir();
+    let mut set = BTreeSet::new(dir.clone(), 5).unwrap();
+    for word in ["pear", "apple", "fig"] {
+        set.insert(word.to_string()).unwrap();
+    }
+    set.close().unwrap();
+
+    let mut set: BTreeSet<String> = BTreeSet::open(dir.clone()).unwrap();
+    assert_eq!(set.len(), 3);
+    assert!(set.contains("fig").unwrap());
+    let words: Vec<String> = set.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(words, ["apple", "fig", "pear"]);
+    drop(set);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, Error};
+
+#[test]
+fn snapshot_keeps_the_old_data() {
+    let dir = common::temp_dir();
+    let snapshot_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..500u64 {
+        tree.insert(key, key).unwrap();
+    }
+
+    let mut snapshot = tree.snapshot(snapshot_dir.clone()).unwrap();
+    // Change every node of the live tree, and delete some of them.
+    for key in 0..500 {
+        tree.insert(key, key + 1).unwrap();
+    }
+    for key in (0..500).step_by(3) {
+        tree.remove(&key).unwrap();
+    }
+    tree.insert(1000, 1000).unwrap();
+    tree.flush().unwrap();
+    tree.gc().unwrap();
+
+    snapshot.validate().unwrap();
+    assert_eq!(snapshot.len(), 500);
+    let entries: Vec<(u64, u64)> = snapshot.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(entries, (0..500).map(|key| (key, key)).collect::<Vec<_>>());
+    assert!(matches!(snapshot.insert(1, 1), Err(Error::ReadOnly)));
+
+    tree.validate().unwrap();
+    assert_eq!(tree.get(&1).unwrap(), Some(2));
+    assert_eq!(tree.get(&3).unwrap(), None);
+    drop(snapshot);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(snapshot_dir).unwrap();
+}
+
+#[test]
+fn snapshot_can_be_reopened() {
+    let dir = common::temp_dir();
+    let snapshot_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+    drop(tree.snapshot(snapshot_dir.clone()).unwrap());
+    drop(tree);
+    fs::remove_dir_all(&dir).unwrap();
+
+    // With the tree it came from gone, the snapshot opens like any other tree.
+    let mut snapshot = BTree::<u64, u64>::open_read_only(snapshot_dir.clone()).unwrap();
+    snapshot.validate().unwrap();
+    assert_eq!(snapshot.get(&42).unwrap(), Some(42));
+    drop(snapshot);
+
+    fs::remove_dir_all(snapshot_dir).unwrap();
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use btree::BTree;
+use serde::Deserialize;
+
+/// Mirrors the layout of the crate's node files, with the values read from the file next to
+/// each node's own.
+#[derive(Deserialize)]
+struct NodeData {
+    capacity: usize,
+    keys: Vec<u64>,
+    #[serde(skip)]
+    values: Vec<u64>,
+    children: Option<Vec<PathBuf>>,
+}
+
+/// Each file starts with a 4 byte checksum, which is skipped.
+fn read_node(path: &Path) -> NodeData {
+    let mut node: NodeData = rmp_serde::from_slice(&fs::read(path).unwrap()[4..]).unwrap();
+    let values_path = format!("{}.values", path.display());
+    node.values = rmp_serde::from_slice(&fs::read(values_path).unwrap()[4..]).unwrap();
+
+    node
+}
+
+fn tree_with(dir: &Path, keys: impl IntoIterator<Item = u64>) -> BTree<u64, u64> {
+    let mut tree = BTree::new(dir.to_path_buf(), 5).unwrap();
+    for key in keys {
+        tree.insert(key, key * 10).unwrap();
+    }
+    tree.flush().unwrap();
+
+    tree
+}
+
+#[test]
+fn split_leaf() {
+    let dir = common::temp_dir();
+    let _tree = tree_with(&dir, 0..6);
+
+    let root = read_node(&dir.join("root"));
+    assert_eq!(root.capacity, 5);
+    assert_eq!(root.keys, vec![2]);
+    assert_eq!(root.values, vec![20]);
+
+    let children = root.children.unwrap();
+    assert_eq!(children.len(), 2);
+    let left = read_node(&children[0]);
+    assert_eq!(left.keys, vec![0, 1]);
+    assert_eq!(left.values, vec![0, 10]);
+    assert!(left.children.is_none());
+    let right = read_node(&children[1]);
+    assert_eq!(right.keys, vec![3, 4, 5]);
+    assert_eq!(right.values, vec![30, 40, 50]);
+    assert!(right.children.is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn split_internal_node() {
+    // Ascending inserts fill the root with [2, 5, 8, 11, 14] after 18 keys, so the 19th splits
+    // the root, which is now an internal node, around 8.
+    let dir = common::temp_dir();
+    let _tree = tree_with(&dir, 0..19);
+
+    let root = read_node(&dir.join("root"));
+    assert_eq!(root.keys, vec![8]);
+    assert_eq!(root.values, vec![80]);
+
+    let children = root.children.unwrap();
+    assert_eq!(children.len(), 2);
+    let left = read_node(&children[0]);
+    assert_eq!(left.keys, vec![2, 5]);
+    assert_eq!(left.values, vec![20, 50]);
+    let right = read_node(&children[1]);
+    assert_eq!(right.keys, vec![11, 14]);
+    assert_eq!(right.values, vec![110, 140]);
+
+    let left_leaves: Vec<_> = left
+        .children
+        .unwrap()
+        .iter()
+        .map(|c| read_node(c))
+        .collect();
+    let right_leaves: Vec<_> = right
+        .children
+        .unwrap()
+        .iter()
+        .map(|c| read_node(c))
+        .collect();
+    let keys = |leaves: &[NodeData]| -> Vec<Vec<u64>> {
+        leaves.iter().map(|leaf| leaf.keys.clone()).collect()
+    };
+    assert_eq!(keys(&left_leaves), vec![vec![0, 1], vec![3, 4], vec![6, 7]]);
+    assert_eq!(
+        keys(&right_leaves),
+        vec![vec![9, 10], vec![12, 13], vec![15, 16, 17, 18]]
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn split_root_over_saved_root() {
+    // The full root is already on disk when it splits, so the new root has to replace its file
+    // rather than be created alongside it.
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, 0..5);
+    assert!(read_node(&dir.join("root")).children.is_none());
+
+    tree.insert(5, 50).unwrap();
+    tree.flush().unwrap();
+    let root = read_node(&dir.join("root"));
+    assert_eq!(root.keys, vec![2]);
+    assert_eq!(root.values, vec![20]);
+    let children = root.children.unwrap();
+    assert_eq!(children.len(), 2);
+    assert!(children.iter().all(|child| !child.ends_with("root")));
+    assert_eq!(read_node(&children[0]).keys, vec![0, 1]);
+    assert_eq!(read_node(&children[1]).keys, vec![3, 4, 5]);
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn split_root_leaves_no_stale_files() {
+    // Splitting the root moves its contents to a fresh node file and writes the new root over
+    // the old one's file, so nothing of the old root may linger.
+    let dir = common::temp_dir();
+    let _tree = tree_with(&dir, 0..6);
+
+    let mut nodes: Vec<PathBuf> = read_node(&dir.join("root")).children.unwrap();
+    nodes.push(dir.join("root"));
+    let mut expected: Vec<PathBuf> = nodes
+        .iter()
+        .flat_map(|node| {
+            [
+                node.clone(),
+                PathBuf::from(format!("{}.values", node.display())),
+            ]
+        })
+        .collect();
+    expected.push(dir.join("meta"));
+    expected.push(dir.join("LOCK"));
+    expected.sort();
+    let mut found: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    found.sort();
+    assert_eq!(found, expected);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// The number of node files in `dir` once `tree` is flushed.
+fn node_count(tree: &mut BTree<u64, u64>, dir: &Path) -> usize {
+    tree.flush().unwrap();
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_none() && !path.ends_with("meta") && !path.ends_with("LOCK")
+        })
+        .count()
+}
+
+#[test]
+fn split_exactly_at_capacity() {
+    let dir = common::temp_dir();
+    let mut tree = tree_with(&dir, 0..4);
+    assert_eq!(node_count(&mut tree, &dir), 1);
+
+    // Capacity has to survive a round trip through the node file, so check the boundary on a
+    // reopened tree where the root was loaded from disk.
+    drop(tree);
+    tree = BTree::open(dir.clone()).unwrap();
+    tree.insert(4, 40).unwrap();
+    assert_eq!(node_count(&mut tree, &dir), 1);
+    tree.insert(5, 50).unwrap();
+    assert_eq!(node_count(&mut tree, &dir), 3);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+#[test]
+fn split_off_moves_the_upper_half() {
+    let dir = common::temp_dir();
+    let other_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..1000u64 {
+        tree.insert(key, key * 2).unwrap();
+    }
+
+    let mut upper = tree.split_off(&500, other_dir.clone()).unwrap();
+    tree.validate().unwrap();
+    upper.validate().unwrap();
+    assert_eq!(tree.len(), 500);
+    assert_eq!(upper.len(), 500);
+
+    let lower: Vec<(u64, u64)> = tree.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        lower,
+        (0..500).map(|key| (key, key * 2)).collect::<Vec<_>>()
+    );
+    let entries: Vec<(u64, u64)> = upper.iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        entries,
+        (500..1000).map(|key| (key, key * 2)).collect::<Vec<_>>()
+    );
+    upper.close().unwrap();
+
+    // The new tree lives in the directory it was given.
+    let mut upper = BTree::<u64, u64>::open(other_dir.clone()).unwrap();
+    assert_eq!(upper.get(&750).unwrap(), Some(1500));
+    assert_eq!(upper.get(&250).unwrap(), None);
+    drop(upper);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(other_dir).unwrap();
+}
+
+#[test]
+fn split_off_past_the_end_leaves_everything() {
+    let dir = common::temp_dir();
+    let other_dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 3).unwrap();
+    for key in 0..50u64 {
+        tree.insert(key, key).unwrap();
+    }
+
+    let mut upper = tree.split_off(&50, other_dir.clone()).unwrap();
+    assert!(upper.is_empty());
+    upper.validate().unwrap();
+    assert_eq!(tree.len(), 50);
+    drop(upper);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(other_dir).unwrap();
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::fs;
+
+use btree::{BTree, DiskUsage, TreeStats};
+use uuid::Uuid;
+
+#[test]
+fn stats_of_empty_tree() {
+    let mut tree: BTree<u64, u64> = BTree::new_in_memory(5).unwrap();
+    assert_eq!(
+        tree.stats().unwrap(),
+        TreeStats {
+            height: 1,
+            node_count: 1,
+            leaf_count: 1,
+            entry_count: 0,
+            average_fill: 0.0,
+        }
+    );
+}
+
+#[test]
+fn stats_of_split_tree() {
+    // Six keys into a tree of capacity five leave `2` in the root, `0..2` in the left leaf and
+    // `3..6` in the right one.
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    for key in 0..6u64 {
+        tree.insert(key, key).unwrap();
+    }
+
+    let stats = tree.stats().unwrap();
+    assert_eq!(stats.height, 2);
+    assert_eq!(stats.node_count, 3);
+    assert_eq!(stats.leaf_count, 2);
+    assert_eq!(stats.entry_count, 6);
+    assert!((stats.average_fill - 6.0 / 15.0).abs() < 1e-9);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stats_of_bulk_loaded_tree() {
+    // Leaves of four keys with a separator between each pair: 24 keys make five leaves under a
+    // root of four, and 124 make 25 leaves under five internal nodes of four under a root.
+    for (len, height, node_count) in [(24, 2, 6), (124, 3, 31)] {
+        let dir = common::temp_dir();
+        let mut tree =
+            BTree::build_sorted(dir.clone(), 5, (0..len).map(|key: u64| (key, key))).unwrap();
+
+        let stats = tree.stats().unwrap();
+        assert_eq!(stats.height, height);
+        assert_eq!(stats.node_count, node_count);
+        assert_eq!(stats.entry_count, len as usize);
+        assert!((stats.average_fill - 0.8).abs() < 1e-9);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn fill_histogram_of_bulk_loaded_tree() {
+    // As above, 124 keys make 25 leaves of four under five internal nodes of four under a root
+    // of four.
+    let dir = common::temp_dir();
+    let mut tree =
+        BTree::build_sorted(dir.clone(), 5, (0..124).map(|key: u64| (key, key))).unwrap();
+    let levels = tree.fill_histogram().unwrap();
+    let counts: Vec<_> = levels
+        .iter()
+        .map(|level| (level.node_count, level.key_count))
+        .collect();
+    assert_eq!(counts, [(1, 4), (5, 20), (25, 100)]);
+    assert!(levels
+        .iter()
+        .all(|level| (level.average_fill - 0.8).abs() < 1e-9));
+
+    // Emptying most of the leaves shows up at the bottom level first.
+    for key in (0..124).filter(|key| key % 5 != 0) {
+        tree.remove(&key).unwrap();
+    }
+    let levels = tree.fill_histogram().unwrap();
+    assert_eq!(
+        levels.iter().map(|level| level.key_count).sum::<usize>(),
+        25
+    );
+    assert!(levels.last().unwrap().average_fill < 0.8);
+    assert_eq!(tree.stats().unwrap().height, levels.len());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn disk_usage_of_known_data() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 15).unwrap();
+    // About a megabyte of values, which outweighs the keys and everything else in the files.
+    for key in 0..1000u64 {
+        tree.insert(key, "x".repeat(1000)).unwrap();
+    }
+    tree.flush().unwrap();
+
+    let usage = tree.disk_usage().unwrap();
+    assert!((1_000_000..1_200_000).contains(&usage.node_bytes));
+    assert!(usage.meta_bytes > 0);
+    assert_eq!(usage.wal_bytes, 0);
+    assert_eq!(usage.orphaned_bytes, 0);
+    let on_disk: u64 = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum();
+    assert_eq!(usage.total_bytes(), on_disk);
+
+    // A copy of the root under a node name of its own, which nothing refers to.
+    let orphan = dir.join(Uuid::new_v4().to_string());
+    fs::copy(dir.join("root"), &orphan).unwrap();
+    let orphaned = fs::metadata(&orphan).unwrap().len();
+    assert_eq!(
+        tree.disk_usage().unwrap(),
+        DiskUsage {
+            orphaned_bytes: orphaned,
+            ..usage
+        }
+    );
+    tree.gc().unwrap();
+    assert_eq!(tree.disk_usage().unwrap(), usage);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
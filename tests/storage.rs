@@ -0,0 +1,58 @@
+mod common;
+
+use std::fs;
+
+use btree::BTree;
+
+/// Inserts, overwrites, lookups and removals across enough keys to split and merge nodes.
+fn exercise(tree: &mut BTree<u64, u64>) {
+    for i in 0..500 {
+        let key = (i * 7919) % 500;
+        assert_eq!(tree.insert(key, key).unwrap(), None);
+    }
+    for key in (0..500).step_by(5) {
+        assert_eq!(tree.insert(key, key + 1).unwrap(), Some(key));
+    }
+    for key in (0..500).step_by(2) {
+        let expected = if key % 5 == 0 { key + 1 } else { key };
+        assert_eq!(tree.remove(&key).unwrap(), Some(expected));
+    }
+
+    assert_eq!(tree.len(), 250);
+    for key in 0..500 {
+        let expected = match key {
+            _ if key % 2 == 0 => None,
+            _ if key % 5 == 0 => Some(key + 1),
+            _ => Some(key),
+        };
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+    let keys: Vec<u64> = tree.iter().map(|entry| entry.unwrap().0).collect();
+    assert_eq!(keys, (1..500).step_by(2).collect::<Vec<_>>());
+}
+
+#[test]
+fn file_storage() {
+    let dir = common::temp_dir();
+    let mut tree = BTree::new(dir.clone(), 5).unwrap();
+    exercise(&mut tree);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn memory_storage() {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    // Evictions have to be written back to memory too.
+    tree.set_cache_capacity(1).unwrap();
+    exercise(&mut tree);
+    tree.flush().unwrap();
+}
+
+#[test]
+fn memory_storage_with_write_ahead_log() {
+    let mut tree = BTree::new_in_memory(5).unwrap();
+    tree.set_write_ahead_log(true).unwrap();
+    exercise(&mut tree);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::fs;
+use std::thread;
+
+use btree::{BTreeBuilder, SyncBTree};
+
+const KEYS: u64 = 400;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn readers_run_alongside_a_writer() {
+    assert_send_sync::<SyncBTree<u64, String>>();
+
+    let dir = common::temp_dir();
+    // A cache this small means most lookups read nodes from storage rather than the cache.
+    let tree = BTreeBuilder::new(dir.clone())
+        .capacity(3)
+        .cache_capacity(4)
+        .build()
+        .unwrap();
+    let tree = SyncBTree::from(tree);
+    for key in (0..KEYS).step_by(2) {
+        tree.insert(key, key.to_string()).unwrap();
+    }
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for key in (1..KEYS).step_by(2) {
+                tree.insert(key, key.to_string()).unwrap();
+            }
+        });
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..5 {
+                    for key in 0..KEYS {
+                        match tree.get(&key).unwrap() {
+                            Some(value) => assert_eq!(value, key.to_string()),
+                            // Only the keys the writer is inserting can be missing.
+                            None => assert_eq!(key % 2, 1),
+                        }
+                    }
+                    let entries = tree.range(100..200).unwrap();
+                    assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+                    for (key, value) in &entries {
+                        assert!((100..200).contains(key));
+                        assert_eq!(*value, key.to_string());
+                    }
+                    let evens = entries.iter().filter(|(key, _)| key % 2 == 0).count();
+                    assert_eq!(evens, 50);
+                }
+            });
+        }
+    });
+
+    assert_eq!(tree.len(), KEYS as usize);
+    assert_eq!(tree.range(..).unwrap().len(), KEYS as usize);
+    assert!(tree.contains_key(&(KEYS - 1)).unwrap());
+    let mut tree = tree.into_inner();
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lookups_see_unflushed_changes() {
+    let dir = common::temp_dir();
+    let tree = SyncBTree::new(dir.clone(), 3).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, key).unwrap();
+    }
+    assert_eq!(tree.remove(&50).unwrap(), Some(50));
+
+    assert_eq!(tree.get(&49).unwrap(), Some(49));
+    assert_eq!(tree.get(&50).unwrap(), None);
+    assert_eq!(
+        tree.range(48..=52).unwrap(),
+        [(48, 48), (49, 49), (51, 51), (52, 52)]
+    );
+    tree.lock().set_cache_capacity(0).unwrap();
+    assert_eq!(tree.get(&99).unwrap(), Some(99));
+    assert_eq!(tree.range(..).unwrap().len(), 99);
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
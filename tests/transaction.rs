@@ -0,0 +1,141 @@
+mod common;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, Error};
+
+const KEYS: u64 = 100;
+
+fn tree(dir: &Path, write_ahead_log: bool, copy_on_write: bool) -> BTree<u64, u64> {
+    let mut tree = BTreeBuilder::new(dir.to_path_buf())
+        .capacity(3)
+        .cache_capacity(4)
+        .write_ahead_log(write_ahead_log)
+        .copy_on_write(copy_on_write)
+        .build()
+        .unwrap();
+    for key in (0..KEYS).step_by(2) {
+        tree.insert(key, key).unwrap();
+    }
+
+    tree
+}
+
+#[test]
+fn failed_transaction_changes_nothing() {
+    for (write_ahead_log, copy_on_write) in [(false, false), (true, false), (false, true)] {
+        let dir = common::temp_dir();
+        let mut tree = tree(&dir, write_ahead_log, copy_on_write);
+        let result = tree.transaction(|txn| {
+            for key in (1..KEYS).step_by(2) {
+                txn.insert(key, key)?;
+            }
+            for key in (0..KEYS).step_by(4) {
+                txn.remove(&key)?;
+            }
+            assert_eq!(txn.get(&1)?, Some(1));
+            assert!(!txn.contains_key(&0)?);
+
+            Err(Error::Invalid("giving up".to_string()))
+        });
+        assert!(matches!(result, Err(Error::Invalid(_))));
+
+        let check = |tree: &mut BTree<u64, u64>| {
+            tree.validate().unwrap();
+            assert_eq!(tree.len(), KEYS as usize / 2);
+            for key in 0..KEYS {
+                let expected = Some(key).filter(|key| key % 2 == 0);
+                assert_eq!(tree.get(&key).unwrap(), expected);
+            }
+        };
+        check(&mut tree);
+        // The tree goes on working as before.
+        tree.insert(1, 1).unwrap();
+        tree.remove(&1).unwrap();
+        tree.close().unwrap();
+
+        let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+        check(&mut tree);
+        drop(tree);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn transaction_commits_every_change() {
+    for (write_ahead_log, copy_on_write) in [(false, false), (true, false), (false, true)] {
+        let dir = common::temp_dir();
+        let mut tree = tree(&dir, write_ahead_log, copy_on_write);
+        tree.flush().unwrap();
+        let version = tree.current_version();
+        tree.transaction(|txn| {
+            for key in (1..KEYS).step_by(2) {
+                txn.insert(key, key)?;
+            }
+            txn.remove(&0)?;
+            assert_eq!(txn.len(), KEYS as usize - 1);
+
+            Ok(())
+        })
+        .unwrap();
+        // Every change went into the one version.
+        assert_eq!(tree.current_version(), version + 1);
+        drop(tree);
+
+        let mut tree = BTree::<u64, u64>::open(dir.clone()).unwrap();
+        tree.validate().unwrap();
+        assert_eq!(tree.len(), KEYS as usize - 1);
+        assert_eq!(tree.get(&0).unwrap(), None);
+        for key in 1..KEYS {
+            assert_eq!(tree.get(&key).unwrap(), Some(key));
+        }
+        drop(tree);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn failed_transaction_leaves_node_names_alone() {
+    let build = |dir: &Path, fail_transaction: bool| {
+        let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.to_path_buf())
+            .capacity(3)
+            .deterministic_names(true)
+            .build()
+            .unwrap();
+        for key in 0..KEYS {
+            tree.insert(key, key).unwrap();
+        }
+        if fail_transaction {
+            // Enough inserts to split nodes, and so name new ones, none of which is kept.
+            let result = tree.transaction(|txn| {
+                for key in KEYS..2 * KEYS {
+                    txn.insert(key, key)?;
+                }
+                Err(Error::Invalid("giving up".to_string()))
+            });
+            assert!(result.is_err());
+        }
+        for key in 2 * KEYS..3 * KEYS {
+            tree.insert(key, key).unwrap();
+        }
+        tree.close().unwrap();
+
+        let names: BTreeSet<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        fs::remove_dir_all(dir).unwrap();
+
+        names
+    };
+
+    // The nodes made after the transaction are named just as if it had never been tried.
+    let with_transaction = build(&common::temp_dir(), true);
+    let without_transaction = build(&common::temp_dir(), false);
+    assert!(with_transaction.contains("node-0000001"));
+    assert_eq!(with_transaction, without_transaction);
+}
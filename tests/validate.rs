@@ -0,0 +1,105 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use btree::{BTree, Error};
+use rmp_serde::Serializer;
+use serde::{Deserialize, Serialize};
+
+/// Mirrors the layout of the crate's node files, which leave out the values.
+#[derive(Deserialize, Serialize)]
+struct NodeData {
+    capacity: usize,
+    keys: Vec<u64>,
+    children: Option<Vec<PathBuf>>,
+}
+
+/// Each file starts with a 4 byte checksum of the rest of it.
+fn read_node(path: &Path) -> NodeData {
+    rmp_serde::from_slice(&fs::read(path).unwrap()[4..]).unwrap()
+}
+
+fn write_file<T: Serialize>(path: &Path, value: &T) {
+    let mut buf = Vec::new();
+    value.serialize(&mut Serializer::new(&mut buf)).unwrap();
+    let mut file = crc32fast::hash(&buf).to_le_bytes().to_vec();
+    file.extend(buf);
+    fs::write(path, file).unwrap();
+}
+
+/// Six keys into a tree of capacity five leave `2` in the root, `0..2` in the left leaf and
+/// `3..6` in the right one. Return the path of the left leaf.
+fn small_tree(dir: &Path) -> PathBuf {
+    let mut tree = BTree::new(dir.to_path_buf(), 5).unwrap();
+    for key in 0..6u64 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.validate().unwrap();
+    tree.close().unwrap();
+
+    read_node(&dir.join("root")).children.unwrap()[0].clone()
+}
+
+/// Change the keys of the leaf at `path`, giving each a value, and return the problem
+/// `validate` finds with them.
+fn problem_with_keys(dir: &Path, path: &Path, keys: Vec<u64>) -> String {
+    write_file(Path::new(&format!("{}.values", path.display())), &keys);
+    let mut leaf = read_node(path);
+    leaf.keys = keys;
+    write_file(path, &leaf);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.to_path_buf()).unwrap();
+    match tree.validate() {
+        Err(Error::Invalid(problem)) => problem,
+        result => panic!("expected the tree to be invalid, got {:?}", result),
+    }
+}
+
+#[test]
+fn validate_changing_trees() {
+    for capacity in [3, 4, 5, 8] {
+        let mut tree = BTree::new_in_memory(capacity).unwrap();
+        for i in 0..500u64 {
+            tree.insert((i * 7919) % 500, i).unwrap();
+            tree.validate().unwrap();
+        }
+        for i in 0..500u64 {
+            tree.remove(&((i * 4177) % 500)).unwrap();
+            tree.validate().unwrap();
+        }
+    }
+}
+
+#[test]
+fn validate_finds_unsorted_keys() {
+    let dir = common::temp_dir();
+    let leaf = small_tree(&dir);
+
+    let problem = problem_with_keys(&dir, &leaf, vec![1, 0]);
+    assert!(problem.contains("out of order"), "{}", problem);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn validate_finds_keys_beyond_separators() {
+    let dir = common::temp_dir();
+    let leaf = small_tree(&dir);
+
+    let problem = problem_with_keys(&dir, &leaf, vec![0, 2]);
+    assert!(problem.contains("separator after it"), "{}", problem);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn validate_finds_underfull_nodes() {
+    let dir = common::temp_dir();
+    let leaf = small_tree(&dir);
+
+    let problem = problem_with_keys(&dir, &leaf, vec![0]);
+    assert!(problem.contains("fewer than 2 keys"), "{}", problem);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use btree::{BTree, BTreeBuilder};
+
+const VALUE_SIZE: usize = 4096;
+
+/// A closed tree of 4KB values, big enough to have a few levels.
+fn tree_with_large_values(dir: &Path) {
+    let mut tree = BTree::new(dir.to_path_buf(), 5).unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, vec![key as u8; VALUE_SIZE]).unwrap();
+    }
+    tree.close().unwrap();
+}
+
+fn files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect()
+}
+
+fn total_size<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> u64 {
+    paths.map(|path| fs::metadata(path).unwrap().len()).sum()
+}
+
+#[test]
+fn contains_key_reads_no_values() {
+    let dir = common::temp_dir();
+    tree_with_large_values(&dir);
+
+    // Reading a whole node used to mean reading its values too, so a search read every byte of
+    // both files of each node on its way down. Now it only reads the keys.
+    let files = files(&dir);
+    let is_values = |path: &&PathBuf| path.extension().is_some_and(|ext| ext == "values");
+    let keys_size = total_size(files.iter().filter(|path| !is_values(path)));
+    let values_size = total_size(files.iter().filter(is_values));
+    assert!(values_size > 100 * VALUE_SIZE as u64);
+    assert!(keys_size * 50 < values_size);
+
+    // With every values file below the root gone, only a lookup that needs a value can fail.
+    for path in files.iter().filter(is_values) {
+        if !path.ends_with("root.values") {
+            fs::remove_file(path).unwrap();
+        }
+    }
+    let mut tree: BTree<u64, Vec<u8>> = BTree::open(dir.clone()).unwrap();
+    for key in 0..100 {
+        assert!(tree.contains_key(&key).unwrap());
+    }
+    assert!(!tree.contains_key(&100).unwrap());
+    assert_eq!(tree.keys().count(), 100);
+    assert!(tree.get(&0).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn values_load_once_needed() {
+    let dir = common::temp_dir();
+    tree_with_large_values(&dir);
+
+    let mut tree: BTree<u64, Vec<u8>> = BTree::open(dir.clone()).unwrap();
+    for key in 0..100u64 {
+        assert!(tree.contains_key(&key).unwrap());
+        assert_eq!(tree.get(&key).unwrap(), Some(vec![key as u8; VALUE_SIZE]));
+    }
+    tree.close().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn large_values_get_files_of_their_own() {
+    let dir = common::temp_dir();
+    // Even keys get a few bytes, and odd ones 4KB, well past the threshold.
+    let value = |key: u64| vec![key as u8; if key.is_multiple_of(2) { 4 } else { VALUE_SIZE }];
+    let mut tree = BTreeBuilder::new(dir.clone())
+        .capacity(5)
+        .external_values(64)
+        .build()
+        .unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, value(key)).unwrap();
+    }
+    tree.close().unwrap();
+
+    let written = files(&dir);
+    let is_external = |path: &&PathBuf| path.to_string_lossy().contains(".value-");
+    assert_eq!(written.iter().filter(is_external).count(), 50);
+    for path in written.iter().filter(|path| !is_external(path)) {
+        assert!(
+            fs::metadata(path).unwrap().len() < 1024,
+            "{}",
+            path.display()
+        );
+    }
+
+    let mut tree: BTree<u64, Vec<u8>> = BTree::open(dir.clone()).unwrap();
+    for key in 0..100u64 {
+        assert_eq!(tree.get(&key).unwrap(), Some(value(key)));
+    }
+    // The files of values that are replaced or removed go with them.
+    tree.insert(1, vec![0; 4]).unwrap();
+    tree.remove(&3).unwrap();
+    tree.insert(0, vec![0; VALUE_SIZE]).unwrap();
+    tree.flush().unwrap();
+    assert_eq!(tree.gc().unwrap(), 0);
+    assert_eq!(files(&dir).iter().filter(is_external).count(), 49);
+    assert_eq!(tree.get(&0).unwrap(), Some(vec![0; VALUE_SIZE]));
+    assert_eq!(tree.get(&1).unwrap(), Some(vec![0; 4]));
+    tree.validate().unwrap();
+    assert!(tree.scrub().unwrap().is_clean());
+    tree.close().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unchanged_large_values_keep_their_files() {
+    let dir = common::temp_dir();
+    let mut tree = BTreeBuilder::new(dir.clone())
+        .capacity(5)
+        .external_values(64)
+        .build()
+        .unwrap();
+    for key in 0..100u64 {
+        tree.insert(key, vec![key as u8; VALUE_SIZE]).unwrap();
+    }
+    tree.close().unwrap();
+    let external = |dir: &Path| -> HashSet<PathBuf> {
+        let is_external = |path: &PathBuf| path.to_string_lossy().contains(".value-");
+        files(dir).into_iter().filter(is_external).collect()
+    };
+    let before = external(&dir);
+    assert_eq!(before.len(), 100);
+
+    // Changing one value of a leaf writes that value out again, and none of the others.
+    let mut tree: BTree<u64, Vec<u8>> = BTree::open(dir.clone()).unwrap();
+    tree.insert(41, vec![0; VALUE_SIZE]).unwrap();
+    tree.flush().unwrap();
+    let after = external(&dir);
+    assert_eq!(before.difference(&after).count(), 1);
+    assert_eq!(after.difference(&before).count(), 1);
+    // A value replaced by one that is the same keeps its file too.
+    tree.insert(42, vec![42; VALUE_SIZE]).unwrap();
+    tree.flush().unwrap();
+    assert_eq!(external(&dir), after);
+
+    assert_eq!(tree.gc().unwrap(), 0);
+    assert_eq!(tree.get(&41).unwrap(), Some(vec![0; VALUE_SIZE]));
+    assert_eq!(tree.get(&42).unwrap(), Some(vec![42; VALUE_SIZE]));
+    tree.validate().unwrap();
+    assert!(tree.scrub().unwrap().is_clean());
+    tree.close().unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
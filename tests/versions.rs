@@ -0,0 +1,184 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use btree::{BTree, BTreeBuilder, Error, Reader};
+
+fn open(dir: &Path) -> BTree<u64, u64> {
+    BTreeBuilder::new(dir.to_path_buf())
+        .capacity(3)
+        .copy_on_write(true)
+        .version_history(1000)
+        .build()
+        .unwrap()
+}
+
+fn contents(reader: &Reader<u64, u64>) -> Vec<(u64, u64)> {
+    reader.iter().map(Result::unwrap).collect()
+}
+
+#[test]
+fn snapshots_of_each_version() {
+    let dir = common::temp_dir();
+    let mut tree = open(&dir);
+    let empty = tree.current_version();
+
+    for key in 0..50 {
+        tree.insert(key, key).unwrap();
+    }
+    let first = tree.current_version();
+    for key in 50..100 {
+        tree.insert(key, key).unwrap();
+    }
+    for key in (0..100).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    let second = tree.current_version();
+    for key in 0..100 {
+        tree.insert(key, key * 10).unwrap();
+    }
+    let third = tree.current_version();
+    assert!(empty < first && first < second && second < third);
+
+    let check = |tree: &BTree<u64, u64>| {
+        assert!(contents(&tree.snapshot_at(empty).unwrap()).is_empty());
+        let reader = tree.snapshot_at(first).unwrap();
+        assert_eq!(reader.version(), first);
+        assert_eq!(reader.len(), 50);
+        assert_eq!(
+            contents(&reader),
+            (0..50).map(|key| (key, key)).collect::<Vec<_>>()
+        );
+        let odd: Vec<_> = (1..100).step_by(2).map(|key| (key, key)).collect();
+        assert_eq!(contents(&tree.snapshot_at(second).unwrap()), odd);
+        let all: Vec<_> = (0..100).map(|key| (key, key * 10)).collect();
+        assert_eq!(contents(&tree.snapshot_at(third).unwrap()), all);
+    };
+    check(&tree);
+
+    // Versions are recorded in the meta file, so they outlive the tree being closed.
+    tree.close().unwrap();
+    let mut tree = open(&dir);
+    assert_eq!(tree.current_version(), third);
+    check(&tree);
+
+    assert!(matches!(
+        tree.snapshot_at(third + 1),
+        Err(Error::VersionUnavailable(v)) if v == third + 1
+    ));
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn released_versions_are_collected() {
+    let dir = common::temp_dir();
+    let mut tree = open(&dir);
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+    let old = tree.current_version();
+    let pinned = tree.snapshot_at(old - 10).unwrap();
+    for key in 0..100 {
+        tree.insert(key, key + 1).unwrap();
+    }
+    let current = tree.current_version();
+
+    // Nothing is released yet, so nothing is garbage.
+    assert_eq!(tree.gc().unwrap(), 0);
+    tree.release_versions_before(current).unwrap();
+    assert!(matches!(
+        tree.snapshot_at(old),
+        Err(Error::VersionUnavailable(_))
+    ));
+    assert!(tree.gc().unwrap() > 0);
+
+    // A reader of a released version still holds on to its files.
+    assert_eq!(pinned.len(), 90);
+    assert_eq!(contents(&pinned).len(), 90);
+    drop(pinned);
+    assert!(tree.gc().unwrap() > 0);
+
+    let reader = tree.snapshot_at(current).unwrap();
+    assert_eq!(
+        contents(&reader),
+        (0..100).map(|key| (key, key + 1)).collect::<Vec<_>>()
+    );
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn versions_without_copy_on_write() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 3).unwrap();
+    let start = tree.current_version();
+    tree.insert(1, 1).unwrap();
+    tree.insert(2, 2).unwrap();
+    tree.flush().unwrap();
+    // Every change between two flushes makes up one version.
+    assert_eq!(tree.current_version(), start + 1);
+    tree.flush().unwrap();
+    assert_eq!(tree.current_version(), start + 1);
+    assert!(matches!(
+        tree.snapshot_at(start),
+        Err(Error::NotCopyOnWrite)
+    ));
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn versions_are_only_kept_when_asked_for() {
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTreeBuilder::new(dir.clone())
+        .capacity(8)
+        .copy_on_write(true)
+        .build()
+        .unwrap();
+    for key in 0..2000 {
+        tree.insert(key, key).unwrap();
+    }
+    let current = tree.current_version();
+    assert!(matches!(
+        tree.snapshot_at(current - 1),
+        Err(Error::VersionUnavailable(_))
+    ));
+
+    // With no reader and no earlier version kept, only the current version's files are left:
+    // each node's own and its values file, along with the meta and lock files.
+    assert!(tree.gc().unwrap() > 1000);
+    let nodes: usize = tree
+        .fill_histogram()
+        .unwrap()
+        .iter()
+        .map(|level| level.node_count)
+        .sum();
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2 * nodes + 2);
+    assert!(fs::metadata(dir.join("meta")).unwrap().len() < 1024);
+
+    // A bounded history keeps that many versions before the current one, and no more.
+    tree.set_version_history(5).unwrap();
+    for key in 0..20 {
+        tree.insert(key, key + 1).unwrap();
+    }
+    let current = tree.current_version();
+    assert_eq!(
+        tree.snapshot_at(current - 5).unwrap().get(&19).unwrap(),
+        Some(19)
+    );
+    assert!(matches!(
+        tree.snapshot_at(current - 6),
+        Err(Error::VersionUnavailable(_))
+    ));
+    tree.validate().unwrap();
+    drop(tree);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use btree::BTree;
+use rmp_serde::Serializer;
+use serde::Serialize;
+
+/// Mirrors the layout of the crate's node files, which leave out the values.
+#[derive(Serialize)]
+struct NodeData {
+    capacity: usize,
+    keys: Vec<u64>,
+    children: Option<Vec<PathBuf>>,
+}
+
+/// Mirrors the layout of the crate's meta file.
+#[derive(Serialize)]
+struct Meta {
+    version: u32,
+    capacity: usize,
+    merge_threshold: f32,
+    root: String,
+    len: usize,
+    free: Vec<String>,
+    encoding: Encoding,
+    naming: Naming,
+    current_version: u64,
+    // Never holds any versions here, since they are only kept with copy-on-write.
+    versions: Vec<()>,
+    bloom_filter: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Encoding {
+    codec: CodecKind,
+    compressed: bool,
+    encrypted: bool,
+    external_values: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Naming {
+    prefix: String,
+    namespace: Option<String>,
+    extension: Option<String>,
+    next_node: Option<u64>,
+}
+
+#[derive(Serialize)]
+enum CodecKind {
+    Msgpack,
+    // Never written here, but it keeps the variants lined up with the crate's.
+    #[allow(dead_code)]
+    Bincode,
+}
+
+/// Mirrors the entries of the crate's write-ahead log.
+#[derive(Serialize)]
+enum Record {
+    Write(PathBuf, NodeData, Vec<u64>),
+    // Never written here, but it keeps the variants lined up with the crate's.
+    #[allow(dead_code)]
+    Remove(PathBuf),
+    Meta(Meta),
+    Commit,
+}
+
+/// Leave behind a tree whose root holds `0..4`, along with a log of a batch that replaces the
+/// root with one holding only `100`. Return the length of the log up to its commit marker.
+fn tree_with_log(dir: &Path) -> usize {
+    let mut tree: BTree<u64, u64> = BTree::new(dir.to_path_buf(), 5).unwrap();
+    for key in 0..4 {
+        tree.insert(key, key).unwrap();
+    }
+    tree.close().unwrap();
+
+    let records = [
+        Record::Write(
+            dir.join("root"),
+            NodeData {
+                capacity: 5,
+                keys: vec![100],
+                children: None,
+            },
+            vec![100],
+        ),
+        Record::Meta(Meta {
+            version: 15,
+            capacity: 5,
+            merge_threshold: 0.5,
+            root: String::from("root"),
+            len: 1,
+            free: Vec::new(),
+            encoding: Encoding {
+                codec: CodecKind::Msgpack,
+                compressed: false,
+                encrypted: false,
+                external_values: None,
+            },
+            naming: Naming {
+                prefix: String::new(),
+                namespace: None,
+                extension: None,
+                next_node: None,
+            },
+            current_version: 2,
+            versions: Vec::new(),
+            bloom_filter: None,
+        }),
+    ];
+    let mut log = Vec::new();
+    for record in records {
+        record.serialize(&mut Serializer::new(&mut log)).unwrap();
+    }
+    let committed_at = log.len();
+    Record::Commit
+        .serialize(&mut Serializer::new(&mut log))
+        .unwrap();
+    fs::write(dir.join("wal"), log).unwrap();
+
+    committed_at
+}
+
+#[test]
+fn committed_log_is_replayed() {
+    let dir = common::temp_dir();
+    tree_with_log(&dir);
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 1);
+    assert_eq!(tree.get(&0).unwrap(), None);
+    assert_eq!(tree.get(&100).unwrap(), Some(100));
+    assert!(!dir.join("wal").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn uncommitted_log_is_discarded() {
+    for cut in [|committed_at| committed_at, |committed_at| committed_at / 2] {
+        let dir = common::temp_dir();
+        let committed_at = tree_with_log(&dir);
+        let log = fs::read(dir.join("wal")).unwrap();
+        fs::write(dir.join("wal"), &log[..cut(committed_at)]).unwrap();
+
+        let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+        assert_eq!(tree.len(), 4);
+        for key in 0..4 {
+            assert_eq!(tree.get(&key).unwrap(), Some(key));
+        }
+        assert_eq!(tree.get(&100).unwrap(), None);
+        assert!(!dir.join("wal").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn logged_changes_survive_without_a_flush() {
+    // A tiny cache forces evictions in the middle of splits and merges, which the log has to hold
+    // back until the change is committed.
+    let dir = common::temp_dir();
+    let mut tree: BTree<u64, u64> = BTree::new(dir.clone(), 5).unwrap();
+    tree.set_cache_capacity(1).unwrap();
+    tree.set_write_ahead_log(true).unwrap();
+    for i in 0..300 {
+        let key = (i * 7919) % 300;
+        tree.insert(key, key).unwrap();
+    }
+    for key in (0..300).step_by(2) {
+        tree.remove(&key).unwrap();
+    }
+    assert!(!dir.join("wal").exists());
+    // Skip the flush on drop, as a crash would. A crash would release the lock too, which the
+    // forgotten tree still holds, so the lock file goes instead.
+    mem::forget(tree);
+    fs::remove_file(dir.join("LOCK")).unwrap();
+
+    let mut tree: BTree<u64, u64> = BTree::open(dir.clone()).unwrap();
+    assert_eq!(tree.len(), 150);
+    for key in 0..300 {
+        let expected = if key % 2 == 1 { Some(key) } else { None };
+        assert_eq!(tree.get(&key).unwrap(), expected);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}